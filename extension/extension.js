@@ -1,16 +1,118 @@
+import Clutter from 'gi://Clutter';
 import Gio from 'gi://Gio';
 import GLib from 'gi://GLib';
 import Meta from 'gi://Meta';
 import Shell from 'gi://Shell';
+import St from 'gi://St';
 import * as Main from 'resource:///org/gnome/shell/ui/main.js';
 
 import {Extension} from 'resource:///org/gnome/shell/extensions/extension.js';
 
+Gio._promisify(Shell.Screenshot.prototype, 'screenshot_window');
+Gio._promisify(Gio.DBusConnection.prototype, 'call_with_unix_fd_list');
+
+// Our own name on the session bus, so the Flatpak may talk to just the extension rather than all of Shell
+const DBUS_NAME = 'org.gnome.Shell.Extensions.GnomeShot';
+const DBUS_PATH = '/org/gnome/Shell/Extensions/GnomeShot';
+const DBUS_INTERFACE = `
+<node>
+  <interface name="org.gnome.Shell.Extensions.GnomeShot">
+    <method name="CaptureWindow">
+      <arg type="h" direction="out" name="image"/>
+      <arg type="s" direction="out" name="title"/>
+      <arg type="s" direction="out" name="app_id"/>
+      <arg type="d" direction="out" name="scale"/>
+    </method>
+  </interface>
+</node>`;
+
+// Returned when the user presses Escape instead of picking a window
+const CANCELLED_ERROR = 'org.gnome.Shell.Extensions.GnomeShot.Error.Cancelled';
+
+const APP_ID = 'org.gnome.GnomeShot';
+
+// How long to wait after focusing a window before capturing it, in milliseconds
+const FOCUS_DELAY = 150;
+
+// System-wide installs of gnome-shot, which only root can replace
+const SYSTEM_LOCATIONS = [
+    '/usr/local/bin/gnome-shot',
+    '/usr/bin/gnome-shot',
+];
+
+// Where gnome-shot may be installed
+function gnomeShotLocations() {
+    return [
+        ...SYSTEM_LOCATIONS,
+        GLib.build_filenamev([GLib.get_home_dir(), '.local', 'bin', 'gnome-shot']),
+    ];
+}
+
+// The process a pidfd refers to, or null once it has exited
+function pidfdToPid(pidfd) {
+    const [, contents] = GLib.file_get_contents(`/proc/self/fdinfo/${pidfd}`);
+    const match = new TextDecoder().decode(contents).match(/^Pid:\s*(-?\d+)$/m);
+    const pid = match ? Number(match[1]) : -1;
+    return pid > 0 ? pid : null;
+}
+
+// The app id of the Flatpak sandbox a process runs in, or null if it isn't sandboxed
+//
+// Like xdg-desktop-portal, this reads /.flatpak-info from the process's root
+// and only accepts a regular file. Any program can make a bwrap sandbox with
+// such a file, so it must also match the info Flatpak keeps on the host for
+// the running instance, which no sandboxed app can write to.
+function flatpakAppId(pid) {
+    const file = Gio.File.new_for_path(`/proc/${pid}/root/.flatpak-info`);
+    let type;
+    try {
+        type = file.query_info('standard::type', Gio.FileQueryInfoFlags.NOFOLLOW_SYMLINKS, null).get_file_type();
+    } catch (e) {
+        // Not sandboxed
+        return null;
+    }
+    if (type !== Gio.FileType.REGULAR)
+        throw new Error('/.flatpak-info is not a regular file');
+
+    const [, contents] = file.load_contents(null);
+    const info = new GLib.KeyFile();
+    info.load_from_bytes(contents, GLib.KeyFileFlags.NONE);
+
+    const instanceId = info.get_string('Instance', 'instance-id');
+    if (!/^[0-9]+$/.test(instanceId))
+        throw new Error(`Unexpected Flatpak instance id '${instanceId}'`);
+    const [, hostContents] = GLib.file_get_contents(
+        GLib.build_filenamev([GLib.get_user_runtime_dir(), '.flatpak', instanceId, 'info']));
+    const decoder = new TextDecoder();
+    if (decoder.decode(contents) !== decoder.decode(hostContents))
+        throw new Error(`/.flatpak-info doesn't match Flatpak instance ${instanceId}`);
+
+    return info.get_string('Application', 'name');
+}
+
+// The topmost window under the point on the current workspace
+function windowAt(x, y) {
+    const workspace = global.workspace_manager.get_active_workspace();
+    const windows = global.get_window_actors()
+        .map(actor => actor.meta_window)
+        .filter(window => !window.minimized &&
+            window.located_on_workspace(workspace) &&
+            window.get_window_type() !== Meta.WindowType.DESKTOP)
+        .reverse();
+    return windows.find(window => {
+        const rect = window.get_frame_rect();
+        return x >= rect.x && x < rect.x + rect.width && y >= rect.y && y < rect.y + rect.height;
+    }) ?? null;
+}
+
 export default class GnomeShotExtension extends Extension {
     constructor(metadata) {
         super(metadata);
         this._settings = null;
         this._keybindings = [];
+        this._dbus = null;
+        this._dbusName = 0;
+        this._finishPick = null;
     }
 
     enable() {
@@ -18,9 +120,21 @@ export default class GnomeShotExtension extends Extension {
 
         // Register keybindings
         this._addKeybinding('capture-shortcut', () => {
-            this._launchGnomeShot();
+            this._launchGnomeShot('--capture');
+        });
+        this._addKeybinding('window-capture-shortcut', () => {
+            this._launchGnomeShot('--capture-window');
+        });
+        this._addKeybinding('preset-capture-shortcut', () => {
+            this._launchGnomeShot('--preset', this._settings.get_string('capture-preset'));
         });
 
+        // Lets the app have the user pick a window, then capture it and learn its title and app id
+        this._dbus = Gio.DBusExportedObject.wrapJSObject(DBUS_INTERFACE, this);
+        this._dbus.export(Gio.DBus.session, DBUS_PATH);
+        this._dbusName = Gio.bus_own_name_on_connection(Gio.DBus.session, DBUS_NAME,
+            Gio.BusNameOwnerFlags.NONE, null, null);
+
         console.log('GNOME Shot extension enabled');
     }
 
@@ -30,6 +144,13 @@ export default class GnomeShotExtension extends Extension {
             Main.wm.removeKeybinding(name);
         }
         this._keybindings = [];
+        this._finishPick?.(null);
+        if (this._dbusName) {
+            Gio.bus_unown_name(this._dbusName);
+            this._dbusName = 0;
+        }
+        this._dbus?.unexport();
+        this._dbus = null;
         this._settings = null;
 
         console.log('GNOME Shot extension disabled');
@@ -46,19 +167,162 @@ export default class GnomeShotExtension extends Extension {
         this._keybindings.push(name);
     }
 
-    _launchGnomeShot() {
+    // Whether the D-Bus caller is GNOME Shot, so other programs can't use
+    // the extension to capture windows without the screenshot permission
+    async _isGnomeShot(sender) {
+        const [reply, fdList] = await Gio.DBus.session.call_with_unix_fd_list(
+            'org.freedesktop.DBus',
+            '/org/freedesktop/DBus',
+            'org.freedesktop.DBus',
+            'GetConnectionCredentials',
+            new GLib.Variant('(s)', [sender]),
+            new GLib.VariantType('(a{sv})'),
+            Gio.DBusCallFlags.NONE,
+            -1,
+            null,
+            null
+        );
+        const [credentials] = reply.deepUnpack();
+
+        // A pidfd keeps referring to the caller, where its PID could be reused
+        // by another process while we look; without one, don't trust anyone
+        if (!credentials.ProcessFD || !fdList)
+            return false;
+        const pidfd = fdList.get(credentials.ProcessFD.unpack());
         try {
-            // Try to find gnome-shot in common locations
-            const locations = [
-                '/home/rb/Code/github/gnome-shot/target/debug/gnome-shot',
-                '/home/rb/Code/github/gnome-shot/target/release/gnome-shot',
-                '/usr/local/bin/gnome-shot',
-                '/usr/bin/gnome-shot',
-                GLib.build_filenamev([GLib.get_home_dir(), '.local', 'bin', 'gnome-shot']),
-            ];
+            const pid = pidfdToPid(pidfd);
+            if (pid === null)
+                return false;
+
+            // A Flatpak is identified by its app id rather than its executable
+            const appId = flatpakAppId(pid);
+            const trusted = appId !== null
+                ? appId === APP_ID
+                // Anything the user can write to could be any program, so only trust system installs
+                : SYSTEM_LOCATIONS.includes(GLib.file_read_link(`/proc/${pid}/exe`));
 
+            // What was read belongs to the caller only if it's still running
+            return trusted && pidfdToPid(pidfd) === pid;
+        } catch (e) {
+            console.error('Failed to identify the caller:', e);
+            return false;
+        } finally {
+            GLib.close(pidfd);
+        }
+    }
+
+    // Let the user click the window to capture; resolves to null on Escape
+    _pickWindow() {
+        return new Promise(resolve => {
+            const overlay = new St.Widget({
+                reactive: true,
+                can_focus: true,
+                width: global.stage.width,
+                height: global.stage.height,
+            });
+            const highlight = new St.Widget({
+                style: 'background-color: rgba(53, 132, 228, 0.25); border: 2px solid #3584e4;',
+                visible: false,
+            });
+            overlay.add_child(highlight);
+            Main.uiGroup.add_child(overlay);
+            const grab = Main.pushModal(overlay);
+            overlay.grab_key_focus();
+            global.display.set_cursor(Meta.Cursor.CROSSHAIR);
+
+            this._finishPick = window => {
+                this._finishPick = null;
+                global.display.set_cursor(Meta.Cursor.DEFAULT);
+                Main.popModal(grab);
+                overlay.destroy();
+                resolve(window);
+            };
+
+            overlay.connect('motion-event', (actor, event) => {
+                const window = windowAt(...event.get_coords());
+                if (window) {
+                    const rect = window.get_frame_rect();
+                    highlight.set_position(rect.x, rect.y);
+                    highlight.set_size(rect.width, rect.height);
+                }
+                highlight.visible = window !== null;
+                return Clutter.EVENT_STOP;
+            });
+            overlay.connect('button-release-event', (actor, event) => {
+                const window = windowAt(...event.get_coords());
+                if (window)
+                    this._finishPick(window);
+                return Clutter.EVENT_STOP;
+            });
+            overlay.connect('key-press-event', (actor, event) => {
+                if (event.get_key_symbol() === Clutter.KEY_Escape)
+                    this._finishPick(null);
+                return Clutter.EVENT_STOP;
+            });
+        });
+    }
+
+    async CaptureWindowAsync(params, invocation) {
+        try {
+            if (!await this._isGnomeShot(invocation.get_sender())) {
+                invocation.return_dbus_error('org.freedesktop.DBus.Error.AccessDenied',
+                    'Only GNOME Shot may capture windows');
+                return;
+            }
+            if (this._finishPick) {
+                invocation.return_dbus_error('org.freedesktop.DBus.Error.LimitsExceeded',
+                    'A window is already being picked');
+                return;
+            }
+
+            const window = await this._pickWindow();
+            if (!window) {
+                invocation.return_dbus_error(CANCELLED_ERROR, 'No window was picked');
+                return;
+            }
+
+            // The app may be sandboxed and unable to open our files, so it
+            // gets a descriptor of an unlinked temporary file instead of a path
+            const [fd, path] = GLib.file_open_tmp('gnome-shot-window-XXXXXX.png');
+            try {
+                const file = Gio.File.new_for_path(path);
+                // Appending writes to the same file as `fd`; replacing would swap in a new one
+                const stream = file.append_to(Gio.FileCreateFlags.PRIVATE, null);
+                file.delete(null);
+
+                // Shell captures the focused window, so bring the target forward first
+                window.activate(global.get_current_time());
+                await new Promise(resolve => GLib.timeout_add(GLib.PRIORITY_DEFAULT, FOCUS_DELAY, () => {
+                    resolve();
+                    return GLib.SOURCE_REMOVE;
+                }));
+
+                await new Shell.Screenshot().screenshot_window(true, false, stream);
+                stream.close(null);
+
+                const fdList = new Gio.UnixFDList();
+                const handle = fdList.append(fd);
+                const app = Shell.WindowTracker.get_default().get_window_app(window);
+                invocation.return_value_with_unix_fd_list(new GLib.Variant('(hssd)', [
+                    handle,
+                    window.get_title() ?? '',
+                    app?.get_id() ?? '',
+                    global.display.get_monitor_scale(window.get_monitor()),
+                ]), fdList);
+            } finally {
+                GLib.close(fd);
+            }
+        } catch (e) {
+            console.error('Failed to capture window:', e);
+            invocation.return_error_literal(Gio.DBusError, Gio.DBusError.FAILED, e.message);
+        }
+    }
+
+    _launchGnomeShot(...args) {
+        try {
+            // Try to find gnome-shot in common locations
             let gnomeShotPath = null;
-            for (const path of locations) {
+            for (const path of gnomeShotLocations()) {
                 if (GLib.file_test(path, GLib.FileTest.IS_EXECUTABLE)) {
                     gnomeShotPath = path;
                     break;
@@ -66,9 +330,9 @@ export default class GnomeShotExtension extends Extension {
             }
 
             if (gnomeShotPath) {
-                // Launch gnome-shot with --capture flag to auto-start capture
+                // Launch gnome-shot with flags to auto-start the capture
                 const subprocess = Gio.Subprocess.new(
-                    [gnomeShotPath, '--capture'],
+                    [gnomeShotPath, ...args],
                     Gio.SubprocessFlags.NONE
                 );
 
@@ -28,6 +28,6 @@ echo "  1. Log out and log back in, OR"
 echo "  2. Press Alt+F2, type 'r', press Enter (X11 only), OR"
 echo "  3. Run: gnome-extensions enable $EXTENSION_UUID"
 echo ""
-echo "Default shortcut: Shift+Super+S"
+echo "Default shortcuts: Shift+Super+S (capture), Shift+Super+W (capture window), Shift+Super+A (capture with preset)"
 echo ""
 echo "You can customize the shortcut in GNOME Extensions settings."
@@ -1,4 +1,5 @@
 import Adw from 'gi://Adw';
+import Gdk from 'gi://Gdk';
 import Gtk from 'gi://Gtk';
 import Gio from 'gi://Gio';
 
@@ -22,14 +23,38 @@ export default class GnomeShotPreferences extends ExtensionPreferences {
         });
         page.add(shortcutsGroup);
 
-        // Capture shortcut row
-        const captureRow = new Adw.ActionRow({
-            title: 'Capture Screenshot',
-            subtitle: 'Opens GNOME Shot to capture a screenshot',
+        shortcutsGroup.add(this._shortcutRow(window, settings, 'capture-shortcut',
+            'Capture Screenshot', 'Opens GNOME Shot to capture a screenshot'));
+        shortcutsGroup.add(this._shortcutRow(window, settings, 'window-capture-shortcut',
+            'Capture Window', 'Click a window to capture it with its title and app'));
+        shortcutsGroup.add(this._shortcutRow(window, settings, 'preset-capture-shortcut',
+            'Capture with Preset', 'Selects a region limited to the capture preset below'));
+
+        const presetRow = new Adw.EntryRow({
+            title: 'Capture Preset',
+            text: settings.get_string('capture-preset'),
+        });
+        settings.bind('capture-preset', presetRow, 'text', Gio.SettingsBindFlags.DEFAULT);
+        shortcutsGroup.add(presetRow);
+
+        // Info group
+        const infoGroup = new Adw.PreferencesGroup({
+            title: 'About',
+        });
+        page.add(infoGroup);
+
+        const infoRow = new Adw.ActionRow({
+            title: 'GNOME Shot',
+            subtitle: 'Screenshot and annotation tool for GNOME',
         });
+        infoGroup.add(infoRow);
+    }
+
+    _shortcutRow(window, settings, key, title, subtitle) {
+        const row = new Adw.ActionRow({title, subtitle});
 
         const shortcutLabel = new Gtk.ShortcutLabel({
-            accelerator: settings.get_strv('capture-shortcut')[0] || '',
+            accelerator: settings.get_strv(key)[0] || '',
             valign: Gtk.Align.CENTER,
         });
 
@@ -40,27 +65,15 @@ export default class GnomeShotPreferences extends ExtensionPreferences {
         });
 
         editButton.connect('clicked', () => {
-            this._showShortcutDialog(window, settings, shortcutLabel);
+            this._showShortcutDialog(window, settings, key, shortcutLabel);
         });
 
-        captureRow.add_suffix(shortcutLabel);
-        captureRow.add_suffix(editButton);
-        shortcutsGroup.add(captureRow);
-
-        // Info group
-        const infoGroup = new Adw.PreferencesGroup({
-            title: 'About',
-        });
-        page.add(infoGroup);
-
-        const infoRow = new Adw.ActionRow({
-            title: 'GNOME Shot',
-            subtitle: 'Screenshot and annotation tool for GNOME',
-        });
-        infoGroup.add(infoRow);
+        row.add_suffix(shortcutLabel);
+        row.add_suffix(editButton);
+        return row;
     }
 
-    _showShortcutDialog(window, settings, shortcutLabel) {
+    _showShortcutDialog(window, settings, key, shortcutLabel) {
         const dialog = new Gtk.Dialog({
             title: 'Set Shortcut',
             transient_for: window,
@@ -92,7 +105,7 @@ export default class GnomeShotPreferences extends ExtensionPreferences {
             }
 
             if (keyval === Gdk.KEY_BackSpace) {
-                settings.set_strv('capture-shortcut', []);
+                settings.set_strv(key, []);
                 shortcutLabel.set_accelerator('');
                 dialog.close();
                 return true;
@@ -100,7 +113,7 @@ export default class GnomeShotPreferences extends ExtensionPreferences {
 
             if (Gtk.accelerator_valid(keyval, state)) {
                 const accel = Gtk.accelerator_name(keyval, state);
-                settings.set_strv('capture-shortcut', [accel]);
+                settings.set_strv(key, [accel]);
                 shortcutLabel.set_accelerator(accel);
                 dialog.close();
             }
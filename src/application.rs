@@ -1,10 +1,17 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 use gtk::{self, gio, glib};
-use std::cell::OnceCell;
+use gtk::gdk_pixbuf::Pixbuf;
+use std::cell::{OnceCell, RefCell};
+use std::rc::Rc;
 use std::sync::OnceLock;
 
 use crate::capture;
+use crate::combine_dialog::CombineDialog;
+use crate::ocr;
+use crate::recording_dialog::{self, RecordingDialog};
+use crate::region_selector::RegionSelector;
+use crate::settings::{self, Settings};
 use crate::window::GnomeShotWindow;
 
 // Global Tokio runtime for async D-Bus operations
@@ -19,12 +26,68 @@ fn runtime() -> &'static tokio::runtime::Runtime {
     })
 }
 
+/// How long to wait after hiding our window before capturing the desktop, so
+/// the compositor has taken it off screen
+const HIDE_DELAY: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Actions with shortcuts that text fields need for themselves
+const TEXT_EDITING_ACTIONS: [&str; 7] = ["undo", "redo", "delete", "duplicate", "copy", "paste", "quick-save"];
+
+/// Run a portal request on the Tokio runtime and hand its result to `on_done`
+/// on the GTK thread; failures are logged
+fn run_portal_request<T: Send + 'static>(
+    request: impl std::future::Future<Output = anyhow::Result<T>> + Send + 'static,
+    on_done: impl FnOnce(T) + 'static,
+) {
+    run_portal_request_or_fail(request, move |result| match result {
+        Ok(result) => on_done(result),
+        Err(e) => eprintln!("Portal request failed: {}", e),
+    });
+}
+
+/// Like `run_portal_request`, but `on_result` also gets the failures, such as
+/// the user cancelling, to clean up after them
+fn run_portal_request_or_fail<T: Send + 'static>(
+    request: impl std::future::Future<Output = anyhow::Result<T>> + Send + 'static,
+    on_result: impl FnOnce(anyhow::Result<T>) + 'static,
+) {
+    // Use std channel for thread communication (only the result crosses threads)
+    let (sender, receiver) = std::sync::mpsc::channel::<anyhow::Result<T>>();
+
+    runtime().spawn(async move {
+        let _ = sender.send(request.await);
+    });
+
+    // Poll for result on GTK main thread using idle callback
+    let mut on_result = Some(on_result);
+    glib::idle_add_local(move || {
+        match receiver.try_recv() {
+            Ok(result) => {
+                if let Some(on_result) = on_result.take() {
+                    on_result(result);
+                }
+                glib::ControlFlow::Break
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => {
+                // Keep polling
+                glib::ControlFlow::Continue
+            }
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                eprintln!("Channel disconnected");
+                glib::ControlFlow::Break
+            }
+        }
+    });
+}
+
 mod imp {
     use super::*;
 
     #[derive(Default)]
     pub struct GnomeShotApplication {
         pub window: OnceCell<GnomeShotWindow>,
+        /// Files captured this session, oldest first
+        pub recent_captures: RefCell<Vec<std::path::PathBuf>>,
     }
 
     #[glib::object_subclass]
@@ -54,10 +117,29 @@ mod imp {
 
             // Check for --capture flag
             let auto_capture = args.iter().any(|arg| arg.to_str() == Some("--capture"));
+            let window_capture = args.iter().any(|arg| arg.to_str() == Some("--capture-window"));
+            let previous_capture = args.iter().any(|arg| arg.to_str() == Some("--capture-previous"));
+            // --preset NAME or --preset=NAME selects a region with a capture preset
+            let preset = args.iter().enumerate().find_map(|(i, arg)| {
+                let arg = arg.to_str()?;
+                match arg.strip_prefix("--preset") {
+                    Some("") => args.get(i + 1).and_then(|value| value.to_str()).map(str::to_string),
+                    Some(value) => value.strip_prefix('=').map(str::to_string),
+                    None => None,
+                }
+            });
 
             app.present_window();
 
-            if auto_capture {
+            if let Some(preset) = preset {
+                glib::idle_add_local_once(glib::clone!(
+                    #[weak]
+                    app,
+                    move || {
+                        app.capture_with_selector(Some(&preset));
+                    }
+                ));
+            } else if auto_capture {
                 // Trigger capture after window is shown
                 glib::idle_add_local_once(glib::clone!(
                     #[weak]
@@ -66,6 +148,22 @@ mod imp {
                         app.capture_screenshot();
                     }
                 ));
+            } else if window_capture {
+                glib::idle_add_local_once(glib::clone!(
+                    #[weak]
+                    app,
+                    move || {
+                        app.capture_window();
+                    }
+                ));
+            } else if previous_capture {
+                glib::idle_add_local_once(glib::clone!(
+                    #[weak]
+                    app,
+                    move || {
+                        app.capture_previous_area();
+                    }
+                ));
             }
 
             glib::ExitCode::SUCCESS
@@ -104,6 +202,92 @@ impl GnomeShotApplication {
             })
             .build();
 
+        // Scrolling capture: several captures stitched into one long image
+        let action_capture_scrolling = gio::ActionEntry::builder("capture-scrolling")
+            .activate(|app: &Self, _, _| {
+                app.capture_scrolling();
+            })
+            .build();
+
+        let action_stitch_files = gio::ActionEntry::builder("stitch-files")
+            .activate(|app: &Self, _, _| {
+                app.stitch_files();
+            })
+            .build();
+
+        // Lay several images out into one
+        let action_combine = gio::ActionEntry::builder("combine")
+            .activate(|app: &Self, _, _| {
+                app.show_combine_dialog();
+            })
+            .build();
+
+        // Capture the same area as last time
+        let action_capture_previous = gio::ActionEntry::builder("capture-previous")
+            .activate(|app: &Self, _, _| {
+                app.capture_previous_area();
+            })
+            .build();
+
+        // Recognize text offline and copy it
+        let action_copy_text = gio::ActionEntry::builder("copy-text")
+            .activate(|app: &Self, _, _| {
+                app.copy_text();
+            })
+            .build();
+
+        // Pick the region in our own overlay instead of the portal's dialog
+        let action_select_region = gio::ActionEntry::builder("select-region")
+            .activate(|app: &Self, _, _| {
+                app.capture_with_selector(None);
+            })
+            .build();
+
+        // Same, starting with a capture preset chosen by name
+        let action_capture_preset = gio::ActionEntry::builder("capture-preset")
+            .parameter_type(Some(glib::VariantTy::STRING))
+            .activate(|app: &Self, _, parameter| {
+                if let Some(name) = parameter.and_then(|p| p.get::<String>()) {
+                    app.capture_with_selector(Some(&name));
+                }
+            })
+            .build();
+
+        // Capture one monitor, by its position in the display's list
+        let action_capture_monitor = gio::ActionEntry::builder("capture-monitor")
+            .parameter_type(Some(glib::VariantTy::INT32))
+            .activate(|app: &Self, _, parameter| {
+                if let Some(index) = parameter.and_then(|p| p.get::<i32>()) {
+                    app.capture_monitor(index.max(0) as usize);
+                }
+            })
+            .build();
+
+        // Capture one window, with its title and app id when the shell extension is installed
+        let action_capture_window = gio::ActionEntry::builder("capture-window")
+            .activate(|app: &Self, _, _| {
+                app.capture_window();
+            })
+            .build();
+
+        // Window capture options, kept in the settings
+        let options = self.window_options();
+        let action_window_shadow = gio::ActionEntry::builder("window-shadow")
+            .state(options.shadow.to_variant())
+            .activate(|_: &Self, action, _| toggle_setting(action, "window_shadow"))
+            .build();
+        let action_window_rounded_corners = gio::ActionEntry::builder("window-rounded-corners")
+            .state(options.rounded_corners.to_variant())
+            .activate(|_: &Self, action, _| toggle_setting(action, "window_rounded_corners"))
+            .build();
+
+        // Record the screen and export it as an animation or video
+        let action_record = gio::ActionEntry::builder("record")
+            .activate(|app: &Self, _, _| {
+                app.start_recording();
+            })
+            .build();
+
         // Quit action
         let action_quit = gio::ActionEntry::builder("quit")
             .activate(|app: &Self, _, _| {
@@ -133,12 +317,76 @@ impl GnomeShotApplication {
             })
             .build();
 
-        // Copy action
+        // Delete selected annotation
+        let action_delete = gio::ActionEntry::builder("delete")
+            .activate(|app: &Self, _, _| {
+                if let Some(window) = app.imp().window.get() {
+                    if let Some(canvas) = window.canvas() {
+                        canvas.delete_selected();
+                    }
+                }
+            })
+            .build();
+
+        // Stacking order of the selected annotation
+        let action_bring_forward = gio::ActionEntry::builder("bring-forward")
+            .activate(|app: &Self, _, _| {
+                if let Some(canvas) = app.imp().window.get().and_then(|w| w.canvas()) {
+                    canvas.bring_forward();
+                }
+            })
+            .build();
+
+        let action_send_backward = gio::ActionEntry::builder("send-backward")
+            .activate(|app: &Self, _, _| {
+                if let Some(canvas) = app.imp().window.get().and_then(|w| w.canvas()) {
+                    canvas.send_backward();
+                }
+            })
+            .build();
+
+        let action_bring_to_front = gio::ActionEntry::builder("bring-to-front")
+            .activate(|app: &Self, _, _| {
+                if let Some(canvas) = app.imp().window.get().and_then(|w| w.canvas()) {
+                    canvas.bring_to_front();
+                }
+            })
+            .build();
+
+        let action_send_to_back = gio::ActionEntry::builder("send-to-back")
+            .activate(|app: &Self, _, _| {
+                if let Some(canvas) = app.imp().window.get().and_then(|w| w.canvas()) {
+                    canvas.send_to_back();
+                }
+            })
+            .build();
+
+        // Duplicate selected annotation
+        let action_duplicate = gio::ActionEntry::builder("duplicate")
+            .activate(|app: &Self, _, _| {
+                if let Some(canvas) = app.imp().window.get().and_then(|w| w.canvas()) {
+                    canvas.duplicate_selected();
+                }
+            })
+            .build();
+
+        // Paste annotations copied from any GNOME Shot window
+        let action_paste = gio::ActionEntry::builder("paste")
+            .activate(|app: &Self, _, _| {
+                if let Some(canvas) = app.imp().window.get().and_then(|w| w.canvas()) {
+                    canvas.paste();
+                }
+            })
+            .build();
+
+        // Copy action: the selected annotation if there is one, otherwise the image
         let action_copy = gio::ActionEntry::builder("copy")
             .activate(|app: &Self, _, _| {
                 if let Some(window) = app.imp().window.get() {
                     if let Some(canvas) = window.canvas() {
-                        if let Some(texture) = canvas.export_to_texture() {
+                        if canvas.has_selection() {
+                            canvas.copy_selected();
+                        } else if let Some(texture) = canvas.export_to_texture() {
                             if let Some(display) = gtk::gdk::Display::default() {
                                 let clipboard = display.clipboard();
                                 clipboard.set_texture(&texture);
@@ -164,65 +412,567 @@ impl GnomeShotApplication {
             })
             .build();
 
-        self.add_action_entries([action_capture, action_quit, action_undo, action_redo, action_copy, action_save, action_quick_save]);
+        self.add_action_entries([
+            action_capture,
+            action_capture_scrolling,
+            action_stitch_files,
+            action_combine,
+            action_capture_previous,
+            action_capture_monitor,
+            action_select_region,
+            action_capture_preset,
+            action_copy_text,
+            action_capture_window,
+            action_window_shadow,
+            action_window_rounded_corners,
+            action_record,
+            action_quit,
+            action_undo,
+            action_redo,
+            action_delete,
+            action_bring_forward,
+            action_send_backward,
+            action_bring_to_front,
+            action_send_to_back,
+            action_duplicate,
+            action_copy,
+            action_paste,
+            action_save,
+            action_quick_save,
+        ]);
+
+        // Nothing to repeat until a region was selected
+        self.update_capture_previous();
+
+        // Text recognition needs Tesseract, so it's off without it
+        if !ocr::TesseractBackend::is_available() {
+            eprintln!("tesseract not found, copying text is disabled");
+            if let Some(action) = self.lookup_action("copy-text").and_downcast::<gio::SimpleAction>() {
+                action.set_enabled(false);
+            }
+        }
 
         // Set keyboard shortcuts
         self.set_accels_for_action("app.capture", &["<Primary>n"]);
+        self.set_accels_for_action("app.capture-previous", &["<Primary><Shift>p"]);
+        self.set_accels_for_action("app.capture-window", &["<Primary><Shift>n"]);
+        self.set_accels_for_action("app.select-region", &["<Primary><Shift>a"]);
+        self.set_accels_for_action("app.record", &["<Primary><Shift>r"]);
+        self.set_accels_for_action("app.copy-text", &["<Primary><Shift>t"]);
         self.set_accels_for_action("app.quit", &["<Primary>q"]);
         self.set_accels_for_action("app.undo", &["<Primary>z"]);
         self.set_accels_for_action("app.redo", &["<Primary><Shift>z"]);
+        self.set_accels_for_action("app.delete", &["Delete"]);
+        self.set_accels_for_action("app.bring-forward", &["<Primary>bracketright"]);
+        self.set_accels_for_action("app.send-backward", &["<Primary>bracketleft"]);
+        self.set_accels_for_action("app.bring-to-front", &["<Primary><Shift>bracketright"]);
+        self.set_accels_for_action("app.send-to-back", &["<Primary><Shift>bracketleft"]);
+        self.set_accels_for_action("app.duplicate", &["<Primary>d"]);
         self.set_accels_for_action("app.copy", &["<Primary>c"]);
+        self.set_accels_for_action("app.paste", &["<Primary>v"]);
         self.set_accels_for_action("app.save", &["<Primary>s"]);
         self.set_accels_for_action("app.quick-save", &["Escape"]);
+        self.set_accels_for_action("win.show-help-overlay", &["<Primary>question"]);
+
+        // Let those keys reach text fields, such as a layer being renamed
+        self.connect_window_added(|app, window| {
+            window.connect_focus_widget_notify(glib::clone!(
+                #[weak]
+                app,
+                move |_| app.update_text_editing_actions()
+            ));
+            window.connect_is_active_notify(glib::clone!(
+                #[weak]
+                app,
+                move |_| app.update_text_editing_actions()
+            ));
+        });
+    }
+
+    /// Turn off the actions whose shortcuts are also text editing keys while
+    /// a text field has focus; a disabled action lets its key through
+    fn update_text_editing_actions(&self) {
+        let typing = self
+            .active_window()
+            .and_then(|window| GtkWindowExt::focus(&window))
+            .is_some_and(|widget| widget.is::<gtk::Text>() || widget.is::<gtk::TextView>());
+        for name in TEXT_EDITING_ACTIONS {
+            if let Some(action) = self.lookup_action(name).and_downcast::<gio::SimpleAction>() {
+                action.set_enabled(!typing);
+            }
+        }
     }
 
     pub fn capture_screenshot(&self) {
         eprintln!("Capture button clicked!");
+        if Settings::load().get("region_selector") == Some("builtin") {
+            self.capture_with_selector(None);
+            return;
+        }
+
         let app = self.clone();
+        self.request_capture(move |path| {
+            app.remember_capture(&path);
 
-        // Use std channel for thread communication (only send PathBuf which is Send)
-        let (sender, receiver) = std::sync::mpsc::channel::<anyhow::Result<std::path::PathBuf>>();
+            // Load the pixbuf on the GTK thread
+            match capture::load_screenshot_from_path(path) {
+                Ok(screenshot) => {
+                    eprintln!("Screenshot loaded successfully!");
+                    app.open_editor(screenshot);
+                }
+                Err(e) => {
+                    eprintln!("Failed to load screenshot: {}", e);
+                }
+            }
+        });
+    }
 
-        // Spawn the async capture on Tokio runtime
-        runtime().spawn(async move {
-            eprintln!("Starting capture on Tokio runtime...");
-            let result = capture::capture_interactive_path().await;
-            let _ = sender.send(result);
+    /// Capture the same area as the last region selected in GNOME Shot's selector again
+    pub fn capture_previous_area(&self) {
+        let Some(area) = settings::last_area() else {
+            self.explain_no_previous_area();
+            return;
+        };
+
+        let app = self.clone();
+        self.capture_desktop(move |path| {
+            match capture::load_fullscreen_from_path(path)
+                .and_then(|fullscreen| capture::crop_to_area(fullscreen, area, capture::CaptureMode::Region))
+            {
+                Ok(screenshot) => app.open_editor(screenshot),
+                Err(e) => eprintln!("Failed to capture previous area: {}", e),
+            }
         });
+    }
 
-        // Poll for result on GTK main thread using idle callback
-        glib::idle_add_local(move || {
-            match receiver.try_recv() {
-                Ok(Ok(path)) => {
-                    eprintln!("Capture path received: {:?}", path);
-                    // Load the pixbuf on the GTK thread
-                    match capture::load_screenshot_from_path(path) {
-                        Ok(screenshot) => {
-                            eprintln!("Screenshot loaded successfully!");
-                            app.open_editor(screenshot);
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to load screenshot: {}", e);
-                        }
+    /// Say why there's nothing to repeat, and offer to select a region
+    ///
+    /// The portal dialog doesn't say where its region was, so only regions
+    /// picked in the selector can be captured again.
+    fn explain_no_previous_area(&self) {
+        let Some(window) = self.imp().window.get() else { return };
+        let dialog = adw::MessageDialog::new(
+            Some(window),
+            Some("No Previous Area"),
+            Some(
+                "Capture Previous Area repeats the last region picked with Select Region. \
+                 Regions picked in GNOME's screenshot dialog can't be repeated.",
+            ),
+        );
+        dialog.add_responses(&[("close", "_Close"), ("select", "_Select Region…")]);
+        dialog.set_response_appearance("select", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("select"));
+        dialog.set_close_response("close");
+
+        let app = self.clone();
+        dialog.connect_response(Some("select"), move |_, _| app.capture_with_selector(None));
+        dialog.present();
+    }
+
+    /// Enable "Capture Previous Area" only once there's an area to repeat
+    fn update_capture_previous(&self) {
+        if let Some(action) = self.lookup_action("capture-previous").and_downcast::<gio::SimpleAction>() {
+            action.set_enabled(settings::last_area().is_some());
+        }
+    }
+
+    /// Capture the whole desktop without the portal dialog, then pick the
+    /// region in GNOME Shot's own overlay, starting with the capture preset
+    /// called `preset` (or described by it, such as `1024x768`) if given
+    pub fn capture_with_selector(&self, preset: Option<&str>) {
+        let mut presets = settings::load_capture_presets();
+        let initial_preset = match preset.map(|query| (query, capture::CapturePreset::find(&presets, query))) {
+            None => 0,
+            Some((_, Some(preset))) => presets.iter().position(|p| *p == preset).unwrap_or_else(|| {
+                presets.push(preset);
+                presets.len() - 1
+            }),
+            Some((query, None)) => {
+                eprintln!("Unknown capture preset '{}'", query);
+                0
+            }
+        };
+
+        let app = self.clone();
+        self.capture_desktop(move |path| {
+            let Some(window) = app.imp().window.get() else { return };
+            let fullscreen = match capture::load_fullscreen_from_path(path.clone()) {
+                Ok(fullscreen) => fullscreen,
+                Err(e) => {
+                    eprintln!("Failed to load capture: {}", e);
+                    return;
+                }
+            };
+            let scale = fullscreen.scale_factor;
+
+            let app = app.clone();
+            RegionSelector::present(window, fullscreen.pixbuf.clone(), presets, initial_preset, move |selected| {
+                let Some((selection, preset)) = selected else {
+                    discard_raw_files([&path]);
+                    return;
+                };
+                let area = capture::Area::from_pixels(selection.x, selection.y, selection.width, selection.height, scale);
+                match capture::crop_to_area(fullscreen, area, capture::CaptureMode::Region) {
+                    Ok(mut screenshot) => {
+                        save_last_area(area);
+                        app.update_capture_previous();
+                        screenshot.preset = (preset.constraint != capture::Constraint::Free).then_some(preset);
+                        app.open_editor(screenshot);
                     }
-                    glib::ControlFlow::Break
+                    Err(e) => eprintln!("Failed to load selection: {}", e),
                 }
-                Ok(Err(e)) => {
-                    eprintln!("Screenshot capture failed: {}", e);
-                    glib::ControlFlow::Break
+            });
+        });
+    }
+
+    /// Capture the whole desktop without the portal dialog and hand the file to
+    /// `on_captured` on the GTK thread; our window is hidden meanwhile so it
+    /// isn't in the picture, and shown again before `on_captured` runs
+    ///
+    /// The file holds the whole desktop, so crops of it aren't added to the
+    /// recent captures.
+    fn capture_desktop(&self, on_captured: impl FnOnce(std::path::PathBuf) + 'static) {
+        let Some(window) = self.imp().window.get().cloned() else { return };
+        let shown = window.is_visible();
+        window.set_visible(false);
+
+        glib::timeout_add_local_once(HIDE_DELAY, move || {
+            run_portal_request_or_fail(capture::capture_fullscreen_path(), move |result| {
+                if shown {
+                    window.present();
                 }
-                Err(std::sync::mpsc::TryRecvError::Empty) => {
-                    // Keep polling
-                    glib::ControlFlow::Continue
+                match result {
+                    Ok(path) => on_captured(path),
+                    Err(e) => eprintln!("Failed to capture the desktop: {}", e),
                 }
-                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
-                    eprintln!("Channel disconnected");
-                    glib::ControlFlow::Break
+            });
+        });
+    }
+
+    /// Run an interactive portal capture and hand the file to `on_captured` on the GTK thread
+    fn request_capture(&self, on_captured: impl FnOnce(std::path::PathBuf) + 'static) {
+        eprintln!("Starting capture on Tokio runtime...");
+        run_portal_request(capture::capture_interactive_path(), move |path| {
+            eprintln!("Capture path received: {:?}", path);
+            on_captured(path);
+        });
+    }
+
+    fn remember_capture(&self, path: &std::path::Path) {
+        const MAX_RECENT: usize = 10;
+
+        let mut recent = self.imp().recent_captures.borrow_mut();
+        recent.retain(|p| p != path);
+        recent.push(path.to_path_buf());
+        if recent.len() > MAX_RECENT {
+            recent.remove(0);
+        }
+    }
+
+    /// Follow a remembered capture that was moved to `to`, or drop it if it's gone
+    pub fn capture_moved(&self, from: &std::path::Path, to: Option<&std::path::Path>) {
+        let mut recent = self.imp().recent_captures.borrow_mut();
+        match to {
+            Some(to) => recent.iter_mut().filter(|p| *p == from).for_each(|p| *p = to.to_path_buf()),
+            None => recent.retain(|p| p != from),
+        }
+    }
+
+    /// Recognize the text in the area dragged with the text tool, the selected
+    /// annotation's area, the crop area or the whole screenshot, and copy it
+    /// to the clipboard
+    ///
+    /// The recognized words are kept on the canvas so annotations snap to them.
+    fn copy_text(&self) {
+        let Some(window) = self.imp().window.get() else { return };
+        let Some(canvas) = window.canvas() else { return };
+        let Some(pixbuf) = canvas.screenshot_pixbuf() else { return };
+
+        let image = capture::pixbuf_to_image(&pixbuf);
+        let region = canvas.selected_region().map(|(x, y, width, height)| {
+            let (left, top) = (x.max(0.0).floor(), y.max(0.0).floor());
+            capture::Region {
+                x: left as u32,
+                y: top as u32,
+                width: ((x + width).ceil() - left).max(0.0) as u32,
+                height: ((y + height).ceil() - top).max(0.0) as u32,
+            }
+        });
+        let language = Settings::load().get("ocr_language").unwrap_or("eng").to_string();
+        let canvas = canvas.downgrade();
+
+        // Recognition takes a moment, so run it off the GTK thread
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let backend = ocr::TesseractBackend { language };
+            let _ = sender.send(ocr::recognize(&backend, &image, region));
+        });
+
+        glib::timeout_add_local(std::time::Duration::from_millis(100), move || match receiver.try_recv() {
+            Ok(Ok(words)) => {
+                let text = ocr::to_text(&words);
+                if text.is_empty() {
+                    eprintln!("No text found");
+                } else if let Some(display) = gtk::gdk::Display::default() {
+                    display.clipboard().set_text(&text);
+                    eprintln!("Copied {} words to clipboard", words.len());
+                }
+                // Words only belong on the screenshot they were read from
+                if let Some(canvas) = canvas.upgrade().filter(|canvas| canvas.screenshot_pixbuf().as_ref() == Some(&pixbuf)) {
+                    canvas.add_recognized_words(words);
                 }
+                glib::ControlFlow::Break
             }
+            Ok(Err(e)) => {
+                eprintln!("Text recognition failed: {}", e);
+                glib::ControlFlow::Break
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => glib::ControlFlow::Continue,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => glib::ControlFlow::Break,
+        });
+    }
+
+    fn show_combine_dialog(&self) {
+        let Some(window) = self.imp().window.get() else { return };
+        let current = window.canvas().and_then(|canvas| canvas.export_to_texture());
+        let recent = self.imp().recent_captures.borrow().clone();
+
+        let app = self.clone();
+        CombineDialog::present(window, &recent, current, move |pixbuf| {
+            app.open_editor(capture::Screenshot {
+                pixbuf,
+                source_path: None,
+                capture_mode: capture::CaptureMode::Combined,
+                window: None,
+                scale_factor: 1.0,
+                preset: None,
+            });
         });
     }
 
+    /// Capture frames one by one while the user scrolls, then stitch them
+    pub fn capture_scrolling(&self) {
+        self.capture_scroll_frame(Rc::new(RefCell::new(Vec::new())));
+    }
+
+    fn capture_scroll_frame(&self, frames: Rc<RefCell<Vec<(std::path::PathBuf, Pixbuf)>>>) {
+        let app = self.clone();
+        run_portal_request_or_fail(capture::capture_interactive_path(), move |result| {
+            match result.and_then(|path| Ok((Pixbuf::from_file(&path)?, path))) {
+                Ok((pixbuf, path)) => frames.borrow_mut().push((path, pixbuf)),
+                Err(e) => {
+                    eprintln!("Failed to capture frame: {}", e);
+                    // Nothing to lose before the first frame, so just stop
+                    if frames.borrow().is_empty() {
+                        return;
+                    }
+                }
+            }
+            // Failed or cancelled captures keep the frames so far
+            app.ask_for_next_frame(frames);
+        });
+    }
+
+    fn ask_for_next_frame(&self, frames: Rc<RefCell<Vec<(std::path::PathBuf, Pixbuf)>>>) {
+        let Some(window) = self.imp().window.get() else { return };
+        let count = frames.borrow().len();
+
+        let dialog = adw::MessageDialog::new(
+            Some(window),
+            Some("Scrolling Capture"),
+            Some(&format!(
+                "{} captured. Scroll so the next part shows, keeping some of the current view on screen, \
+                 and capture the same area again.",
+                if count == 1 { "1 frame".to_string() } else { format!("{} frames", count) }
+            )),
+        );
+        dialog.add_responses(&[("cancel", "_Cancel"), ("next", "Capture _Next"), ("done", "_Finish")]);
+        dialog.set_response_appearance("done", adw::ResponseAppearance::Suggested);
+        dialog.set_response_enabled("done", count > 0);
+        dialog.set_default_response(Some("next"));
+        dialog.set_close_response("cancel");
+
+        let app = self.clone();
+        dialog.connect_response(None, move |_, response| match response {
+            "next" => app.capture_scroll_frame(frames.clone()),
+            "done" => app.finish_scrolling(&frames.borrow()),
+            _ => discard_raw_files(frames.borrow().iter().map(|(path, _)| path)),
+        });
+        dialog.present();
+    }
+
+    /// Stitch a scrolling capture into a new file next to the first frame and
+    /// open it; the frames are handled like any raw capture, and so is the
+    /// stitched file once the screenshot is saved
+    fn finish_scrolling(&self, frames: &[(std::path::PathBuf, Pixbuf)]) {
+        let pixbufs: Vec<Pixbuf> = frames.iter().map(|(_, pixbuf)| pixbuf.clone()).collect();
+        let pixbuf = match capture::stitch_pixbufs(&pixbufs) {
+            Ok(pixbuf) => pixbuf,
+            Err(e) => {
+                eprintln!("Failed to stitch frames: {}", e);
+                return;
+            }
+        };
+
+        let Some((first, _)) = frames.first() else { return };
+        let dir = first.parent().unwrap_or(std::path::Path::new("."));
+        let stem = first.file_stem().unwrap_or_default().to_string_lossy();
+        let stitched = capture::unique_path(dir, std::path::Path::new(&format!("{} (scrolling).png", stem)));
+        let source_path = match pixbuf.savev(&stitched, "png", &[]) {
+            Ok(()) => {
+                let policy = settings::raw_file_policy();
+                for (path, _) in frames {
+                    if let Err(e) = capture::release_raw_file(path, &stitched, policy) {
+                        eprintln!("Failed to clean up {}: {}", path.display(), e);
+                    }
+                }
+                self.remember_capture(&stitched);
+                Some(stitched)
+            }
+            Err(e) => {
+                eprintln!("Failed to write the stitched capture: {}", e);
+                None
+            }
+        };
+
+        self.open_editor(capture::Screenshot {
+            pixbuf,
+            source_path,
+            capture_mode: capture::CaptureMode::Scrolling,
+            window: None,
+            scale_factor: capture::display_scale_factor(),
+            preset: None,
+        });
+    }
+
+    /// Stitch frames, given top to bottom, and open the result in the editor
+    fn open_stitched(&self, frames: &[Pixbuf], scale_factor: f64) {
+        match capture::stitch_pixbufs(frames) {
+            Ok(pixbuf) => self.open_editor(capture::Screenshot {
+                pixbuf,
+                source_path: None,
+                capture_mode: capture::CaptureMode::Scrolling,
+                window: None,
+                scale_factor,
+                preset: None,
+            }),
+            Err(e) => eprintln!("Failed to stitch frames: {}", e),
+        }
+    }
+
+    /// Stitch images picked from disk, in file name order
+    pub fn stitch_files(&self) {
+        let Some(window) = self.imp().window.get() else { return };
+
+        let filter = gtk::FileFilter::new();
+        filter.add_pixbuf_formats();
+        filter.set_name(Some("Images"));
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+
+        let dialog = gtk::FileDialog::builder()
+            .title("Stitch Images")
+            .modal(true)
+            .filters(&filters)
+            .build();
+
+        let app = self.clone();
+        dialog.open_multiple(Some(window), gio::Cancellable::NONE, move |result| {
+            let Ok(files) = result else { return };
+            let mut paths: Vec<std::path::PathBuf> = files
+                .iter::<gio::File>()
+                .filter_map(|file| file.ok()?.path())
+                .collect();
+            paths.sort();
+
+            let frames: Result<Vec<Pixbuf>, _> = paths.iter().map(Pixbuf::from_file).collect();
+            match frames {
+                Ok(frames) => app.open_stitched(&frames, 1.0),
+                Err(e) => eprintln!("Failed to load images: {}", e),
+            }
+        });
+    }
+
+    /// Ask the portal what to record, then record until stopped and offer to export
+    pub fn start_recording(&self) {
+        let app = self.clone();
+        run_portal_request(capture::open_screencast(), move |stream| {
+            let Some(window) = app.imp().window.get() else { return };
+            match capture::Recorder::start(stream) {
+                Ok(recorder) => {
+                    let window_for_export = window.clone();
+                    recording_dialog::present_recording_controls(window, recorder, move |recording| {
+                        RecordingDialog::present(&window_for_export, recording);
+                    });
+                }
+                Err(e) => eprintln!("Failed to start recording: {}", e),
+            }
+        });
+    }
+
+    /// Capture the whole of one monitor
+    pub fn capture_monitor(&self, index: usize) {
+        let Some(monitor) = capture::monitors().into_iter().nth(index) else {
+            eprintln!("No monitor {}", index);
+            return;
+        };
+        let area = capture::monitor_area(&monitor);
+
+        let app = self.clone();
+        self.capture_desktop(move |path| {
+            match capture::load_fullscreen_from_path(path)
+                .and_then(|fullscreen| capture::crop_to_area(fullscreen, area, capture::CaptureMode::Fullscreen))
+            {
+                Ok(screenshot) => app.open_editor(screenshot),
+                Err(e) => eprintln!("Failed to capture monitor: {}", e),
+            }
+        });
+    }
+
+    /// Capture a single window, through the shell extension if it's installed
+    pub fn capture_window(&self) {
+        let app = self.clone();
+        run_portal_request(capture::capture_window_path(), move |(path, window, scale_factor)| {
+            app.remember_capture(&path);
+            let scale_factor = scale_factor.unwrap_or_else(capture::display_scale_factor);
+            let options = app.window_options();
+
+            // The drop shadow is a large blur, so apply it off the GTK thread
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let image_path = path.clone();
+            std::thread::spawn(move || {
+                let _ = sender.send(capture::load_window_image(&image_path, options, scale_factor));
+            });
+
+            let mut pending = Some((path, window));
+            glib::timeout_add_local(std::time::Duration::from_millis(100), move || match receiver.try_recv() {
+                Ok(Ok(image)) => {
+                    if let Some((path, window)) = pending.take() {
+                        app.open_editor(capture::window_screenshot(path, window, image, scale_factor));
+                    }
+                    glib::ControlFlow::Break
+                }
+                Ok(Err(e)) => {
+                    eprintln!("Failed to load window capture: {}", e);
+                    glib::ControlFlow::Break
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => glib::ControlFlow::Continue,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => glib::ControlFlow::Break,
+            });
+        });
+    }
+
+    /// Shadow and corner choices for window captures, from the settings
+    fn window_options(&self) -> capture::WindowOptions {
+        let settings = Settings::load();
+        let defaults = capture::WindowOptions::default();
+        let flag = |key: &str, default: bool| settings.get(key).map_or(default, |value| value == "true");
+        capture::WindowOptions {
+            shadow: flag("window_shadow", defaults.shadow),
+            rounded_corners: flag("window_rounded_corners", defaults.rounded_corners),
+        }
+    }
+
     fn open_editor(&self, screenshot: capture::Screenshot) {
         let window = self.imp().window.get().expect("Window not initialized");
         window.load_screenshot(screenshot);
@@ -254,6 +1004,7 @@ impl GnomeShotApplication {
             eprintln!("Failed to save: {}", e);
         } else {
             eprintln!("Saved to: {}", path.display());
+            window.screenshot_saved(&path);
         }
 
         // Copy to clipboard using wl-copy (works better on Wayland)
@@ -300,20 +1051,11 @@ impl GnomeShotApplication {
     }
 
     fn get_screenshots_dir() -> std::path::PathBuf {
-        // Check for config file first
-        let config_dir = glib::user_config_dir().join("gnome-shot");
-        let config_file = config_dir.join("config");
-
-        if config_file.exists() {
-            if let Ok(content) = std::fs::read_to_string(&config_file) {
-                for line in content.lines() {
-                    if let Some(path) = line.strip_prefix("save_dir=") {
-                        let path = std::path::PathBuf::from(path.trim());
-                        if path.exists() || std::fs::create_dir_all(&path).is_ok() {
-                            return path;
-                        }
-                    }
-                }
+        // Check the config file first
+        if let Some(path) = Settings::load().get("save_dir") {
+            let path = std::path::PathBuf::from(path);
+            if path.exists() || std::fs::create_dir_all(&path).is_ok() {
+                return path;
             }
         }
 
@@ -324,10 +1066,9 @@ impl GnomeShotApplication {
     }
 
     pub fn set_screenshots_dir(path: &std::path::Path) -> std::io::Result<()> {
-        let config_dir = glib::user_config_dir().join("gnome-shot");
-        std::fs::create_dir_all(&config_dir)?;
-        let config_file = config_dir.join("config");
-        std::fs::write(&config_file, format!("save_dir={}\n", path.display()))
+        let mut settings = Settings::load();
+        settings.set("save_dir", path.display().to_string());
+        settings.save()
     }
 
     fn show_save_dialog(&self) {
@@ -353,6 +1094,7 @@ impl GnomeShotApplication {
         filters.append(&filter);
         dialog.set_filters(Some(&filters));
 
+        let saved_window = window.clone();
         dialog.save(Some(window), gio::Cancellable::NONE, move |result: Result<gio::File, glib::Error>| {
             if let Ok(file) = result {
                 if let Some(path) = file.path() {
@@ -360,9 +1102,44 @@ impl GnomeShotApplication {
                         eprintln!("Failed to save: {}", e);
                     } else {
                         eprintln!("Saved to: {}", path.display());
+                        saved_window.screenshot_saved(&path);
                     }
                 }
             }
         });
     }
 }
+
+/// Clean up after a cancelled capture: its raw files are only removed if
+/// raw files are deleted anyway, and otherwise kept like any other capture
+fn discard_raw_files<'a>(paths: impl IntoIterator<Item = &'a std::path::PathBuf>) {
+    if settings::raw_file_policy() != capture::RawFilePolicy::Delete {
+        return;
+    }
+    for path in paths {
+        if let Err(e) = std::fs::remove_file(path) {
+            eprintln!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Keep `area` for "Capture Previous Area"
+fn save_last_area(area: capture::Area) {
+    let mut settings = Settings::load();
+    settings.set("last_area", area.to_setting());
+    if let Err(e) = settings.save() {
+        eprintln!("Failed to save settings: {}", e);
+    }
+}
+
+/// Flip a boolean action's state and remember it in the settings under `key`
+pub fn toggle_setting(action: &gio::SimpleAction, key: &str) {
+    let enabled = !action.state().and_then(|state| state.get::<bool>()).unwrap_or(false);
+    action.set_state(&enabled.to_variant());
+
+    let mut settings = Settings::load();
+    settings.set(key, enabled.to_string());
+    if let Err(e) = settings.save() {
+        eprintln!("Failed to save settings: {}", e);
+    }
+}
@@ -1,6 +1,30 @@
 use gtk::gdk::RGBA;
+use gtk::gdk_pixbuf::Pixbuf;
+use gtk::pango::{self, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
-#[derive(Debug, Clone, Copy)]
+/// MIME type used to put annotations on the clipboard
+pub const ANNOTATIONS_MIME_TYPE: &str = "application/x-gnome-shot-annotations";
+
+/// (De)serialize an `RGBA` as `[red, green, blue, alpha]`
+pub(crate) mod rgba_serde {
+    use gtk::gdk::RGBA;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &RGBA, serializer: S) -> Result<S::Ok, S::Error> {
+        [color.red(), color.green(), color.blue(), color.alpha()].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<RGBA, D::Error> {
+        let [red, green, blue, alpha] = <[f32; 4]>::deserialize(deserializer)?;
+        Ok(RGBA::new(red, green, blue, alpha))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
@@ -10,9 +34,56 @@ impl Point {
     pub fn new(x: f64, y: f64) -> Self {
         Self { x, y }
     }
+
+    pub fn distance(&self, other: Point) -> f64 {
+        ((other.x - self.x).powi(2) + (other.y - self.y).powi(2)).sqrt()
+    }
+
+    pub fn offset(&self, dx: f64, dy: f64) -> Point {
+        Point::new(self.x + dx, self.y + dy)
+    }
 }
 
-#[derive(Debug, Clone)]
+/// The part of an annotation found under the pointer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+    /// The annotation as a whole, dragging moves it
+    Body,
+    /// One of the points returned by `Annotation::handles`
+    Handle(usize),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DashStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+    DashDot,
+}
+
+impl DashStyle {
+    /// Set the dash pattern on `cr`, sized relative to the line width
+    pub fn apply(&self, cr: &cairo::Context, line_width: f64) {
+        let w = line_width.max(1.0);
+        match self {
+            DashStyle::Solid => cr.set_dash(&[], 0.0),
+            DashStyle::Dashed => cr.set_dash(&[3.0 * w, 2.0 * w], 0.0),
+            // Zero-length dashes with round caps draw as dots
+            DashStyle::Dotted => {
+                cr.set_line_cap(cairo::LineCap::Round);
+                cr.set_dash(&[0.0, 2.0 * w], 0.0);
+            }
+            DashStyle::DashDot => {
+                cr.set_line_cap(cairo::LineCap::Round);
+                cr.set_dash(&[3.0 * w, 2.0 * w, 0.0, 2.0 * w], 0.0);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum Annotation {
     Arrow(ArrowAnnotation),
     Rectangle(RectAnnotation),
@@ -20,9 +91,44 @@ pub enum Annotation {
     Ellipse(EllipseAnnotation),
     Highlight(HighlightAnnotation),
     Blur(BlurAnnotation),
+    Spotlight(SpotlightAnnotation),
+    Magnify(MagnifyAnnotation),
+    Callout(CalloutAnnotation),
+    Stamp(StampAnnotation),
 }
 
 impl Annotation {
+    /// Human-readable kind, used in history descriptions
+    pub fn name(&self) -> &'static str {
+        match self {
+            Annotation::Arrow(_) => "Arrow",
+            Annotation::Rectangle(_) => "Rectangle",
+            Annotation::Line(_) => "Line",
+            Annotation::Ellipse(_) => "Ellipse",
+            Annotation::Highlight(_) => "Highlight",
+            Annotation::Blur(_) => "Blur",
+            Annotation::Spotlight(_) => "Spotlight",
+            Annotation::Magnify(_) => "Magnifier",
+            Annotation::Callout(_) => "Callout",
+            Annotation::Stamp(_) => "Stamp",
+        }
+    }
+
+    /// Color the annotation is drawn in, if it has one
+    pub fn color(&self) -> Option<RGBA> {
+        match self {
+            Annotation::Arrow(a) => Some(a.color),
+            Annotation::Rectangle(r) => Some(r.color),
+            Annotation::Line(l) => Some(l.color),
+            Annotation::Ellipse(e) => Some(e.color),
+            Annotation::Highlight(h) => Some(h.color),
+            Annotation::Magnify(m) => Some(m.color),
+            Annotation::Callout(c) => Some(c.color),
+            Annotation::Stamp(s) => matches!(s.content, StampContent::Symbol(_)).then_some(s.color),
+            Annotation::Blur(_) | Annotation::Spotlight(_) => None,
+        }
+    }
+
     pub fn draw(&self, cr: &cairo::Context, scale: f64) {
         match self {
             Annotation::Arrow(a) => a.draw(cr, scale),
@@ -31,6 +137,10 @@ impl Annotation {
             Annotation::Ellipse(e) => e.draw(cr, scale),
             Annotation::Highlight(h) => h.draw(cr, scale),
             Annotation::Blur(b) => b.draw(cr, scale),
+            Annotation::Spotlight(s) => s.draw(cr, scale),
+            Annotation::Magnify(m) => m.draw(cr, scale),
+            Annotation::Callout(c) => c.draw(cr, scale),
+            Annotation::Stamp(s) => s.draw(cr, scale),
         }
     }
 
@@ -42,21 +152,214 @@ impl Annotation {
             Annotation::Ellipse(e) => e.bounds(),
             Annotation::Highlight(h) => h.bounds(),
             Annotation::Blur(b) => b.bounds(),
+            Annotation::Spotlight(s) => s.bounds(),
+            Annotation::Magnify(m) => m.bounds(),
+            Annotation::Callout(c) => c.bounds(),
+            Annotation::Stamp(s) => s.bounds(),
+        }
+    }
+
+    fn endpoints_mut(&mut self) -> (&mut Point, &mut Point) {
+        match self {
+            Annotation::Arrow(a) => (&mut a.start, &mut a.end),
+            Annotation::Rectangle(r) => (&mut r.start, &mut r.end),
+            Annotation::Line(l) => (&mut l.start, &mut l.end),
+            Annotation::Ellipse(e) => (&mut e.start, &mut e.end),
+            Annotation::Highlight(h) => (&mut h.start, &mut h.end),
+            Annotation::Blur(b) => (&mut b.start, &mut b.end),
+            Annotation::Spotlight(s) => (&mut s.start, &mut s.end),
+            Annotation::Magnify(m) => (&mut m.start, &mut m.end),
+            Annotation::Callout(c) => (&mut c.tail, &mut c.anchor),
+            Annotation::Stamp(s) => (&mut s.start, &mut s.end),
+        }
+    }
+
+    /// Points the user can drag to reshape the annotation
+    pub fn handles(&self) -> Vec<Point> {
+        match self {
+            Annotation::Arrow(a) if a.style == ArrowStyle::Curved => vec![a.start, a.end, a.control],
+            Annotation::Arrow(a) => vec![a.start, a.end],
+            Annotation::Rectangle(r) => vec![r.start, r.end],
+            Annotation::Line(l) => vec![l.start, l.end],
+            Annotation::Ellipse(e) => vec![e.start, e.end],
+            Annotation::Highlight(h) => vec![h.start, h.end],
+            Annotation::Blur(b) => vec![b.start, b.end],
+            Annotation::Spotlight(s) => vec![s.start, s.end],
+            Annotation::Magnify(m) => m.handles(),
+            Annotation::Callout(c) => vec![c.tail, c.anchor],
+            Annotation::Stamp(s) => vec![s.start, s.end],
+        }
+    }
+
+    pub fn move_handle(&mut self, index: usize, point: Point) {
+        match self {
+            Annotation::Magnify(m) => {
+                m.move_handle(index, point);
+                return;
+            }
+            Annotation::Arrow(a) if index == ArrowAnnotation::CONTROL_HANDLE => {
+                a.control = point;
+                return;
+            }
+            _ => {}
+        }
+
+        let (start, end) = self.endpoints_mut();
+        match index {
+            0 => *start = point,
+            1 => *end = point,
+            _ => {}
+        }
+    }
+
+    pub fn translate(&mut self, dx: f64, dy: f64) {
+        match self {
+            Annotation::Magnify(m) => m.center = m.center.offset(dx, dy),
+            Annotation::Arrow(a) => a.control = a.control.offset(dx, dy),
+            _ => {}
+        }
+
+        let (start, end) = self.endpoints_mut();
+        *start = start.offset(dx, dy);
+        *end = end.offset(dx, dy);
+    }
+
+    /// Find what part of the annotation, if any, is under `point`
+    pub fn hit_test(&self, point: Point, tolerance: f64) -> Option<Hit> {
+        let handles = self.handles();
+        if let Some(index) = handles.iter().position(|h| h.distance(point) <= tolerance) {
+            return Some(Hit::Handle(index));
+        }
+
+        match self {
+            Annotation::Arrow(a) => (a.distance_to(point) <= tolerance + a.stroke_width / 2.0).then_some(Hit::Body),
+            Annotation::Line(l) => {
+                (distance_to_segment(point, l.start, l.end) <= tolerance + l.stroke_width / 2.0).then_some(Hit::Body)
+            }
+            Annotation::Magnify(m) => m.hit_test(point),
+            Annotation::Callout(c) => {
+                let (x, y, w, h) = c.box_rect();
+                let inside = point.x >= x - tolerance
+                    && point.x <= x + w + tolerance
+                    && point.y >= y - tolerance
+                    && point.y <= y + h + tolerance;
+                inside.then_some(Hit::Body)
+            }
+            _ => {
+                let (x, y, w, h) = self.bounds();
+                let inside = point.x >= x - tolerance
+                    && point.x <= x + w + tolerance
+                    && point.y >= y - tolerance
+                    && point.y <= y + h + tolerance;
+                inside.then_some(Hit::Body)
+            }
         }
     }
 }
 
-#[derive(Debug, Clone)]
+fn distance_to_segment(point: Point, a: Point, b: Point) -> f64 {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let length_sq = dx * dx + dy * dy;
+    if length_sq == 0.0 {
+        return point.distance(a);
+    }
+
+    let t = (((point.x - a.x) * dx + (point.y - a.y) * dy) / length_sq).clamp(0.0, 1.0);
+    point.distance(Point::new(a.x + t * dx, a.y + t * dy))
+}
+
+/// Add a rectangle with rounded corners to the current path
+pub fn rounded_rectangle(cr: &cairo::Context, x: f64, y: f64, w: f64, h: f64, radius: f64) {
+    let r = radius.min(w / 2.0).min(h / 2.0).max(0.0);
+    let pi = std::f64::consts::PI;
+
+    cr.new_sub_path();
+    cr.arc(x + w - r, y + r, r, -pi / 2.0, 0.0);
+    cr.arc(x + w - r, y + h - r, r, 0.0, pi / 2.0);
+    cr.arc(x + r, y + h - r, r, pi / 2.0, pi);
+    cr.arc(x + r, y + r, r, pi, 3.0 * pi / 2.0);
+    cr.close_path();
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArrowStyle {
+    /// Straight line with an open chevron head
+    #[default]
+    Open,
+    /// Straight line with a filled triangular head
+    Filled,
+    /// Filled heads at both ends
+    Double,
+    /// Shaft that widens from the tail towards a filled head
+    Tapered,
+    /// Curve bent through a draggable control point
+    Curved,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ArrowAnnotation {
     pub start: Point,
     pub end: Point,
+    #[serde(with = "rgba_serde")]
     pub color: RGBA,
     pub stroke_width: f64,
+    pub style: ArrowStyle,
+    /// Control point of the curve, only used by `ArrowStyle::Curved`
+    pub control: Point,
 }
 
 impl ArrowAnnotation {
-    pub fn new(start: Point, end: Point, color: RGBA, stroke_width: f64) -> Self {
-        Self { start, end, color, stroke_width }
+    /// Handle index of the curve control point
+    pub const CONTROL_HANDLE: usize = 2;
+
+    pub fn new(start: Point, end: Point, color: RGBA, stroke_width: f64, style: ArrowStyle) -> Self {
+        let control = Self::default_control(start, end);
+        Self { start, end, color, stroke_width, style, control }
+    }
+
+    /// A gentle bend to one side of the straight line
+    pub fn default_control(start: Point, end: Point) -> Point {
+        let mx = (start.x + end.x) / 2.0;
+        let my = (start.y + end.y) / 2.0;
+        let dx = end.x - start.x;
+        let dy = end.y - start.y;
+        Point::new(mx + dy * 0.25, my - dx * 0.25)
+    }
+
+    /// Length of the arrowhead, growing with the stroke so thick arrows keep their proportions
+    pub fn head_length(&self) -> f64 {
+        self.stroke_width * 4.0 + 3.0
+    }
+
+    /// Direction the head points in, following the curve for curved arrows
+    fn end_angle(&self) -> f64 {
+        let from = if self.style == ArrowStyle::Curved { self.control } else { self.start };
+        (self.end.y - from.y).atan2(self.end.x - from.x)
+    }
+
+    fn start_angle(&self) -> f64 {
+        (self.start.y - self.end.y).atan2(self.start.x - self.end.x)
+    }
+
+    /// Add a triangular head with its tip at `tip` to the current path
+    fn add_head_path(cr: &cairo::Context, tip: Point, angle: f64, length: f64, scale: f64) {
+        let spread = std::f64::consts::PI / 7.0;
+        cr.move_to(tip.x * scale, tip.y * scale);
+        cr.line_to(
+            (tip.x - length * (angle - spread).cos()) * scale,
+            (tip.y - length * (angle - spread).sin()) * scale,
+        );
+        cr.line_to(
+            (tip.x - length * (angle + spread).cos()) * scale,
+            (tip.y - length * (angle + spread).sin()) * scale,
+        );
+        cr.close_path();
+    }
+
+    /// Point `distance` back from `tip` along `angle`, where a filled head's base sits
+    fn head_base(tip: Point, angle: f64, distance: f64) -> Point {
+        Point::new(tip.x - distance * angle.cos(), tip.y - distance * angle.sin())
     }
 
     pub fn draw(&self, cr: &cairo::Context, scale: f64) {
@@ -68,55 +371,147 @@ impl ArrowAnnotation {
         );
         cr.set_line_width(self.stroke_width * scale);
         cr.set_line_cap(cairo::LineCap::Round);
+        cr.set_line_join(cairo::LineJoin::Round);
 
-        let sx = self.start.x * scale;
-        let sy = self.start.y * scale;
-        let ex = self.end.x * scale;
-        let ey = self.end.y * scale;
+        let head = self.head_length();
+        let end_angle = self.end_angle();
+        // Stop the shaft inside the head so the round cap doesn't poke through the tip
+        let inset = head * 0.7;
 
-        // Draw line
-        cr.move_to(sx, sy);
-        cr.line_to(ex, ey);
-        let _ = cr.stroke();
+        match self.style {
+            ArrowStyle::Open => {
+                cr.move_to(self.start.x * scale, self.start.y * scale);
+                cr.line_to(self.end.x * scale, self.end.y * scale);
+                let _ = cr.stroke();
 
-        // Draw arrowhead
-        let angle = (ey - sy).atan2(ex - sx);
-        let arrow_length = 15.0 * scale;
-        let arrow_angle = std::f64::consts::PI / 6.0;
+                let spread = std::f64::consts::PI / 6.0;
+                for side in [-spread, spread] {
+                    cr.move_to(self.end.x * scale, self.end.y * scale);
+                    cr.line_to(
+                        (self.end.x - head * (end_angle + side).cos()) * scale,
+                        (self.end.y - head * (end_angle + side).sin()) * scale,
+                    );
+                }
+                let _ = cr.stroke();
+            }
+            ArrowStyle::Filled => {
+                let base = Self::head_base(self.end, end_angle, inset);
+                cr.move_to(self.start.x * scale, self.start.y * scale);
+                cr.line_to(base.x * scale, base.y * scale);
+                let _ = cr.stroke();
+
+                Self::add_head_path(cr, self.end, end_angle, head, scale);
+                let _ = cr.fill();
+            }
+            ArrowStyle::Double => {
+                let start_angle = self.start_angle();
+                let from = Self::head_base(self.start, start_angle, inset);
+                let to = Self::head_base(self.end, end_angle, inset);
+                cr.move_to(from.x * scale, from.y * scale);
+                cr.line_to(to.x * scale, to.y * scale);
+                let _ = cr.stroke();
+
+                Self::add_head_path(cr, self.start, start_angle, head, scale);
+                Self::add_head_path(cr, self.end, end_angle, head, scale);
+                let _ = cr.fill();
+            }
+            ArrowStyle::Tapered => {
+                let head = head * 1.3;
+                let base = Self::head_base(self.end, end_angle, head * 0.8);
+                let (nx, ny) = (-end_angle.sin(), end_angle.cos());
+                let tail_half = self.stroke_width * 0.15;
+                let base_half = self.stroke_width * 0.9;
 
-        let x1 = ex - arrow_length * (angle - arrow_angle).cos();
-        let y1 = ey - arrow_length * (angle - arrow_angle).sin();
-        let x2 = ex - arrow_length * (angle + arrow_angle).cos();
-        let y2 = ey - arrow_length * (angle + arrow_angle).sin();
+                cr.move_to((self.start.x + nx * tail_half) * scale, (self.start.y + ny * tail_half) * scale);
+                cr.line_to((base.x + nx * base_half) * scale, (base.y + ny * base_half) * scale);
+                cr.line_to((base.x - nx * base_half) * scale, (base.y - ny * base_half) * scale);
+                cr.line_to((self.start.x - nx * tail_half) * scale, (self.start.y - ny * tail_half) * scale);
+                cr.close_path();
+                let _ = cr.fill();
 
-        cr.move_to(ex, ey);
-        cr.line_to(x1, y1);
-        cr.move_to(ex, ey);
-        cr.line_to(x2, y2);
-        let _ = cr.stroke();
+                Self::add_head_path(cr, self.end, end_angle, head, scale);
+                let _ = cr.fill();
+            }
+            ArrowStyle::Curved => {
+                // Quadratic curve through the control point, expressed as a cubic
+                let base = Self::head_base(self.end, end_angle, inset);
+                let c1 = Point::new(
+                    self.start.x + 2.0 / 3.0 * (self.control.x - self.start.x),
+                    self.start.y + 2.0 / 3.0 * (self.control.y - self.start.y),
+                );
+                let c2 = Point::new(
+                    base.x + 2.0 / 3.0 * (self.control.x - base.x),
+                    base.y + 2.0 / 3.0 * (self.control.y - base.y),
+                );
+                cr.move_to(self.start.x * scale, self.start.y * scale);
+                cr.curve_to(c1.x * scale, c1.y * scale, c2.x * scale, c2.y * scale, base.x * scale, base.y * scale);
+                let _ = cr.stroke();
+
+                Self::add_head_path(cr, self.end, end_angle, head, scale);
+                let _ = cr.fill();
+            }
+        }
+    }
+
+    /// Points along the shaft, used for hit testing and bounds
+    fn shaft_points(&self) -> Vec<Point> {
+        if self.style != ArrowStyle::Curved {
+            return vec![self.start, self.end];
+        }
+
+        (0..=16)
+            .map(|i| {
+                let t = i as f64 / 16.0;
+                let u = 1.0 - t;
+                Point::new(
+                    u * u * self.start.x + 2.0 * u * t * self.control.x + t * t * self.end.x,
+                    u * u * self.start.y + 2.0 * u * t * self.control.y + t * t * self.end.y,
+                )
+            })
+            .collect()
+    }
+
+    /// Distance from `point` to the shaft
+    pub fn distance_to(&self, point: Point) -> f64 {
+        self.shaft_points()
+            .windows(2)
+            .map(|pair| distance_to_segment(point, pair[0], pair[1]))
+            .fold(f64::INFINITY, f64::min)
     }
 
     pub fn bounds(&self) -> (f64, f64, f64, f64) {
-        let x = self.start.x.min(self.end.x);
-        let y = self.start.y.min(self.end.y);
-        let w = (self.end.x - self.start.x).abs();
-        let h = (self.end.y - self.start.y).abs();
-        (x, y, w, h)
+        let points = self.shaft_points();
+        let x1 = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min);
+        let y1 = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min);
+        let x2 = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max);
+        let y2 = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max);
+        (x1, y1, x2 - x1, y2 - y1)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RectAnnotation {
     pub start: Point,
     pub end: Point,
+    #[serde(with = "rgba_serde")]
     pub color: RGBA,
     pub stroke_width: f64,
     pub filled: bool,
+    pub dash: DashStyle,
+    pub corner_radius: f64,
 }
 
 impl RectAnnotation {
-    pub fn new(start: Point, end: Point, color: RGBA, stroke_width: f64, filled: bool) -> Self {
-        Self { start, end, color, stroke_width, filled }
+    pub fn new(
+        start: Point,
+        end: Point,
+        color: RGBA,
+        stroke_width: f64,
+        filled: bool,
+        dash: DashStyle,
+        corner_radius: f64,
+    ) -> Self {
+        Self { start, end, color, stroke_width, filled, dash, corner_radius }
     }
 
     pub fn draw(&self, cr: &cairo::Context, scale: f64) {
@@ -133,12 +528,19 @@ impl RectAnnotation {
         let w = (self.end.x - self.start.x).abs() * scale;
         let h = (self.end.y - self.start.y).abs() * scale;
 
-        cr.rectangle(x, y, w, h);
+        if self.corner_radius > 0.0 {
+            rounded_rectangle(cr, x, y, w, h, self.corner_radius * scale);
+        } else {
+            cr.rectangle(x, y, w, h);
+        }
 
         if self.filled {
             let _ = cr.fill();
         } else {
+            cr.save().unwrap();
+            self.dash.apply(cr, self.stroke_width * scale);
             let _ = cr.stroke();
+            cr.restore().unwrap();
         }
     }
 
@@ -151,17 +553,19 @@ impl RectAnnotation {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LineAnnotation {
     pub start: Point,
     pub end: Point,
+    #[serde(with = "rgba_serde")]
     pub color: RGBA,
     pub stroke_width: f64,
+    pub dash: DashStyle,
 }
 
 impl LineAnnotation {
-    pub fn new(start: Point, end: Point, color: RGBA, stroke_width: f64) -> Self {
-        Self { start, end, color, stroke_width }
+    pub fn new(start: Point, end: Point, color: RGBA, stroke_width: f64, dash: DashStyle) -> Self {
+        Self { start, end, color, stroke_width, dash }
     }
 
     pub fn draw(&self, cr: &cairo::Context, scale: f64) {
@@ -176,7 +580,10 @@ impl LineAnnotation {
 
         cr.move_to(self.start.x * scale, self.start.y * scale);
         cr.line_to(self.end.x * scale, self.end.y * scale);
+        cr.save().unwrap();
+        self.dash.apply(cr, self.stroke_width * scale);
         let _ = cr.stroke();
+        cr.restore().unwrap();
     }
 
     pub fn bounds(&self) -> (f64, f64, f64, f64) {
@@ -188,18 +595,20 @@ impl LineAnnotation {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EllipseAnnotation {
     pub start: Point,
     pub end: Point,
+    #[serde(with = "rgba_serde")]
     pub color: RGBA,
     pub stroke_width: f64,
     pub filled: bool,
+    pub dash: DashStyle,
 }
 
 impl EllipseAnnotation {
-    pub fn new(start: Point, end: Point, color: RGBA, stroke_width: f64, filled: bool) -> Self {
-        Self { start, end, color, stroke_width, filled }
+    pub fn new(start: Point, end: Point, color: RGBA, stroke_width: f64, filled: bool, dash: DashStyle) -> Self {
+        Self { start, end, color, stroke_width, filled, dash }
     }
 
     pub fn draw(&self, cr: &cairo::Context, scale: f64) {
@@ -231,7 +640,10 @@ impl EllipseAnnotation {
             if self.filled {
                 let _ = cr.fill();
             } else {
+                cr.save().unwrap();
+                self.dash.apply(cr, self.stroke_width * scale);
                 let _ = cr.stroke();
+                cr.restore().unwrap();
             }
         }
     }
@@ -245,16 +657,18 @@ impl EllipseAnnotation {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HighlightAnnotation {
     pub start: Point,
     pub end: Point,
+    #[serde(with = "rgba_serde")]
     pub color: RGBA,
+    pub corner_radius: f64,
 }
 
 impl HighlightAnnotation {
-    pub fn new(start: Point, end: Point, color: RGBA) -> Self {
-        Self { start, end, color }
+    pub fn new(start: Point, end: Point, color: RGBA, corner_radius: f64) -> Self {
+        Self { start, end, color, corner_radius }
     }
 
     pub fn draw(&self, cr: &cairo::Context, scale: f64) {
@@ -271,7 +685,11 @@ impl HighlightAnnotation {
         let w = (self.end.x - self.start.x).abs() * scale;
         let h = (self.end.y - self.start.y).abs() * scale;
 
-        cr.rectangle(x, y, w, h);
+        if self.corner_radius > 0.0 {
+            rounded_rectangle(cr, x, y, w, h, self.corner_radius * scale);
+        } else {
+            cr.rectangle(x, y, w, h);
+        }
         let _ = cr.fill();
     }
 
@@ -284,7 +702,7 @@ impl HighlightAnnotation {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BlurAnnotation {
     pub start: Point,
     pub end: Point,
@@ -334,3 +752,797 @@ impl BlurAnnotation {
         (x, y, w, h)
     }
 }
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpotlightShape {
+    #[default]
+    Rectangle,
+    Ellipse,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpotlightEffect {
+    #[default]
+    Dim,
+    Blur,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpotlightAnnotation {
+    pub start: Point,
+    pub end: Point,
+    pub shape: SpotlightShape,
+    pub effect: SpotlightEffect,
+    pub opacity: f64,
+}
+
+impl SpotlightAnnotation {
+    pub fn new(start: Point, end: Point, shape: SpotlightShape, effect: SpotlightEffect, opacity: f64) -> Self {
+        Self { start, end, shape, effect, opacity }
+    }
+
+    /// Add the outline of the spotlight "hole" to the current path
+    pub fn add_hole_path(&self, cr: &cairo::Context, scale: f64) {
+        let x = self.start.x.min(self.end.x) * scale;
+        let y = self.start.y.min(self.end.y) * scale;
+        let w = (self.end.x - self.start.x).abs() * scale;
+        let h = (self.end.y - self.start.y).abs() * scale;
+
+        match self.shape {
+            SpotlightShape::Rectangle => cr.rectangle(x, y, w, h),
+            SpotlightShape::Ellipse => {
+                if w > 0.0 && h > 0.0 {
+                    cr.save().unwrap();
+                    cr.translate(x + w / 2.0, y + h / 2.0);
+                    cr.scale(w / 2.0, h / 2.0);
+                    cr.new_sub_path();
+                    cr.arc(0.0, 0.0, 1.0, 0.0, 2.0 * std::f64::consts::PI);
+                    cr.close_path();
+                    cr.restore().unwrap();
+                }
+            }
+        }
+    }
+
+    /// Dim everything outside this single spotlight.
+    ///
+    /// The canvas renders spotlights together through `render::draw_annotations`
+    /// so that several of them share one dimmed layer; this is the fallback for
+    /// drawing a spotlight on its own.
+    pub fn draw(&self, cr: &cairo::Context, scale: f64) {
+        let Ok((x1, y1, x2, y2)) = cr.clip_extents() else { return };
+
+        cr.save().unwrap();
+        cr.set_fill_rule(cairo::FillRule::EvenOdd);
+        cr.rectangle(x1, y1, x2 - x1, y2 - y1);
+        self.add_hole_path(cr, scale);
+        cr.set_source_rgba(0.0, 0.0, 0.0, self.opacity);
+        let _ = cr.fill();
+        cr.restore().unwrap();
+    }
+
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        let x = self.start.x.min(self.end.x);
+        let y = self.start.y.min(self.end.y);
+        let w = (self.end.x - self.start.x).abs();
+        let h = (self.end.y - self.start.y).abs();
+        (x, y, w, h)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MagnifyShape {
+    #[default]
+    Circle,
+    RoundedRect,
+}
+
+/// An enlarged copy of a region of the screenshot, shown in a callout
+///
+/// `start`/`end` delimit the source region and `center` is the middle of the
+/// callout. The callout content is read from the screenshot at draw time, so
+/// moving the source region updates it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MagnifyAnnotation {
+    pub start: Point,
+    pub end: Point,
+    pub center: Point,
+    pub zoom: f64,
+    pub shape: MagnifyShape,
+    #[serde(with = "rgba_serde")]
+    pub color: RGBA,
+    pub stroke_width: f64,
+}
+
+impl MagnifyAnnotation {
+    /// Handle that moves the source region without resizing it
+    pub const SOURCE_HANDLE: usize = 2;
+    /// Handle that moves the callout
+    pub const CALLOUT_HANDLE: usize = 3;
+
+    pub fn new(start: Point, end: Point, center: Point, zoom: f64, shape: MagnifyShape, color: RGBA, stroke_width: f64) -> Self {
+        Self { start, end, center, zoom, shape, color, stroke_width }
+    }
+
+    pub fn source_center(&self) -> Point {
+        Point::new((self.start.x + self.end.x) / 2.0, (self.start.y + self.end.y) / 2.0)
+    }
+
+    /// Half width and half height of the callout
+    pub fn callout_half_size(&self) -> (f64, f64) {
+        let w = (self.end.x - self.start.x).abs() * self.zoom / 2.0;
+        let h = (self.end.y - self.start.y).abs() * self.zoom / 2.0;
+        match self.shape {
+            MagnifyShape::Circle => {
+                let r = w.max(h);
+                (r, r)
+            }
+            MagnifyShape::RoundedRect => (w, h),
+        }
+    }
+
+    fn add_callout_path(&self, cr: &cairo::Context, scale: f64) {
+        let (hw, hh) = self.callout_half_size();
+        let cx = self.center.x * scale;
+        let cy = self.center.y * scale;
+
+        match self.shape {
+            MagnifyShape::Circle => {
+                cr.new_sub_path();
+                cr.arc(cx, cy, hw * scale, 0.0, 2.0 * std::f64::consts::PI);
+                cr.close_path();
+            }
+            MagnifyShape::RoundedRect => {
+                let radius = (hw.min(hh) * 0.2).min(16.0) * scale;
+                rounded_rectangle(cr, cx - hw * scale, cy - hh * scale, 2.0 * hw * scale, 2.0 * hh * scale, radius);
+            }
+        }
+    }
+
+    /// Distance from the center of a shape to its edge along (dx, dy)
+    fn edge_distance(dx: f64, dy: f64, hw: f64, hh: f64, circle: bool) -> f64 {
+        if circle {
+            return hw;
+        }
+        let tx = if dx.abs() > f64::EPSILON { hw / dx.abs() } else { f64::INFINITY };
+        let ty = if dy.abs() > f64::EPSILON { hh / dy.abs() } else { f64::INFINITY };
+        tx.min(ty) * (dx * dx + dy * dy).sqrt()
+    }
+
+    /// Paint the enlarged screenshot region inside the callout
+    pub fn draw_magnified(&self, cr: &cairo::Context, pixbuf: &gtk::gdk_pixbuf::Pixbuf, scale: f64) {
+        let source = self.source_center();
+
+        cr.save().unwrap();
+        self.add_callout_path(cr, scale);
+        cr.clip();
+
+        // White backing so transparent regions don't show what's underneath
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        let _ = cr.paint();
+
+        cr.translate(self.center.x * scale, self.center.y * scale);
+        cr.scale(self.zoom * scale, self.zoom * scale);
+        cr.translate(-source.x, -source.y);
+        gtk::gdk::prelude::GdkCairoContextExt::set_source_pixbuf(cr, pixbuf, 0.0, 0.0);
+        let _ = cr.paint();
+        cr.restore().unwrap();
+    }
+
+    /// Draw the source outline, the connector and the callout border
+    pub fn draw(&self, cr: &cairo::Context, scale: f64) {
+        cr.set_source_rgba(
+            self.color.red() as f64,
+            self.color.green() as f64,
+            self.color.blue() as f64,
+            self.color.alpha() as f64,
+        );
+        cr.set_line_width(self.stroke_width * scale);
+        cr.set_line_cap(cairo::LineCap::Round);
+
+        let x = self.start.x.min(self.end.x);
+        let y = self.start.y.min(self.end.y);
+        let w = (self.end.x - self.start.x).abs();
+        let h = (self.end.y - self.start.y).abs();
+
+        // Source region
+        cr.rectangle(x * scale, y * scale, w * scale, h * scale);
+        let _ = cr.stroke();
+
+        // Connector from the edge of the source region to the edge of the callout
+        let source = self.source_center();
+        let dx = self.center.x - source.x;
+        let dy = self.center.y - source.y;
+        let length = (dx * dx + dy * dy).sqrt();
+        if length > 0.0 {
+            let (hw, hh) = self.callout_half_size();
+            let from = Self::edge_distance(dx, dy, w / 2.0, h / 2.0, false);
+            let to = length - Self::edge_distance(dx, dy, hw, hh, self.shape == MagnifyShape::Circle);
+            if to > from {
+                let ux = dx / length;
+                let uy = dy / length;
+                cr.move_to((source.x + ux * from) * scale, (source.y + uy * from) * scale);
+                cr.line_to((source.x + ux * to) * scale, (source.y + uy * to) * scale);
+                let _ = cr.stroke();
+            }
+        }
+
+        // Callout border
+        self.add_callout_path(cr, scale);
+        let _ = cr.stroke();
+    }
+
+    pub fn handles(&self) -> Vec<Point> {
+        vec![self.start, self.end, self.source_center(), self.center]
+    }
+
+    pub fn move_handle(&mut self, index: usize, point: Point) {
+        match index {
+            0 => self.start = point,
+            1 => self.end = point,
+            Self::SOURCE_HANDLE => {
+                let source = self.source_center();
+                let (dx, dy) = (point.x - source.x, point.y - source.y);
+                self.start = self.start.offset(dx, dy);
+                self.end = self.end.offset(dx, dy);
+            }
+            Self::CALLOUT_HANDLE => self.center = point,
+            _ => {}
+        }
+    }
+
+    /// Clicking inside the callout moves the callout, inside the source moves the source
+    pub fn hit_test(&self, point: Point) -> Option<Hit> {
+        let (hw, hh) = self.callout_half_size();
+        let dx = point.x - self.center.x;
+        let dy = point.y - self.center.y;
+        let in_callout = match self.shape {
+            MagnifyShape::Circle => dx * dx + dy * dy <= hw * hw,
+            MagnifyShape::RoundedRect => dx.abs() <= hw && dy.abs() <= hh,
+        };
+        if in_callout {
+            return Some(Hit::Handle(Self::CALLOUT_HANDLE));
+        }
+
+        let x = self.start.x.min(self.end.x);
+        let y = self.start.y.min(self.end.y);
+        let w = (self.end.x - self.start.x).abs();
+        let h = (self.end.y - self.start.y).abs();
+        if point.x >= x && point.x <= x + w && point.y >= y && point.y <= y + h {
+            return Some(Hit::Handle(Self::SOURCE_HANDLE));
+        }
+
+        None
+    }
+
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        let (hw, hh) = self.callout_half_size();
+        let x1 = self.start.x.min(self.end.x).min(self.center.x - hw);
+        let y1 = self.start.y.min(self.end.y).min(self.center.y - hh);
+        let x2 = self.start.x.max(self.end.x).max(self.center.x + hw);
+        let y2 = self.start.y.max(self.end.y).max(self.center.y + hh);
+        (x1, y1, x2 - x1, y2 - y1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CalloutStyle {
+    /// Rounded speech bubble with a wedge-shaped tail
+    #[default]
+    Bubble,
+    /// Rounded box with a thin pointer line ending in a dot
+    Box,
+    /// Pill-shaped label with a leader line
+    Tag,
+}
+
+/// Text inside a shape that points at a location
+///
+/// `anchor` is the center of the text box, which grows around it to fit the
+/// text, and `tail` is where the callout points.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalloutAnnotation {
+    pub tail: Point,
+    pub anchor: Point,
+    pub text: String,
+    pub style: CalloutStyle,
+    #[serde(with = "rgba_serde")]
+    pub color: RGBA,
+    pub font_size: f64,
+}
+
+thread_local! {
+    /// Pango context for measuring text, shared so measuring doesn't create one each time
+    static TEXT_CONTEXT: pango::Context = {
+        let context = pangocairo::FontMap::default().create_context();
+        pangocairo::functions::context_set_font_options(&context, text_font_options().as_ref());
+        context
+    };
+
+    /// Callout text sizes by text and font size, as bounds and hit tests ask for them on every pointer move
+    static CALLOUT_SIZES: RefCell<HashMap<(String, u64), (f64, f64)>> = RefCell::new(HashMap::new());
+}
+
+/// Font options for callout text; without hinted metrics, text measured
+/// once keeps its size at any zoom
+fn text_font_options() -> Option<cairo::FontOptions> {
+    let mut options = cairo::FontOptions::new().ok()?;
+    options.set_hint_metrics(cairo::HintMetrics::Off);
+    Some(options)
+}
+
+impl CalloutAnnotation {
+    pub fn new(tail: Point, anchor: Point, text: String, style: CalloutStyle, color: RGBA, font_size: f64) -> Self {
+        Self { tail, anchor, text, style, color, font_size }
+    }
+
+    fn padding(&self) -> f64 {
+        self.font_size * 0.6
+    }
+
+    /// The text laid out in `context`, centered line by line, in image coordinates
+    fn text_layout(&self, context: &pango::Context) -> pango::Layout {
+        let layout = pango::Layout::new(context);
+        let mut font = pango::FontDescription::from_string("Sans Bold");
+        font.set_absolute_size(self.font_size * pango::SCALE as f64);
+        layout.set_font_description(Some(&font));
+        layout.set_alignment(pango::Alignment::Center);
+        layout.set_text(if self.text.is_empty() { " " } else { &self.text });
+        layout
+    }
+
+    /// Size of the text in image coordinates
+    fn text_size(&self) -> (f64, f64) {
+        let key = (self.text.clone(), self.font_size.to_bits());
+        if let Some(size) = CALLOUT_SIZES.with(|cache| cache.borrow().get(&key).copied()) {
+            return size;
+        }
+
+        let (_, logical) = TEXT_CONTEXT.with(|context| self.text_layout(context).extents());
+        let scale = pango::SCALE as f64;
+        let size = (logical.width() as f64 / scale, logical.height() as f64 / scale);
+        CALLOUT_SIZES.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            if cache.len() > 64 {
+                cache.clear();
+            }
+            cache.insert(key, size);
+        });
+        size
+    }
+
+    /// The text box in image coordinates, sized to fit the text
+    pub fn box_rect(&self) -> (f64, f64, f64, f64) {
+        let (width, height) = self.text_size();
+        let padding = self.padding();
+        let w = width + 2.0 * padding;
+        let h = height + 2.0 * padding;
+        (self.anchor.x - w / 2.0, self.anchor.y - h / 2.0, w, h)
+    }
+
+    fn corner_radius(&self, w: f64, h: f64) -> f64 {
+        match self.style {
+            CalloutStyle::Bubble => (h / 2.0).min(self.font_size),
+            CalloutStyle::Box => self.font_size * 0.35,
+            CalloutStyle::Tag => h / 2.0,
+        }
+        .min(w / 2.0)
+    }
+
+    /// Whether the tail lies outside the box, so there is something to point with
+    fn has_tail(&self, x: f64, y: f64, w: f64, h: f64) -> bool {
+        self.tail.x < x || self.tail.x > x + w || self.tail.y < y || self.tail.y > y + h
+    }
+
+    fn add_wedge_path(&self, cr: &cairo::Context, scale: f64, w: f64, h: f64) {
+        let dx = self.tail.x - self.anchor.x;
+        let dy = self.tail.y - self.anchor.y;
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+
+        // Base of the wedge straddles the box center, perpendicular to the tail
+        let half_base = w.min(h) * 0.25;
+        let px = -dy / length * half_base;
+        let py = dx / length * half_base;
+
+        cr.new_sub_path();
+        cr.move_to((self.anchor.x + px) * scale, (self.anchor.y + py) * scale);
+        cr.line_to(self.tail.x * scale, self.tail.y * scale);
+        cr.line_to((self.anchor.x - px) * scale, (self.anchor.y - py) * scale);
+        cr.close_path();
+    }
+
+    pub fn draw(&self, cr: &cairo::Context, scale: f64) {
+        let (x, y, w, h) = self.box_rect();
+        let radius = self.corner_radius(w, h);
+        let has_tail = self.has_tail(x, y, w, h);
+
+        let (r, g, b, a) = (
+            self.color.red() as f64,
+            self.color.green() as f64,
+            self.color.blue() as f64,
+            self.color.alpha() as f64,
+        );
+        let outline = (self.font_size * 0.12).max(1.0);
+
+        cr.save().unwrap();
+        cr.set_line_join(cairo::LineJoin::Round);
+        cr.set_line_cap(cairo::LineCap::Round);
+
+        match self.style {
+            CalloutStyle::Bubble => {
+                // Stroking first and filling second hides the seam between box and wedge
+                rounded_rectangle(cr, x * scale, y * scale, w * scale, h * scale, radius * scale);
+                if has_tail {
+                    self.add_wedge_path(cr, scale, w, h);
+                }
+                cr.set_source_rgba(0.0, 0.0, 0.0, 0.25 * a);
+                cr.set_line_width(2.0 * outline * scale);
+                let _ = cr.stroke_preserve();
+                cr.set_source_rgba(r, g, b, a);
+                let _ = cr.fill();
+            }
+            CalloutStyle::Box | CalloutStyle::Tag => {
+                if has_tail {
+                    cr.set_source_rgba(r, g, b, a);
+                    cr.set_line_width(outline * 2.0 * scale);
+                    cr.move_to(self.anchor.x * scale, self.anchor.y * scale);
+                    cr.line_to(self.tail.x * scale, self.tail.y * scale);
+                    let _ = cr.stroke();
+
+                    if self.style == CalloutStyle::Box {
+                        cr.arc(self.tail.x * scale, self.tail.y * scale, outline * 3.0 * scale, 0.0, 2.0 * std::f64::consts::PI);
+                        let _ = cr.fill();
+                    }
+                }
+
+                rounded_rectangle(cr, x * scale, y * scale, w * scale, h * scale, radius * scale);
+                cr.set_source_rgba(r, g, b, a);
+                let _ = cr.fill();
+            }
+        }
+
+        // Text in black or white, whichever reads better on the fill color
+        let luminance = 0.299 * r + 0.587 * g + 0.114 * b;
+        let text_shade = if luminance > 0.6 { 0.0 } else { 1.0 };
+        cr.set_source_rgb(text_shade, text_shade, text_shade);
+
+        // Laid out at image size like when measured, then scaled with the rest
+        let context = pangocairo::functions::create_context(cr);
+        pangocairo::functions::context_set_font_options(&context, text_font_options().as_ref());
+        let layout = self.text_layout(&context);
+        let (_, logical) = layout.extents();
+        let padding = self.padding();
+        cr.translate((x + padding) * scale, (y + padding) * scale);
+        cr.scale(scale, scale);
+        cr.translate(-logical.x() as f64 / pango::SCALE as f64, -logical.y() as f64 / pango::SCALE as f64);
+        pangocairo::functions::update_layout(cr, &layout);
+        pangocairo::functions::show_layout(cr, &layout);
+
+        cr.restore().unwrap();
+    }
+
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        let (x, y, w, h) = self.box_rect();
+        let x1 = x.min(self.tail.x);
+        let y1 = y.min(self.tail.y);
+        let x2 = (x + w).max(self.tail.x);
+        let y2 = (y + h).max(self.tail.y);
+        (x1, y1, x2 - x1, y2 - y1)
+    }
+}
+
+/// Built-in vector stamps, drawn in the stamp color
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StampSymbol {
+    #[default]
+    Check,
+    Cross,
+    Warning,
+    Info,
+    Question,
+    Star,
+}
+
+impl StampSymbol {
+    pub const ALL: [StampSymbol; 6] = [
+        StampSymbol::Check,
+        StampSymbol::Cross,
+        StampSymbol::Warning,
+        StampSymbol::Info,
+        StampSymbol::Question,
+        StampSymbol::Star,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StampSymbol::Check => "Checkmark",
+            StampSymbol::Cross => "Cross",
+            StampSymbol::Warning => "Warning",
+            StampSymbol::Info => "Information",
+            StampSymbol::Question => "Question",
+            StampSymbol::Star => "Star",
+        }
+    }
+
+    /// Draw the symbol filling the unit square at the origin
+    pub fn draw_unit(&self, cr: &cairo::Context, color: &RGBA) {
+        let pi = std::f64::consts::PI;
+        let (r, g, b, a) = (color.red() as f64, color.green() as f64, color.blue() as f64, color.alpha() as f64);
+
+        // Marks inside the badge in black or white, whichever reads better
+        let luminance = 0.299 * r + 0.587 * g + 0.114 * b;
+        let mark = if luminance > 0.6 { 0.0 } else { 1.0 };
+
+        cr.set_line_cap(cairo::LineCap::Round);
+        cr.set_line_join(cairo::LineJoin::Round);
+        cr.set_source_rgba(r, g, b, a);
+
+        match self {
+            StampSymbol::Warning => {
+                cr.move_to(0.5, 0.08);
+                cr.line_to(0.95, 0.9);
+                cr.line_to(0.05, 0.9);
+                cr.close_path();
+                cr.set_line_width(0.08);
+                let _ = cr.stroke_preserve();
+                let _ = cr.fill();
+            }
+            StampSymbol::Star => {
+                for i in 0..10 {
+                    let radius = if i % 2 == 0 { 0.5 } else { 0.2 };
+                    let angle = -pi / 2.0 + i as f64 * pi / 5.0;
+                    cr.line_to(0.5 + radius * angle.cos(), 0.53 + radius * angle.sin());
+                }
+                cr.close_path();
+                let _ = cr.fill();
+                return;
+            }
+            _ => {
+                cr.arc(0.5, 0.5, 0.5, 0.0, 2.0 * pi);
+                let _ = cr.fill();
+            }
+        }
+
+        cr.set_source_rgba(mark, mark, mark, a);
+        cr.set_line_width(0.1);
+        match self {
+            StampSymbol::Check => {
+                cr.move_to(0.28, 0.52);
+                cr.line_to(0.44, 0.68);
+                cr.line_to(0.73, 0.36);
+                let _ = cr.stroke();
+            }
+            StampSymbol::Cross => {
+                cr.move_to(0.33, 0.33);
+                cr.line_to(0.67, 0.67);
+                cr.move_to(0.67, 0.33);
+                cr.line_to(0.33, 0.67);
+                let _ = cr.stroke();
+            }
+            StampSymbol::Warning => {
+                cr.move_to(0.5, 0.38);
+                cr.line_to(0.5, 0.6);
+                let _ = cr.stroke();
+                cr.arc(0.5, 0.75, 0.055, 0.0, 2.0 * pi);
+                let _ = cr.fill();
+            }
+            StampSymbol::Info => {
+                cr.move_to(0.5, 0.46);
+                cr.line_to(0.5, 0.74);
+                let _ = cr.stroke();
+                cr.arc(0.5, 0.28, 0.065, 0.0, 2.0 * pi);
+                let _ = cr.fill();
+            }
+            StampSymbol::Question => {
+                // Hook over the top and down to the middle, then the dot
+                cr.arc(0.5, 0.36, 0.14, pi, 2.5 * pi);
+                cr.line_to(0.5, 0.6);
+                let _ = cr.stroke();
+                cr.arc(0.5, 0.75, 0.055, 0.0, 2.0 * pi);
+                let _ = cr.fill();
+            }
+            StampSymbol::Star => {}
+        }
+    }
+}
+
+/// What a stamp shows
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StampContent {
+    Symbol(StampSymbol),
+    /// An emoji, drawn with the system emoji font
+    Emoji(String),
+    /// The contents of a PNG or SVG file chosen by the user, kept with the
+    /// stamp so it still shows if the file is moved or the stamp is pasted elsewhere
+    Image(StampImage),
+}
+
+/// The file behind an image stamp
+///
+/// Copies of the annotation share the bytes, and their hash is worked out
+/// once to find the decoded image in the cache.
+#[derive(Debug, Clone)]
+pub struct StampImage {
+    bytes: gtk::glib::Bytes,
+    hash: u64,
+}
+
+impl StampImage {
+    pub fn new(data: Vec<u8>) -> Self {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        data.hash(&mut hasher);
+        Self { bytes: gtk::glib::Bytes::from_owned(data), hash: hasher.finish() }
+    }
+}
+
+impl PartialEq for StampImage {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.bytes == other.bytes
+    }
+}
+
+/// Stored as base64, which is far smaller in JSON than a list of numbers
+impl Serialize for StampImage {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        gtk::glib::base64_encode(&self.bytes).as_str().serialize(serializer)
+    }
+}
+
+/// Fails on anything that isn't a readable image, so a damaged clipboard or
+/// preset file doesn't turn into an invisible stamp
+impl<'de> Deserialize<'de> for StampImage {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let text = String::deserialize(deserializer)?;
+        let data = gtk::glib::base64_decode(&text);
+        // glib skips over anything that isn't base64, so check nothing was
+        if data.is_empty() || gtk::glib::base64_encode(&data).as_str() != text {
+            return Err(D::Error::custom("image stamp is not valid base64"));
+        }
+
+        let image = Self::new(data);
+        let stream = gtk::gio::MemoryInputStream::from_bytes(&image.bytes);
+        Pixbuf::from_stream(&stream, gtk::gio::Cancellable::NONE)
+            .map_err(|e| D::Error::custom(format!("image stamp can't be read: {}", e)))?;
+        Ok(image)
+    }
+}
+
+impl Default for StampContent {
+    fn default() -> Self {
+        StampContent::Symbol(StampSymbol::default())
+    }
+}
+
+/// A symbol, emoji or picture placed in a box
+///
+/// The content keeps its aspect ratio and is centered in the box. Emoji and
+/// images are rendered for the final pixel size, so they stay sharp when
+/// zoomed in and in full-resolution exports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StampAnnotation {
+    pub start: Point,
+    pub end: Point,
+    pub content: StampContent,
+    /// Only used by symbols
+    #[serde(with = "rgba_serde")]
+    pub color: RGBA,
+}
+
+thread_local! {
+    /// Decoded stamp images by content hash and pixel size, so redraws don't decode them again
+    static STAMP_IMAGES: RefCell<HashMap<(u64, i32, i32), Option<Pixbuf>>> = RefCell::new(HashMap::new());
+}
+
+impl StampAnnotation {
+    /// Size of a stamp placed with a click instead of a drag
+    pub const DEFAULT_SIZE: f64 = 64.0;
+
+    pub fn new(start: Point, end: Point, content: StampContent, color: RGBA) -> Self {
+        Self { start, end, content, color }
+    }
+
+    /// A square stamp `size` pixels wide centered on `center`
+    pub fn centered(center: Point, size: f64, content: StampContent, color: RGBA) -> Self {
+        let half = size / 2.0;
+        Self::new(center.offset(-half, -half), center.offset(half, half), content, color)
+    }
+
+    pub fn draw(&self, cr: &cairo::Context, scale: f64) {
+        let (x, y, w, h) = self.bounds();
+        let (x, y, w, h) = (x * scale, y * scale, w * scale, h * scale);
+        if w < 1.0 || h < 1.0 {
+            return;
+        }
+
+        cr.save().unwrap();
+        match &self.content {
+            StampContent::Symbol(symbol) => {
+                let size = w.min(h);
+                cr.translate(x + (w - size) / 2.0, y + (h - size) / 2.0);
+                cr.scale(size, size);
+                symbol.draw_unit(cr, &self.color);
+            }
+            StampContent::Emoji(emoji) => Self::draw_emoji(cr, emoji, x, y, w, h),
+            StampContent::Image(image) => Self::draw_image(cr, image, x, y, w, h),
+        }
+        cr.restore().unwrap();
+    }
+
+    fn draw_emoji(cr: &cairo::Context, emoji: &str, x: f64, y: f64, w: f64, h: f64) {
+        // Lay out at a reference size, then scale the result into the box
+        let layout = pangocairo::functions::create_layout(cr);
+        let mut font = pango::FontDescription::from_string("emoji");
+        font.set_absolute_size(100.0 * pango::SCALE as f64);
+        layout.set_font_description(Some(&font));
+        layout.set_text(emoji);
+
+        let (_, logical) = layout.pixel_extents();
+        if logical.width() <= 0 || logical.height() <= 0 {
+            return;
+        }
+        let (lw, lh) = (logical.width() as f64, logical.height() as f64);
+        let fit = (w / lw).min(h / lh);
+
+        cr.translate(x + (w - lw * fit) / 2.0, y + (h - lh * fit) / 2.0);
+        cr.scale(fit, fit);
+        cr.translate(-logical.x() as f64, -logical.y() as f64);
+        pangocairo::functions::update_layout(cr, &layout);
+        pangocairo::functions::show_layout(cr, &layout);
+    }
+
+    fn draw_image(cr: &cairo::Context, image: &StampImage, x: f64, y: f64, w: f64, h: f64) {
+        // Decode at the size the box covers on the target surface
+        let (dw, dh) = cr.user_to_device_distance(w, h).unwrap_or((w, h));
+        let (pw, ph) = (dw.abs().ceil().max(1.0) as i32, dh.abs().ceil().max(1.0) as i32);
+        let key = (image.hash, pw, ph);
+
+        let pixbuf = STAMP_IMAGES.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            if cache.len() > 32 {
+                cache.clear();
+            }
+            cache
+                .entry(key)
+                .or_insert_with(|| {
+                    let stream = gtk::gio::MemoryInputStream::from_bytes(&image.bytes);
+                    Pixbuf::from_stream_at_scale(&stream, pw, ph, true, gtk::gio::Cancellable::NONE).ok()
+                })
+                .clone()
+        });
+
+        let Some(pixbuf) = pixbuf else {
+            // Unreadable image: show where it would go
+            cr.set_source_rgba(0.5, 0.5, 0.5, 0.8);
+            cr.set_line_width(2.0);
+            cr.set_dash(&[6.0, 4.0], 0.0);
+            cr.rectangle(x, y, w, h);
+            let _ = cr.stroke();
+            return;
+        };
+
+        // Back from device pixels to user space
+        let fit_x = w / pw as f64;
+        let fit_y = h / ph as f64;
+        let (iw, ih) = (pixbuf.width() as f64 * fit_x, pixbuf.height() as f64 * fit_y);
+
+        cr.translate(x + (w - iw) / 2.0, y + (h - ih) / 2.0);
+        cr.scale(fit_x, fit_y);
+        gtk::gdk::prelude::GdkCairoContextExt::set_source_pixbuf(cr, &pixbuf, 0.0, 0.0);
+        cr.source().set_filter(cairo::Filter::Good);
+        let _ = cr.paint();
+    }
+
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        let x = self.start.x.min(self.end.x);
+        let y = self.start.y.min(self.end.y);
+        let w = (self.end.x - self.start.x).abs();
+        let h = (self.end.y - self.start.y).abs();
+        (x, y, w, h)
+    }
+}
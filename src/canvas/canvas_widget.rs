@@ -1,46 +1,120 @@
+use adw::prelude::*;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
-use gtk::{gdk, glib, graphene};
+use gtk::{gdk, gio, glib, graphene};
 use std::cell::{Cell, RefCell};
-
-use super::annotations::{Annotation, ArrowAnnotation, BlurAnnotation, EllipseAnnotation, HighlightAnnotation, LineAnnotation, Point, RectAnnotation};
-use super::history::History;
+use std::sync::OnceLock;
+
+use super::annotations::{
+    ANNOTATIONS_MIME_TYPE, Annotation, ArrowAnnotation, ArrowStyle, BlurAnnotation, CalloutAnnotation, CalloutStyle, DashStyle, EllipseAnnotation, HighlightAnnotation, Hit, LineAnnotation,
+    MagnifyAnnotation, MagnifyShape, Point, RectAnnotation, SpotlightAnnotation, SpotlightEffect, SpotlightShape, StampAnnotation, StampContent,
+};
+use super::document::{Document, Layer};
+use super::frame::FrameSettings;
+use super::history::{AddCommand, BatchCommand, Command, CropCommand, History, HistoryEntry, LayerCommand, ModifyCommand, MoveCommand, RemoveCommand, ReorderCommand};
+use super::presets::StylePreset;
+use super::render;
+use super::snapping::{self, Guide, SnapTargets};
 use super::tools::Tool;
 use crate::capture::Screenshot;
+use crate::ocr::Word;
+use crate::settings;
+
+/// Recognized words less certain than this aren't snapped to
+const MIN_SNAP_CONFIDENCE: f32 = 60.0;
 
 mod imp {
     use super::*;
 
     pub struct CanvasWidget {
         pub screenshot: RefCell<Option<Screenshot>>,
-        pub annotations: RefCell<Vec<Annotation>>,
+        pub document: RefCell<Document>,
         pub history: RefCell<History>,
         pub current_tool: Cell<Tool>,
         pub primary_color: RefCell<gdk::RGBA>,
         pub stroke_width: Cell<f64>,
+        pub filled: Cell<bool>,
+        pub arrow_style: Cell<ArrowStyle>,
+        pub dash_style: Cell<DashStyle>,
+        pub corner_radius: Cell<f64>,
+        pub spotlight_shape: Cell<SpotlightShape>,
+        pub spotlight_effect: Cell<SpotlightEffect>,
+        pub spotlight_opacity: Cell<f64>,
+        pub magnify_zoom: Cell<f64>,
+        pub magnify_shape: Cell<MagnifyShape>,
+        pub callout_style: Cell<CalloutStyle>,
+        pub font_size: Cell<f64>,
+        pub stamp: RefCell<StampContent>,
+        pub frame: RefCell<FrameSettings>,
+        /// Export HiDPI screenshots at their full resolution rather than scaled to 1x
+        pub export_native: Cell<bool>,
+        pub selected: Cell<Option<usize>>,
+        pub edit_hit: Cell<Option<Hit>>,
+        pub edit_original: RefCell<Option<Annotation>>,
+        pub edit_offset: Cell<(f64, f64)>,
         pub scale: Cell<f64>,
         pub offset_x: Cell<f64>,
         pub offset_y: Cell<f64>,
         pub drag_start: Cell<Option<(f64, f64)>>,
         pub drag_current: Cell<Option<(f64, f64)>>,
+        /// Start and end of the shape being dragged out, in image coordinates,
+        /// worked out on each motion so drawing only reads them
+        pub drag_points: Cell<Option<(Point, Point)>>,
         pub is_drawing: Cell<bool>,
+        pub drag_modifiers: Cell<gdk::ModifierType>,
+        pub space_held: Cell<bool>,
+        pub snapping: Cell<bool>,
+        pub show_grid: Cell<bool>,
+        pub ui_edges: RefCell<SnapTargets>,
+        /// Words found by text recognition, also snapped to
+        pub recognized_words: RefCell<Vec<Word>>,
+        /// Area last dragged out with the text tool
+        pub text_region: Cell<Option<(f64, f64, f64, f64)>>,
+        pub guides: RefCell<Vec<Guide>>,
     }
 
     impl Default for CanvasWidget {
         fn default() -> Self {
             Self {
                 screenshot: RefCell::new(None),
-                annotations: RefCell::new(Vec::new()),
+                document: RefCell::new(Document::new()),
                 history: RefCell::new(History::new()),
                 current_tool: Cell::new(Tool::Arrow),
                 primary_color: RefCell::new(gdk::RGBA::new(1.0, 0.0, 0.0, 1.0)),
                 stroke_width: Cell::new(3.0),
+                filled: Cell::new(false),
+                arrow_style: Cell::new(ArrowStyle::Open),
+                dash_style: Cell::new(DashStyle::Solid),
+                corner_radius: Cell::new(0.0),
+                spotlight_shape: Cell::new(SpotlightShape::Rectangle),
+                spotlight_effect: Cell::new(SpotlightEffect::Dim),
+                spotlight_opacity: Cell::new(0.6),
+                magnify_zoom: Cell::new(2.0),
+                magnify_shape: Cell::new(MagnifyShape::Circle),
+                callout_style: Cell::new(CalloutStyle::Bubble),
+                font_size: Cell::new(16.0),
+                stamp: RefCell::new(StampContent::default()),
+                frame: RefCell::new(FrameSettings::default()),
+                export_native: Cell::new(true),
+                selected: Cell::new(None),
+                edit_hit: Cell::new(None),
+                edit_original: RefCell::new(None),
+                edit_offset: Cell::new((0.0, 0.0)),
                 scale: Cell::new(1.0),
                 offset_x: Cell::new(0.0),
                 offset_y: Cell::new(0.0),
                 drag_start: Cell::new(None),
                 drag_current: Cell::new(None),
+                drag_points: Cell::new(None),
                 is_drawing: Cell::new(false),
+                drag_modifiers: Cell::new(gdk::ModifierType::empty()),
+                space_held: Cell::new(false),
+                snapping: Cell::new(true),
+                show_grid: Cell::new(false),
+                ui_edges: RefCell::new(SnapTargets::new()),
+                recognized_words: RefCell::new(Vec::new()),
+                text_region: Cell::new(None),
+                guides: RefCell::new(Vec::new()),
             }
         }
     }
@@ -57,11 +131,28 @@ mod imp {
     }
 
     impl ObjectImpl for CanvasWidget {
+        fn signals() -> &'static [glib::subclass::Signal] {
+            static SIGNALS: OnceLock<Vec<glib::subclass::Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                // Emitted whenever undo history or the position in it changes,
+                // whenever a different annotation is selected, and when the tool
+                // or a preset changes the tool and style settings
+                vec![
+                    glib::subclass::Signal::builder("history-changed").build(),
+                    glib::subclass::Signal::builder("selection-changed").build(),
+                    glib::subclass::Signal::builder("style-changed").build(),
+                ]
+            })
+        }
+
         fn constructed(&self) {
             self.parent_constructed();
 
             let obj = self.obj();
 
+            // Focusable so Space can be read while dragging
+            obj.set_focusable(true);
+
             // Set up event controllers
             obj.setup_event_controllers();
         }
@@ -100,55 +191,49 @@ mod imp {
                 gtk::gdk::prelude::GdkCairoContextExt::set_source_pixbuf(&cr, pixbuf, 0.0, 0.0);
                 let _ = cr.paint();
 
-                // Draw existing annotations
-                for annotation in self.annotations.borrow().iter() {
-                    annotation.draw(&cr, 1.0);
+                // Draw the annotation currently being created on top of the existing ones
+                let tool = self.current_tool.get();
+                let cropping = tool == Tool::Crop;
+                let preview = if self.is_drawing.get() && !cropping {
+                    self.drag_points.get().and_then(|(start, end)| widget.create_annotation(start, end))
+                } else {
+                    None
+                };
+
+                let document = self.document.borrow();
+                render::draw_annotations(&cr, pixbuf, document.visible_annotations().chain(preview.as_ref()));
+
+                // Outline the selected annotation and show its handles
+                if let Some(annotation) = self.selected.get().and_then(|i| document.annotation(i)) {
+                    widget.draw_selection(&cr, annotation, scale);
                 }
 
-                // Draw current annotation being created
-                if self.is_drawing.get() {
-                    if let (Some(start), Some(current)) = (self.drag_start.get(), self.drag_current.get()) {
-                        let start_pt = Point::new(
-                            (start.0 - offset_x) / scale,
-                            (start.1 - offset_y) / scale,
-                        );
-                        let end_pt = Point::new(
-                            (current.0 - offset_x) / scale,
-                            (current.1 - offset_y) / scale,
-                        );
-
-                        let color = self.primary_color.borrow().clone();
-                        let stroke_width = self.stroke_width.get();
-
-                        match self.current_tool.get() {
-                            Tool::Arrow => {
-                                let arrow = ArrowAnnotation::new(start_pt, end_pt, color, stroke_width);
-                                arrow.draw(&cr, 1.0);
-                            }
-                            Tool::Rectangle => {
-                                let rect = RectAnnotation::new(start_pt, end_pt, color, stroke_width, false);
-                                rect.draw(&cr, 1.0);
-                            }
-                            Tool::Line => {
-                                let line = LineAnnotation::new(start_pt, end_pt, color, stroke_width);
-                                line.draw(&cr, 1.0);
-                            }
-                            Tool::Ellipse => {
-                                let ellipse = EllipseAnnotation::new(start_pt, end_pt, color, stroke_width, false);
-                                ellipse.draw(&cr, 1.0);
-                            }
-                            Tool::Highlight => {
-                                let highlight = HighlightAnnotation::new(start_pt, end_pt, color);
-                                highlight.draw(&cr, 1.0);
-                            }
-                            Tool::Blur => {
-                                let blur = BlurAnnotation::new(start_pt, end_pt);
-                                blur.draw(&cr, 1.0);
-                            }
-                        }
-                    }
+                // Show the crop being dragged, or the current one
+                let crop = if self.is_drawing.get() && cropping {
+                    self.drag_points.get().map(|(start, end)| rect_between(start, end))
+                } else {
+                    document.crop
+                };
+                if let Some(crop) = crop {
+                    widget.draw_crop(&cr, crop, pixbuf.width() as f64, pixbuf.height() as f64, scale);
                 }
 
+                // Show the text area being dragged, or the one last read
+                let text_region = if self.is_drawing.get() && tool == Tool::Text {
+                    self.drag_points.get().map(|(start, end)| rect_between(start, end))
+                } else {
+                    self.text_region.get().filter(|_| tool == Tool::Text)
+                };
+                if let Some(region) = text_region {
+                    widget.draw_text_region(&cr, region, scale);
+                }
+
+                if self.show_grid.get() {
+                    widget.draw_grid(&cr, pixbuf.width() as f64, pixbuf.height() as f64, scale);
+                }
+
+                widget.draw_guides(&cr, pixbuf.width() as f64, pixbuf.height() as f64, scale);
+
                 cr.restore().unwrap();
             }
         }
@@ -188,16 +273,16 @@ impl CanvasWidget {
         click.connect_pressed(glib::clone!(
             #[weak(rename_to = canvas)]
             self,
-            move |_, _, x, y| {
-                canvas.on_press(x, y);
+            move |gesture, n_press, x, y| {
+                canvas.on_press(n_press, x, y, gesture.current_event_state());
             }
         ));
 
         click.connect_released(glib::clone!(
             #[weak(rename_to = canvas)]
             self,
-            move |_, _, x, y| {
-                canvas.on_release(x, y);
+            move |gesture, _, x, y| {
+                canvas.on_release(x, y, gesture.current_event_state());
             }
         ));
 
@@ -208,12 +293,31 @@ impl CanvasWidget {
         motion.connect_motion(glib::clone!(
             #[weak(rename_to = canvas)]
             self,
-            move |_, x, y| {
-                canvas.on_motion(x, y);
+            move |controller, x, y| {
+                canvas.on_motion(x, y, controller.current_event_state());
             }
         ));
         self.add_controller(motion);
 
+        // Keyboard controller for Space (reposition while drawing), arrow
+        // keys (nudge the selection) and Tab (cycle the selection)
+        let keys = gtk::EventControllerKey::new();
+        keys.connect_key_pressed(glib::clone!(
+            #[weak(rename_to = canvas)]
+            self,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, keyval, _, state| canvas.on_key_pressed(keyval, state)
+        ));
+        keys.connect_key_released(glib::clone!(
+            #[weak(rename_to = canvas)]
+            self,
+            move |_, keyval, _, _| {
+                canvas.on_key_released(keyval);
+            }
+        ));
+        self.add_controller(keys);
+
         // Scroll controller for zoom
         let scroll = gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::VERTICAL);
         scroll.connect_scroll(glib::clone!(
@@ -229,75 +333,737 @@ impl CanvasWidget {
         self.add_controller(scroll);
     }
 
-    fn on_press(&self, x: f64, y: f64) {
+    fn on_press(&self, n_press: i32, x: f64, y: f64, modifiers: gdk::ModifierType) {
         let imp = self.imp();
+        self.grab_focus();
+
+        if imp.current_tool.get() == Tool::Select {
+            self.begin_edit(x, y);
+
+            // Double-clicking a callout edits its text
+            if n_press == 2 {
+                if let Some(index) = imp.selected.get() {
+                    if matches!(imp.document.borrow().annotation(index), Some(Annotation::Callout(_))) {
+                        imp.edit_hit.set(None);
+                        imp.edit_original.take();
+                        imp.drag_start.set(None);
+                        self.edit_callout_text(index);
+                    }
+                }
+            }
+            return;
+        }
+
         if imp.screenshot.borrow().is_some() {
             imp.drag_start.set(Some((x, y)));
             imp.drag_current.set(Some((x, y)));
+            imp.drag_modifiers.set(modifiers);
             imp.is_drawing.set(true);
+            self.update_drag_points();
         }
     }
 
-    fn on_motion(&self, x: f64, y: f64) {
+    fn on_motion(&self, x: f64, y: f64, modifiers: gdk::ModifierType) {
         let imp = self.imp();
+        if imp.edit_hit.get().is_some() {
+            self.update_edit(x, y);
+            return;
+        }
+
         if imp.is_drawing.get() {
+            // Holding Space moves the whole shape instead of resizing it
+            if let (true, Some(start), Some(current)) = (imp.space_held.get(), imp.drag_start.get(), imp.drag_current.get()) {
+                imp.drag_start.set(Some((start.0 + x - current.0, start.1 + y - current.1)));
+            }
             imp.drag_current.set(Some((x, y)));
+            imp.drag_modifiers.set(modifiers);
+            self.update_drag_points();
             self.queue_draw();
         }
     }
 
-    fn on_release(&self, x: f64, y: f64) {
+    fn on_key_pressed(&self, keyval: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
         let imp = self.imp();
+        if keyval == gdk::Key::space && imp.is_drawing.get() {
+            imp.space_held.set(true);
+            return glib::Propagation::Stop;
+        }
 
-        if !imp.is_drawing.get() {
+        if state.intersects(gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::ALT_MASK) {
+            return glib::Propagation::Proceed;
+        }
+
+        let step = if state.contains(gdk::ModifierType::SHIFT_MASK) { 10.0 } else { 1.0 };
+        let handled = match keyval {
+            gdk::Key::Left => self.nudge_selected(-step, 0.0),
+            gdk::Key::Right => self.nudge_selected(step, 0.0),
+            gdk::Key::Up => self.nudge_selected(0.0, -step),
+            gdk::Key::Down => self.nudge_selected(0.0, step),
+            gdk::Key::Tab => self.cycle_selection(true),
+            gdk::Key::ISO_Left_Tab => self.cycle_selection(false),
+            _ => false,
+        };
+
+        if handled {
+            glib::Propagation::Stop
+        } else {
+            glib::Propagation::Proceed
+        }
+    }
+
+    fn on_key_released(&self, keyval: gdk::Key) {
+        match keyval {
+            gdk::Key::space => self.imp().space_held.set(false),
+            // Holding an arrow key makes one undo step, each separate press another
+            gdk::Key::Left | gdk::Key::Right | gdk::Key::Up | gdk::Key::Down => {
+                self.imp().history.borrow_mut().seal();
+            }
+            _ => {}
+        }
+    }
+
+    /// Move the selected annotation by (dx, dy) image pixels, if it isn't locked
+    fn nudge_selected(&self, dx: f64, dy: f64) -> bool {
+        let imp = self.imp();
+        let Some(index) = imp.selected.get() else { return false };
+        if imp.edit_hit.get().is_some() {
+            return true;
+        }
+
+        let mut document = imp.document.borrow_mut();
+        let Some(layer) = document.layers.get(index).filter(|layer| !layer.locked) else { return false };
+        let command = MoveCommand {
+            index,
+            dx,
+            dy,
+            name: layer.annotation.name(),
+        };
+        imp.history.borrow_mut().execute_continuous(command, &mut document);
+        drop(document);
+
+        self.history_changed();
+        true
+    }
+
+    /// Select the next (or previous) visible, unlocked annotation, wrapping around
+    fn cycle_selection(&self, forward: bool) -> bool {
+        let imp = self.imp();
+        let candidates: Vec<usize> = imp
+            .document
+            .borrow()
+            .layers
+            .iter()
+            .enumerate()
+            .filter(|(_, layer)| layer.visible && !layer.locked)
+            .map(|(i, _)| i)
+            .collect();
+        if candidates.is_empty() || imp.is_drawing.get() || imp.edit_hit.get().is_some() {
+            return false;
+        }
+
+        let next = match imp.selected.get() {
+            Some(current) if forward => candidates.iter().copied().find(|&i| i > current).unwrap_or(candidates[0]),
+            Some(current) => candidates.iter().rev().copied().find(|&i| i < current).unwrap_or(candidates[candidates.len() - 1]),
+            None if forward => candidates[0],
+            None => candidates[candidates.len() - 1],
+        };
+
+        // Keyboard selection only makes sense with the select tool
+        self.set_tool(Tool::Select);
+        self.set_selected(Some(next));
+        true
+    }
+
+    /// Work out where the shape being drawn goes, and its snapping guides,
+    /// for drawing to read
+    fn update_drag_points(&self) {
+        let points = self.compute_drag_points();
+        self.imp().drag_points.set(points);
+    }
+
+    /// Start and end of the shape being drawn, in image coordinates, with modifiers applied
+    ///
+    /// Shift constrains lines and arrows to 15° steps and boxes to squares or
+    /// circles; Alt makes the press point the center of the shape.
+    fn compute_drag_points(&self) -> Option<(Point, Point)> {
+        let imp = self.imp();
+        let (start, current) = (imp.drag_start.get()?, imp.drag_current.get()?);
+        let modifiers = imp.drag_modifiers.get();
+
+        let mut start = self.to_image(start.0, start.1);
+        let mut end = self.to_image(current.0, current.1);
+        let tool = imp.current_tool.get();
+
+        // Snap both ends, unless Shift is already constraining the shape
+        let mut guides = Vec::new();
+        if imp.snapping.get() && !modifiers.contains(gdk::ModifierType::SHIFT_MASK) {
+            let targets = self.snap_targets(None);
+            let threshold = self.snap_threshold();
+            start = targets.snap_point(start, threshold, &mut guides);
+            end = targets.snap_point(end, threshold, &mut guides);
+        }
+        imp.guides.replace(guides);
+
+        if modifiers.contains(gdk::ModifierType::SHIFT_MASK) {
+            let dx = end.x - start.x;
+            let dy = end.y - start.y;
+            match tool {
+                Tool::Arrow | Tool::Line => {
+                    let step = std::f64::consts::PI / 12.0;
+                    let angle = (dy.atan2(dx) / step).round() * step;
+                    let length = (dx * dx + dy * dy).sqrt();
+                    end = Point::new(start.x + length * angle.cos(), start.y + length * angle.sin());
+                }
+                Tool::Rectangle
+                | Tool::Ellipse
+                | Tool::Highlight
+                | Tool::Blur
+                | Tool::Spotlight
+                | Tool::Magnify
+                | Tool::Stamp
+                | Tool::Crop => {
+                    let side = dx.abs().max(dy.abs());
+                    end = Point::new(start.x + side.copysign(dx), start.y + side.copysign(dy));
+                }
+                Tool::Select | Tool::Callout | Tool::Text => {}
+            }
+        }
+
+        if modifiers.contains(gdk::ModifierType::ALT_MASK) {
+            let mirrored = Point::new(2.0 * start.x - end.x, 2.0 * start.y - end.y);
+            return Some((mirrored, end));
+        }
+
+        Some((start, end))
+    }
+
+    fn on_release(&self, x: f64, y: f64, modifiers: gdk::ModifierType) {
+        let imp = self.imp();
+
+        if imp.edit_hit.get().is_some() {
+            self.end_edit(x, y);
             return;
         }
 
-        if let Some(start) = imp.drag_start.get() {
-            let scale = imp.scale.get();
-            let offset_x = imp.offset_x.get();
-            let offset_y = imp.offset_y.get();
+        if !imp.is_drawing.get() {
+            return;
+        }
 
-            let start_pt = Point::new(
-                (start.0 - offset_x) / scale,
-                (start.1 - offset_y) / scale,
-            );
-            let end_pt = Point::new(
-                (x - offset_x) / scale,
-                (y - offset_y) / scale,
-            );
+        imp.drag_current.set(Some((x, y)));
+        imp.drag_modifiers.set(modifiers);
+        self.update_drag_points();
 
+        if let Some((start_pt, end_pt)) = imp.drag_points.get() {
             // Only create annotation if there's meaningful distance
             let dist = ((end_pt.x - start_pt.x).powi(2) + (end_pt.y - start_pt.y).powi(2)).sqrt();
-            if dist > 5.0 {
-                let color = imp.primary_color.borrow().clone();
-                let stroke_width = imp.stroke_width.get();
-
-                let annotation = match imp.current_tool.get() {
-                    Tool::Arrow => Annotation::Arrow(ArrowAnnotation::new(start_pt, end_pt, color, stroke_width)),
-                    Tool::Rectangle => Annotation::Rectangle(RectAnnotation::new(start_pt, end_pt, color, stroke_width, false)),
-                    Tool::Line => Annotation::Line(LineAnnotation::new(start_pt, end_pt, color, stroke_width)),
-                    Tool::Ellipse => Annotation::Ellipse(EllipseAnnotation::new(start_pt, end_pt, color, stroke_width, false)),
-                    Tool::Highlight => Annotation::Highlight(HighlightAnnotation::new(start_pt, end_pt, color)),
-                    Tool::Blur => Annotation::Blur(BlurAnnotation::new(start_pt, end_pt)),
-                };
-
-                imp.annotations.borrow_mut().push(annotation.clone());
-                imp.history.borrow_mut().push_add(annotation);
+            if imp.current_tool.get() == Tool::Crop {
+                // A click without dragging removes the crop
+                self.set_crop((dist > 5.0).then(|| rect_between(start_pt, end_pt)));
+            } else if imp.current_tool.get() == Tool::Text {
+                if dist > 5.0 {
+                    imp.text_region.set(Some(rect_between(start_pt, end_pt)));
+                    let _ = self.activate_action("app.copy-text", None);
+                }
+            } else if imp.current_tool.get() == Tool::Stamp && dist <= 5.0 {
+                // A click places the stamp at its default size
+                let stamp = StampAnnotation::centered(
+                    end_pt,
+                    StampAnnotation::DEFAULT_SIZE * self.content_scale(),
+                    imp.stamp.borrow().clone(),
+                    imp.primary_color.borrow().clone(),
+                );
+                self.add_annotation(Annotation::Stamp(stamp));
+            } else if dist > 5.0 {
+                match self.create_annotation(start_pt, end_pt) {
+                    // Callouts are only added once they have some text
+                    Some(Annotation::Callout(callout)) => self.add_callout(callout),
+                    Some(annotation) => self.add_annotation(annotation),
+                    None => {}
+                }
             }
         }
 
         imp.drag_start.set(None);
         imp.drag_current.set(None);
+        imp.drag_points.set(None);
         imp.is_drawing.set(false);
+        imp.space_held.set(false);
+        imp.guides.borrow_mut().clear();
         self.queue_draw();
     }
 
+    /// The annotation the current tool and settings draw between two image
+    /// points, or `None` for tools that don't draw one
+    fn create_annotation(&self, start: Point, end: Point) -> Option<Annotation> {
+        let imp = self.imp();
+        let color = imp.primary_color.borrow().clone();
+        // Sizes are chosen in screen pixels, so they look the same on HiDPI captures
+        let content_scale = self.content_scale();
+        let stroke_width = imp.stroke_width.get() * content_scale;
+        let dash = imp.dash_style.get();
+        let corner_radius = imp.corner_radius.get() * content_scale;
+        let filled = imp.filled.get();
+
+        let annotation = match imp.current_tool.get() {
+            Tool::Arrow => Annotation::Arrow(ArrowAnnotation::new(
+                start,
+                end,
+                color,
+                stroke_width,
+                imp.arrow_style.get(),
+            )),
+            Tool::Rectangle => Annotation::Rectangle(RectAnnotation::new(
+                start,
+                end,
+                color,
+                stroke_width,
+                filled,
+                dash,
+                corner_radius,
+            )),
+            Tool::Line => Annotation::Line(LineAnnotation::new(start, end, color, stroke_width, dash)),
+            Tool::Ellipse => Annotation::Ellipse(EllipseAnnotation::new(start, end, color, stroke_width, filled, dash)),
+            Tool::Highlight => Annotation::Highlight(HighlightAnnotation::new(start, end, color, corner_radius)),
+            Tool::Blur => Annotation::Blur(BlurAnnotation::new(start, end)),
+            Tool::Spotlight => Annotation::Spotlight(SpotlightAnnotation::new(
+                start,
+                end,
+                imp.spotlight_shape.get(),
+                imp.spotlight_effect.get(),
+                imp.spotlight_opacity.get(),
+            )),
+            Tool::Magnify => {
+                let zoom = imp.magnify_zoom.get();
+                let center = self.callout_position(start, end, zoom);
+                Annotation::Magnify(MagnifyAnnotation::new(
+                    start,
+                    end,
+                    center,
+                    zoom,
+                    imp.magnify_shape.get(),
+                    color,
+                    stroke_width,
+                ))
+            }
+            Tool::Callout => Annotation::Callout(CalloutAnnotation::new(
+                start,
+                end,
+                "Text".to_string(),
+                imp.callout_style.get(),
+                color,
+                imp.font_size.get() * content_scale,
+            )),
+            Tool::Stamp => Annotation::Stamp(StampAnnotation::new(start, end, imp.stamp.borrow().clone(), color)),
+            // Nothing is drawn with the select, crop and text tools
+            Tool::Select | Tool::Crop | Tool::Text => return None,
+        };
+        Some(annotation)
+    }
+
+    fn add_annotation(&self, annotation: Annotation) {
+        let imp = self.imp();
+        let index = imp.document.borrow().layers.len();
+        self.execute(AddCommand { index, layer: Layer::new(annotation) });
+    }
+
+    /// Apply `command` to the document as its own undo step
+    fn execute(&self, command: impl Command) {
+        let imp = self.imp();
+        imp.history.borrow_mut().execute(command, &mut imp.document.borrow_mut());
+        self.history_changed();
+    }
+
+    fn history_changed(&self) {
+        self.queue_draw();
+        self.emit_by_name::<()>("history-changed", &[]);
+    }
+
+    fn set_selected(&self, index: Option<usize>) {
+        let imp = self.imp();
+        if imp.selected.replace(index) != index {
+            self.queue_draw();
+            self.emit_by_name::<()>("selection-changed", &[]);
+        }
+    }
+
+    pub fn connect_selection_changed<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "selection-changed",
+            false,
+            glib::closure_local!(move |canvas: &CanvasWidget| f(canvas)),
+        )
+    }
+
+    pub fn connect_style_changed<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "style-changed",
+            false,
+            glib::closure_local!(move |canvas: &CanvasWidget| f(canvas)),
+        )
+    }
+
+    pub fn connect_history_changed<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "history-changed",
+            false,
+            glib::closure_local!(move |canvas: &CanvasWidget| f(canvas)),
+        )
+    }
+
+    /// Set the area kept on export, or clear it with `None`
+    pub fn set_crop(&self, crop: Option<(f64, f64, f64, f64)>) {
+        let before = self.imp().document.borrow().crop;
+        if before != crop {
+            self.execute(CropCommand { before, after: crop });
+        }
+    }
+
+    fn add_callout(&self, callout: CalloutAnnotation) {
+        let canvas = self.downgrade();
+        self.prompt_text("", move |text| {
+            let Some(canvas) = canvas.upgrade() else { return };
+            if text.is_empty() {
+                return;
+            }
+            let mut callout = callout.clone();
+            callout.text = text;
+            canvas.add_annotation(Annotation::Callout(callout));
+        });
+    }
+
+    fn edit_callout_text(&self, index: usize) {
+        let Some(Annotation::Callout(callout)) = self.imp().document.borrow().annotation(index).cloned() else {
+            return;
+        };
+
+        let canvas = self.downgrade();
+        self.prompt_text(&callout.text, move |text| {
+            let Some(canvas) = canvas.upgrade() else { return };
+            if text.is_empty() {
+                return;
+            }
+            canvas.set_selected(Some(index));
+            canvas.modify_selected(|annotation| {
+                if let Annotation::Callout(c) = annotation {
+                    c.text = text;
+                }
+            });
+        });
+    }
+
+    /// Ask for callout text in a dialog; `on_done` gets the trimmed text unless cancelled
+    fn prompt_text(&self, initial: &str, on_done: impl Fn(String) + 'static) {
+        let window = self.root().and_downcast::<gtk::Window>();
+        let dialog = adw::MessageDialog::new(window.as_ref(), Some("Callout Text"), None);
+
+        let text_view = gtk::TextView::builder()
+            .wrap_mode(gtk::WrapMode::WordChar)
+            .accepts_tab(false)
+            .build();
+        text_view.buffer().set_text(initial);
+
+        let scrolled = gtk::ScrolledWindow::builder()
+            .min_content_width(280)
+            .min_content_height(80)
+            .child(&text_view)
+            .build();
+
+        dialog.set_extra_child(Some(&scrolled));
+        dialog.add_responses(&[("cancel", "_Cancel"), ("ok", "_OK")]);
+        dialog.set_response_appearance("ok", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("ok"));
+        dialog.set_close_response("cancel");
+
+        dialog.connect_response(
+            None,
+            glib::clone!(
+                #[weak]
+                text_view,
+                move |_, response| {
+                    if response == "ok" {
+                        let buffer = text_view.buffer();
+                        let text = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+                        on_done(text.trim().to_string());
+                    }
+                }
+            ),
+        );
+
+        dialog.present();
+        text_view.grab_focus();
+    }
+
+    /// Place a new magnifier callout beside its source region, inside the image
+    fn callout_position(&self, start: Point, end: Point, zoom: f64) -> Point {
+        let image_width = self.imp().screenshot.borrow().as_ref().map(|s| s.width() as f64).unwrap_or(f64::MAX);
+
+        let half_width = (end.x - start.x).abs() * zoom / 2.0;
+        let gap = 24.0;
+        let right = start.x.max(end.x) + gap + half_width;
+        let left = start.x.min(end.x) - gap - half_width;
+        let x = if right + half_width <= image_width || left - half_width < 0.0 { right } else { left };
+
+        Point::new(x, (start.y + end.y) / 2.0)
+    }
+
+    /// Convert widget coordinates into image coordinates
+    fn to_image(&self, x: f64, y: f64) -> Point {
+        let imp = self.imp();
+        let scale = imp.scale.get();
+        Point::new((x - imp.offset_x.get()) / scale, (y - imp.offset_y.get()) / scale)
+    }
+
+    fn begin_edit(&self, x: f64, y: f64) {
+        let imp = self.imp();
+        let point = self.to_image(x, y);
+        let tolerance = 6.0 / imp.scale.get();
+
+        // Topmost annotation wins; hidden and locked ones can't be picked
+        let hit = imp
+            .document
+            .borrow()
+            .layers
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, layer)| layer.visible && !layer.locked)
+            .find_map(|(index, layer)| layer.annotation.hit_test(point, tolerance).map(|hit| (index, hit)));
+
+        match hit {
+            Some((index, hit)) => {
+                self.set_selected(Some(index));
+                imp.edit_hit.set(Some(hit));
+                imp.edit_original.replace(imp.document.borrow().annotation(index).cloned());
+                imp.edit_offset.set((0.0, 0.0));
+                imp.drag_start.set(Some((x, y)));
+            }
+            None => self.set_selected(None),
+        }
+        self.queue_draw();
+    }
+
+    fn update_edit(&self, x: f64, y: f64) {
+        let imp = self.imp();
+        let (Some(index), Some(hit), Some(start)) = (imp.selected.get(), imp.edit_hit.get(), imp.drag_start.get()) else {
+            return;
+        };
+        let Some(original) = imp.edit_original.borrow().clone() else { return };
+
+        let scale = imp.scale.get();
+        let dx = (x - start.0) / scale;
+        let dy = (y - start.1) / scale;
+
+        let mut guides = Vec::new();
+        let targets = imp.snapping.get().then(|| self.snap_targets(Some(index)));
+        let threshold = self.snap_threshold();
+
+        // Every step of the drag goes through history and is merged into one undo step
+        let mut history = imp.history.borrow_mut();
+        let mut document = imp.document.borrow_mut();
+        match hit {
+            Hit::Body => {
+                let (mut total_dx, mut total_dy) = (dx, dy);
+                if let Some(targets) = &targets {
+                    let mut moved = original.clone();
+                    moved.translate(dx, dy);
+                    let (snap_dx, snap_dy) = targets.snap_rect(moved.bounds(), threshold, &mut guides);
+                    total_dx += snap_dx;
+                    total_dy += snap_dy;
+                }
+
+                let (applied_dx, applied_dy) = imp.edit_offset.get();
+                if (total_dx, total_dy) != (applied_dx, applied_dy) {
+                    let command = MoveCommand {
+                        index,
+                        dx: total_dx - applied_dx,
+                        dy: total_dy - applied_dy,
+                        name: original.name(),
+                    };
+                    history.execute_continuous(command, &mut document);
+                    imp.edit_offset.set((total_dx, total_dy));
+                }
+            }
+            Hit::Handle(handle) => {
+                let mut edited = original.clone();
+                if let Some(point) = edited.handles().get(handle).copied() {
+                    let mut point = point.offset(dx, dy);
+                    if let Some(targets) = &targets {
+                        point = targets.snap_point(point, threshold, &mut guides);
+                    }
+                    edited.move_handle(handle, point);
+                }
+
+                if let Some(before) = document.annotation(index).cloned().filter(|current| *current != edited) {
+                    history.execute_continuous(ModifyCommand { index, before, after: edited }, &mut document);
+                }
+            }
+        }
+        drop(document);
+        drop(history);
+
+        // The history panels are only told once the drag ends
+        imp.guides.replace(guides);
+        self.queue_draw();
+    }
+
+    fn end_edit(&self, x: f64, y: f64) {
+        let imp = self.imp();
+        self.update_edit(x, y);
+        imp.history.borrow_mut().seal();
+
+        let original = imp.edit_original.take();
+        let changed = original.is_some()
+            && imp.selected.get().and_then(|index| imp.document.borrow().annotation(index).cloned()) != original;
+        imp.edit_hit.set(None);
+        imp.drag_start.set(None);
+        imp.guides.borrow_mut().clear();
+        if changed {
+            self.history_changed();
+        } else {
+            self.queue_draw();
+        }
+    }
+
+    /// Snap within a few screen pixels, whatever the zoom
+    fn snap_threshold(&self) -> f64 {
+        8.0 / self.imp().scale.get()
+    }
+
+    /// Everything a point can snap to: the image edges and center, the other
+    /// annotations, edges detected in the screenshot and recognized words
+    fn snap_targets(&self, exclude: Option<usize>) -> SnapTargets {
+        let imp = self.imp();
+        let mut targets = SnapTargets::new();
+
+        if let Some(screenshot) = imp.screenshot.borrow().as_ref() {
+            targets.add_rect((0.0, 0.0, screenshot.width() as f64, screenshot.height() as f64));
+        }
+
+        for (index, layer) in imp.document.borrow().layers.iter().enumerate() {
+            if layer.visible && Some(index) != exclude {
+                targets.add_rect(layer.annotation.bounds());
+            }
+        }
+
+        targets.extend(&imp.ui_edges.borrow());
+        for word in imp.recognized_words.borrow().iter().filter(|word| word.confidence >= MIN_SNAP_CONFIDENCE) {
+            targets.add_rect(word.bounds());
+        }
+        targets
+    }
+
+    fn draw_guides(&self, cr: &cairo::Context, width: f64, height: f64, scale: f64) {
+        let guides = self.imp().guides.borrow();
+        if guides.is_empty() {
+            return;
+        }
+
+        cr.save().unwrap();
+        cr.set_source_rgba(1.0, 0.0, 0.8, 0.9);
+        cr.set_line_width(1.0 / scale);
+        for guide in guides.iter() {
+            match *guide {
+                Guide::Vertical(x) => {
+                    cr.move_to(x, 0.0);
+                    cr.line_to(x, height);
+                }
+                Guide::Horizontal(y) => {
+                    cr.move_to(0.0, y);
+                    cr.line_to(width, y);
+                }
+            }
+        }
+        let _ = cr.stroke();
+        cr.restore().unwrap();
+    }
+
+    /// Draw a grid over the image: one line per pixel when zoomed in far
+    /// enough to see them, otherwise every 10 pixels
+    fn draw_grid(&self, cr: &cairo::Context, width: f64, height: f64, scale: f64) {
+        let step = if scale >= 4.0 { 1.0 } else { 10.0 * (1.0 / scale).ceil() };
+
+        cr.save().unwrap();
+        cr.set_source_rgba(0.5, 0.5, 0.5, 0.35);
+        cr.set_line_width(1.0 / scale);
+
+        let mut x = step;
+        while x < width {
+            cr.move_to(x, 0.0);
+            cr.line_to(x, height);
+            x += step;
+        }
+        let mut y = step;
+        while y < height {
+            cr.move_to(0.0, y);
+            cr.line_to(width, y);
+            y += step;
+        }
+        let _ = cr.stroke();
+        cr.restore().unwrap();
+    }
+
+    /// Dim everything outside the crop area and outline it
+    fn draw_crop(&self, cr: &cairo::Context, (x, y, w, h): (f64, f64, f64, f64), width: f64, height: f64, scale: f64) {
+        cr.save().unwrap();
+        cr.set_fill_rule(cairo::FillRule::EvenOdd);
+        cr.rectangle(0.0, 0.0, width, height);
+        cr.rectangle(x, y, w, h);
+        cr.set_source_rgba(0.0, 0.0, 0.0, 0.55);
+        let _ = cr.fill();
+
+        cr.set_line_width(1.0 / scale);
+        cr.set_dash(&[6.0 / scale, 4.0 / scale], 0.0);
+        cr.set_source_rgba(1.0, 1.0, 1.0, 0.9);
+        cr.rectangle(x, y, w, h);
+        let _ = cr.stroke();
+        cr.restore().unwrap();
+    }
+
+    /// Outline the area the text tool reads
+    fn draw_text_region(&self, cr: &cairo::Context, (x, y, w, h): (f64, f64, f64, f64), scale: f64) {
+        cr.save().unwrap();
+        cr.rectangle(x, y, w, h);
+        cr.set_source_rgba(0.2, 0.5, 1.0, 0.15);
+        let _ = cr.fill_preserve();
+        cr.set_line_width(1.0 / scale);
+        cr.set_dash(&[4.0 / scale, 4.0 / scale], 0.0);
+        cr.set_source_rgba(0.2, 0.5, 1.0, 0.9);
+        let _ = cr.stroke();
+        cr.restore().unwrap();
+    }
+
+    fn draw_selection(&self, cr: &cairo::Context, annotation: &Annotation, scale: f64) {
+        let (x, y, w, h) = annotation.bounds();
+        let pad = 4.0 / scale;
+
+        cr.save().unwrap();
+        cr.set_line_width(1.0 / scale);
+        cr.set_dash(&[4.0 / scale, 4.0 / scale], 0.0);
+        cr.set_source_rgba(0.2, 0.5, 1.0, 0.9);
+        cr.rectangle(x - pad, y - pad, w + 2.0 * pad, h + 2.0 * pad);
+        let _ = cr.stroke();
+
+        cr.set_dash(&[], 0.0);
+        let size = 8.0 / scale;
+        for handle in annotation.handles() {
+            cr.rectangle(handle.x - size / 2.0, handle.y - size / 2.0, size, size);
+            cr.set_source_rgb(1.0, 1.0, 1.0);
+            let _ = cr.fill_preserve();
+            cr.set_source_rgba(0.2, 0.5, 1.0, 1.0);
+            let _ = cr.stroke();
+        }
+        cr.restore().unwrap();
+    }
+
     fn on_scroll(&self, dy: f64) {
         let imp = self.imp();
         let scale = imp.scale.get();
         let new_scale = (scale * (1.0 - dy * 0.1)).clamp(0.1, 5.0);
         imp.scale.set(new_scale);
+        // The pointer now lies over another part of the image
+        if imp.is_drawing.get() {
+            self.update_drag_points();
+        }
         self.queue_draw();
     }
 
@@ -322,15 +1088,68 @@ impl CanvasWidget {
         imp.offset_x.set(offset_x.max(0.0));
         imp.offset_y.set(offset_y.max(0.0));
 
+        // Find UI edges in the screenshot once, for snapping; that reads every
+        // pixel, so it runs off the GTK thread and they snap once it's done
+        imp.ui_edges.replace(SnapTargets::new());
+        let pixbuf = screenshot.pixbuf.clone();
+        let pixels = pixbuf.read_pixel_bytes();
+        let (width, height) = (pixbuf.width() as usize, pixbuf.height() as usize);
+        let (rowstride, channels) = (pixbuf.rowstride() as usize, pixbuf.n_channels() as usize);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(snapping::detect_edges(&pixels, width, height, rowstride, channels));
+        });
+
+        let canvas = self.downgrade();
+        glib::timeout_add_local(std::time::Duration::from_millis(100), move || match receiver.try_recv() {
+            Ok(edges) => {
+                // Edges only belong to the screenshot they were found in
+                if let Some(canvas) = canvas.upgrade().filter(|canvas| canvas.screenshot_pixbuf().as_ref() == Some(&pixbuf)) {
+                    canvas.imp().ui_edges.replace(edges);
+                }
+                glib::ControlFlow::Break
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => glib::ControlFlow::Continue,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => glib::ControlFlow::Break,
+        });
+
+        imp.recognized_words.borrow_mut().clear();
+        imp.text_region.set(None);
         imp.screenshot.replace(Some(screenshot));
-        imp.annotations.borrow_mut().clear();
-        imp.history.borrow_mut().clear();
+        imp.document.replace(Document::new());
+        {
+            let mut history = imp.history.borrow_mut();
+            history.clear();
+            history.set_limit(settings::history_limit());
+        }
+        self.set_selected(None);
 
-        self.queue_draw();
+        self.history_changed();
     }
 
     pub fn set_tool(&self, tool: Tool) {
-        self.imp().current_tool.set(tool);
+        let imp = self.imp();
+        let previous = imp.current_tool.replace(tool);
+        if tool != Tool::Select {
+            self.set_selected(None);
+        }
+        if previous != tool {
+            // A drag started with the old tool can't be finished with the new one
+            if imp.is_drawing.get() {
+                imp.is_drawing.set(false);
+                imp.drag_start.set(None);
+                imp.drag_current.set(None);
+                imp.drag_points.set(None);
+                imp.guides.borrow_mut().clear();
+                self.queue_draw();
+            }
+            self.emit_by_name::<()>("style-changed", &[]);
+        }
+    }
+
+    /// Whether an annotation or crop is being dragged out
+    pub fn is_drawing(&self) -> bool {
+        self.imp().is_drawing.get()
     }
 
     pub fn current_tool(&self) -> Tool {
@@ -349,25 +1168,367 @@ impl CanvasWidget {
         self.imp().stroke_width.set(width);
     }
 
+    pub fn stroke_width(&self) -> f64 {
+        self.imp().stroke_width.get()
+    }
+
+    /// Whether new rectangles and ellipses are filled
+    pub fn set_filled(&self, filled: bool) {
+        self.imp().filled.set(filled);
+    }
+
+    pub fn filled(&self) -> bool {
+        self.imp().filled.get()
+    }
+
+    pub fn arrow_style(&self) -> ArrowStyle {
+        self.imp().arrow_style.get()
+    }
+
+    pub fn dash_style(&self) -> DashStyle {
+        self.imp().dash_style.get()
+    }
+
+    pub fn corner_radius(&self) -> f64 {
+        self.imp().corner_radius.get()
+    }
+
+    pub fn font_size(&self) -> f64 {
+        self.imp().font_size.get()
+    }
+
+    /// What the stamp tool places next; also switches to the stamp tool
+    pub fn set_stamp(&self, stamp: StampContent) {
+        self.imp().stamp.replace(stamp);
+        self.set_tool(Tool::Stamp);
+    }
+
+    pub fn stamp(&self) -> StampContent {
+        self.imp().stamp.borrow().clone()
+    }
+
+    /// Switch to a preset's tool and use its style for new annotations; the
+    /// selected annotation keeps its own style
+    pub fn apply_preset(&self, preset: &StylePreset) {
+        let imp = self.imp();
+        self.set_tool(preset.tool);
+        imp.primary_color.replace(preset.color);
+        imp.stroke_width.set(preset.stroke_width);
+        imp.filled.set(preset.filled);
+        imp.font_size.set(preset.font_size);
+        imp.arrow_style.set(preset.arrow_style);
+        imp.dash_style.set(preset.dash_style);
+        imp.corner_radius.set(preset.corner_radius);
+        self.emit_by_name::<()>("style-changed", &[]);
+    }
+
+    /// The current tool and style, to save as a preset
+    pub fn current_style(&self, name: &str) -> StylePreset {
+        let imp = self.imp();
+        StylePreset {
+            name: name.to_string(),
+            tool: imp.current_tool.get(),
+            color: *imp.primary_color.borrow(),
+            stroke_width: imp.stroke_width.get(),
+            filled: imp.filled.get(),
+            font_size: imp.font_size.get(),
+            arrow_style: imp.arrow_style.get(),
+            dash_style: imp.dash_style.get(),
+            corner_radius: imp.corner_radius.get(),
+        }
+    }
+
+    /// Set the style for new arrows, and for the selected one if any
+    pub fn set_arrow_style(&self, style: ArrowStyle) {
+        self.imp().arrow_style.set(style);
+        self.modify_selected(|annotation| {
+            if let Annotation::Arrow(a) = annotation {
+                if style == ArrowStyle::Curved && a.style != ArrowStyle::Curved {
+                    a.control = ArrowAnnotation::default_control(a.start, a.end);
+                }
+                a.style = style;
+            }
+        });
+    }
+
+    /// Set the dash pattern for new lines and shapes, and for the selected one if any
+    pub fn set_dash_style(&self, dash: DashStyle) {
+        self.imp().dash_style.set(dash);
+        self.modify_selected(|annotation| match annotation {
+            Annotation::Line(l) => l.dash = dash,
+            Annotation::Rectangle(r) => r.dash = dash,
+            Annotation::Ellipse(e) => e.dash = dash,
+            _ => {}
+        });
+    }
+
+    /// Set the corner radius for new rectangles and highlights, and for the selected one if any
+    pub fn set_corner_radius(&self, radius: f64) {
+        self.imp().corner_radius.set(radius);
+        let radius = radius * self.content_scale();
+        self.modify_selected(|annotation| match annotation {
+            Annotation::Rectangle(r) => r.corner_radius = radius,
+            Annotation::Highlight(h) => h.corner_radius = radius,
+            _ => {}
+        });
+    }
+
+    pub fn set_snapping(&self, enabled: bool) {
+        self.imp().snapping.set(enabled);
+    }
+
+    pub fn set_show_grid(&self, show: bool) {
+        self.imp().show_grid.set(show);
+        self.queue_draw();
+    }
+
+    /// Set the shape for new spotlights, and for the selected one if any
+    pub fn set_spotlight_shape(&self, shape: SpotlightShape) {
+        self.imp().spotlight_shape.set(shape);
+        self.modify_selected(|annotation| {
+            if let Annotation::Spotlight(s) = annotation {
+                s.shape = shape;
+            }
+        });
+    }
+
+    /// Set the effect for new spotlights, and for the selected one if any
+    pub fn set_spotlight_effect(&self, effect: SpotlightEffect) {
+        self.imp().spotlight_effect.set(effect);
+        self.modify_selected(|annotation| {
+            if let Annotation::Spotlight(s) = annotation {
+                s.effect = effect;
+            }
+        });
+    }
+
+    /// Set the opacity for new spotlights, and for the selected one if any
+    pub fn set_spotlight_opacity(&self, opacity: f64) {
+        self.imp().spotlight_opacity.set(opacity);
+        self.modify_selected(|annotation| {
+            if let Annotation::Spotlight(s) = annotation {
+                s.opacity = opacity;
+            }
+        });
+    }
+
+    /// Set the zoom for new magnifiers, and for the selected one if any
+    pub fn set_magnify_zoom(&self, zoom: f64) {
+        let imp = self.imp();
+        imp.magnify_zoom.set(zoom);
+        self.modify_selected(|annotation| {
+            if let Annotation::Magnify(m) = annotation {
+                m.zoom = zoom;
+            }
+        });
+    }
+
+    /// Set the shape for new magnifiers, and for the selected one if any
+    pub fn set_magnify_shape(&self, shape: MagnifyShape) {
+        let imp = self.imp();
+        imp.magnify_shape.set(shape);
+        self.modify_selected(|annotation| {
+            if let Annotation::Magnify(m) = annotation {
+                m.shape = shape;
+            }
+        });
+    }
+
+    /// Apply `edit` to the selected annotation, recording it in history if it changed anything
+    fn modify_selected(&self, edit: impl FnOnce(&mut Annotation)) {
+        let imp = self.imp();
+        let Some(index) = imp.selected.get() else { return };
+        let Some(before) = imp.document.borrow().layers.get(index).filter(|l| !l.locked).map(|l| l.annotation.clone()) else {
+            return;
+        };
+
+        let mut after = before.clone();
+        edit(&mut after);
+        if before != after {
+            self.execute(ModifyCommand { index, before, after });
+        }
+    }
+
+    /// Set the style for new callouts, and for the selected one if any
+    pub fn set_callout_style(&self, style: CalloutStyle) {
+        self.imp().callout_style.set(style);
+        self.modify_selected(|annotation| {
+            if let Annotation::Callout(c) = annotation {
+                c.style = style;
+            }
+        });
+    }
+
+    /// Set the font size for new callouts, and for the selected one if any
+    pub fn set_font_size(&self, size: f64) {
+        self.imp().font_size.set(size);
+        let size = size * self.content_scale();
+        self.modify_selected(|annotation| {
+            if let Annotation::Callout(c) = annotation {
+                c.font_size = size;
+            }
+        });
+    }
+
+    /// Move the selected annotation to another position in the stacking order
+    fn move_selected_to(&self, to: usize) {
+        let imp = self.imp();
+        let Some(from) = imp.selected.get() else { return };
+        let len = imp.document.borrow().layers.len();
+        let to = to.min(len.saturating_sub(1));
+        if from >= len || from == to {
+            return;
+        }
+
+        self.execute(ReorderCommand { from, to, layers: len });
+        self.set_selected(Some(to));
+    }
+
+    pub fn bring_forward(&self) {
+        if let Some(index) = self.imp().selected.get() {
+            self.move_selected_to(index + 1);
+        }
+    }
+
+    pub fn send_backward(&self) {
+        if let Some(index) = self.imp().selected.get() {
+            self.move_selected_to(index.saturating_sub(1));
+        }
+    }
+
+    pub fn bring_to_front(&self) {
+        self.move_selected_to(usize::MAX);
+    }
+
+    pub fn send_to_back(&self) {
+        self.move_selected_to(0);
+    }
+
+    /// Copy the selected annotation, slightly offset, and select the copy
+    pub fn duplicate_selected(&self) {
+        let imp = self.imp();
+        let Some(mut annotation) = imp.selected.get().and_then(|i| imp.document.borrow().annotation(i).cloned()) else {
+            return;
+        };
+
+        annotation.translate(10.0, 10.0);
+        self.add_annotation(annotation);
+        self.set_selected(Some(imp.document.borrow().layers.len() - 1));
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.imp().selected.get().is_some()
+    }
+
+    /// Put the selected annotation on the clipboard so it can be pasted into another screenshot
+    pub fn copy_selected(&self) {
+        let imp = self.imp();
+        let Some(annotation) = imp.selected.get().and_then(|i| imp.document.borrow().annotation(i).cloned()) else {
+            return;
+        };
+
+        match serde_json::to_vec(&[annotation]) {
+            Ok(json) => {
+                let provider = gdk::ContentProvider::for_bytes(ANNOTATIONS_MIME_TYPE, &glib::Bytes::from_owned(json));
+                if let Err(e) = self.clipboard().set_content(Some(&provider)) {
+                    eprintln!("Failed to copy annotation: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Failed to serialize annotation: {}", e),
+        }
+    }
+
+    /// Paste annotations copied from this or another GNOME Shot window
+    pub fn paste(&self) {
+        let clipboard = self.clipboard();
+        if !clipboard.formats().contain_mime_type(ANNOTATIONS_MIME_TYPE) {
+            return;
+        }
+
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = canvas)]
+            self,
+            async move {
+                let result = async {
+                    let (stream, _) = clipboard.read_future(&[ANNOTATIONS_MIME_TYPE], glib::Priority::DEFAULT).await?;
+                    let output = gio::MemoryOutputStream::new_resizable();
+                    output
+                        .splice_future(
+                            &stream,
+                            gio::OutputStreamSpliceFlags::CLOSE_SOURCE | gio::OutputStreamSpliceFlags::CLOSE_TARGET,
+                            glib::Priority::DEFAULT,
+                        )
+                        .await?;
+                    let bytes = output.steal_as_bytes();
+                    Ok::<_, anyhow::Error>(serde_json::from_slice::<Vec<Annotation>>(&bytes)?)
+                };
+
+                match result.await {
+                    Ok(annotations) => canvas.add_pasted(annotations),
+                    Err(e) => eprintln!("Failed to paste annotations: {}", e),
+                }
+            }
+        ));
+    }
+
+    fn add_pasted(&self, annotations: Vec<Annotation>) {
+        let imp = self.imp();
+        let count = annotations.len();
+        let first = imp.document.borrow().layers.len();
+
+        let mut commands: Vec<Box<dyn Command>> = Vec::with_capacity(count);
+        for (i, mut annotation) in annotations.into_iter().enumerate() {
+            // Don't hide a pasted copy exactly on top of its original
+            if imp.document.borrow().layers.iter().any(|layer| layer.annotation == annotation) {
+                annotation.translate(10.0, 10.0);
+            }
+            commands.push(Box::new(AddCommand {
+                index: first + i,
+                layer: Layer::new(annotation),
+            }));
+        }
+
+        match count {
+            0 => return,
+            1 => self.execute(BatchCommand::new("Annotation pasted", commands)),
+            _ => self.execute(BatchCommand::new(format!("{} annotations pasted", count), commands)),
+        }
+
+        self.set_selected(Some(first + count - 1));
+    }
+
+    pub fn delete_selected(&self) {
+        let imp = self.imp();
+        let Some(index) = imp.selected.get() else { return };
+        let Some(layer) = imp.document.borrow().layers.get(index).filter(|l| !l.locked).cloned() else {
+            return;
+        };
+
+        self.set_selected(None);
+        self.execute(RemoveCommand { index, layer });
+    }
+
     pub fn undo(&self) {
         let imp = self.imp();
-        let mut annotations = imp.annotations.borrow_mut();
+        let mut document = imp.document.borrow_mut();
         let mut history = imp.history.borrow_mut();
-        if history.undo(&mut annotations) {
-            drop(annotations);
+        if history.undo(&mut document) {
+            drop(document);
             drop(history);
-            self.queue_draw();
+            self.set_selected(None);
+            self.history_changed();
         }
     }
 
     pub fn redo(&self) {
         let imp = self.imp();
-        let mut annotations = imp.annotations.borrow_mut();
+        let mut document = imp.document.borrow_mut();
         let mut history = imp.history.borrow_mut();
-        if history.redo(&mut annotations) {
-            drop(annotations);
+        if history.redo(&mut document) {
+            drop(document);
             drop(history);
-            self.queue_draw();
+            self.set_selected(None);
+            self.history_changed();
         }
     }
 
@@ -379,67 +1540,139 @@ impl CanvasWidget {
         self.imp().history.borrow().can_redo()
     }
 
-    pub fn export_to_texture(&self) -> Option<gdk::Texture> {
-        let imp = self.imp();
-        let screenshot = imp.screenshot.borrow();
-        let screenshot = screenshot.as_ref()?;
+    /// Snapshot of all layers, bottom first
+    pub fn layers(&self) -> Vec<Layer> {
+        self.imp().document.borrow().layers.clone()
+    }
 
-        let width = screenshot.width();
-        let height = screenshot.height();
+    pub fn layer_count(&self) -> usize {
+        self.imp().document.borrow().layers.len()
+    }
 
-        // Create a surface to draw on
-        let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height).ok()?;
-        let cr = cairo::Context::new(&surface).ok()?;
+    pub fn selected(&self) -> Option<usize> {
+        self.imp().selected.get()
+    }
 
-        // Draw the original image
-        gtk::gdk::prelude::GdkCairoContextExt::set_source_pixbuf(&cr, &screenshot.pixbuf, 0.0, 0.0);
-        cr.paint().ok()?;
+    /// Select a layer, e.g. from the layers panel; hidden layers can't be selected
+    pub fn select(&self, index: Option<usize>) {
+        let valid = index.filter(|&i| self.imp().document.borrow().layers.get(i).is_some_and(|layer| layer.visible));
+        self.set_selected(valid);
+    }
 
-        // Draw annotations at original scale
-        for annotation in imp.annotations.borrow().iter() {
-            annotation.draw(&cr, 1.0);
+    pub fn set_layer_visible(&self, index: usize, visible: bool) {
+        if !visible && self.imp().selected.get() == Some(index) {
+            self.set_selected(None);
         }
+        self.update_layer(index, |layer| layer.visible = visible);
+    }
 
-        drop(cr);
-        surface.flush();
+    pub fn set_layer_locked(&self, index: usize, locked: bool) {
+        self.update_layer(index, |layer| layer.locked = locked);
+    }
 
-        // Convert to texture
-        let data = surface.data().ok()?;
-        let bytes = glib::Bytes::from(&*data);
+    /// Give a layer a name; an empty name goes back to the annotation kind
+    pub fn rename_layer(&self, index: usize, name: &str) {
+        let name = name.trim();
+        self.update_layer(index, |layer| {
+            layer.name = (!name.is_empty() && name != layer.annotation.name()).then(|| name.to_string());
+        });
+    }
 
-        Some(gdk::MemoryTexture::new(
-            width,
-            height,
-            gdk::MemoryFormat::B8g8r8a8Premultiplied,
-            &bytes,
-            (width * 4) as usize,
-        ).upcast())
+    fn update_layer(&self, index: usize, edit: impl FnOnce(&mut Layer)) {
+        let Some(before) = self.imp().document.borrow().layers.get(index).cloned() else { return };
+
+        let mut after = before.clone();
+        edit(&mut after);
+        if before != after {
+            self.execute(LayerCommand { index, before, after });
+        }
     }
 
-    pub fn save_to_file(&self, path: &std::path::Path) -> anyhow::Result<()> {
+    pub fn history_entries(&self) -> Vec<HistoryEntry> {
+        self.imp().history.borrow().entries()
+    }
+
+    /// Whether the oldest history steps were dropped, so the original
+    /// screenshot can no longer be restored
+    pub fn history_has_dropped_steps(&self) -> bool {
+        self.imp().history.borrow().has_dropped_steps()
+    }
+
+    /// Number of history steps currently applied
+    pub fn history_position(&self) -> usize {
+        self.imp().history.borrow().position()
+    }
+
+    /// Undo or redo until `position` history steps are applied
+    pub fn jump_to_history(&self, position: usize) {
+        let imp = self.imp();
+        let changed = imp.history.borrow_mut().jump_to(position, &mut imp.document.borrow_mut());
+        if changed {
+            self.set_selected(None);
+            self.history_changed();
+        }
+    }
+
+    /// The annotated image as it is exported, cropped and framed
+    fn render_output(&self) -> anyhow::Result<cairo::ImageSurface> {
         let imp = self.imp();
         let screenshot = imp.screenshot.borrow();
         let screenshot = screenshot.as_ref().ok_or_else(|| anyhow::anyhow!("No screenshot loaded"))?;
 
-        let width = screenshot.width();
-        let height = screenshot.height();
+        let document = imp.document.borrow();
+        let (x, y, width, height) = document.output_rect(screenshot.width(), screenshot.height());
 
         // Create a surface to draw on
         let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)
             .map_err(|e| anyhow::anyhow!("Failed to create surface: {}", e))?;
         let cr = cairo::Context::new(&surface)
             .map_err(|e| anyhow::anyhow!("Failed to create context: {}", e))?;
+        cr.translate(-x as f64, -y as f64);
 
         // Draw the original image
         gtk::gdk::prelude::GdkCairoContextExt::set_source_pixbuf(&cr, &screenshot.pixbuf, 0.0, 0.0);
         cr.paint().map_err(|e| anyhow::anyhow!("Failed to paint: {}", e))?;
 
         // Draw annotations at original scale
-        for annotation in imp.annotations.borrow().iter() {
-            annotation.draw(&cr, 1.0);
-        }
+        render::draw_annotations(&cr, &screenshot.pixbuf, document.visible_annotations());
 
         drop(cr);
+        surface.flush();
+
+        // Back to 1x before framing, so the frame keeps its size
+        let content_scale = screenshot.scale_factor;
+        let surface = if imp.export_native.get() || content_scale <= 1.0 {
+            surface
+        } else {
+            downscale(&surface, content_scale).map_err(|e| anyhow::anyhow!("Failed to scale image: {}", e))?
+        };
+
+        let frame = imp.frame.borrow();
+        if !frame.enabled {
+            return Ok(surface);
+        }
+        frame.apply(&surface).map_err(|e| anyhow::anyhow!("Failed to draw frame: {}", e))
+    }
+
+    pub fn export_to_texture(&self) -> Option<gdk::Texture> {
+        let mut surface = self.render_output().ok()?;
+        let (width, height, stride) = (surface.width(), surface.height(), surface.stride());
+
+        // Convert to texture
+        let data = surface.data().ok()?;
+        let bytes = glib::Bytes::from(&*data);
+
+        Some(gdk::MemoryTexture::new(
+            width,
+            height,
+            gdk::MemoryFormat::B8g8r8a8Premultiplied,
+            &bytes,
+            stride as usize,
+        ).upcast())
+    }
+
+    pub fn save_to_file(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let surface = self.render_output()?;
 
         // Write to PNG file
         let mut file = std::fs::File::create(path)?;
@@ -448,4 +1681,89 @@ impl CanvasWidget {
 
         Ok(())
     }
+
+    /// The screenshot as captured, without annotations
+    pub fn screenshot_pixbuf(&self) -> Option<gtk::gdk_pixbuf::Pixbuf> {
+        self.imp().screenshot.borrow().as_ref().map(|screenshot| screenshot.pixbuf.clone())
+    }
+
+    /// Area to work on: the area dragged with the text tool while it's in
+    /// use, the selected annotation's bounds, otherwise the crop area
+    pub fn selected_region(&self) -> Option<(f64, f64, f64, f64)> {
+        let imp = self.imp();
+        if let Some(region) = imp.text_region.get().filter(|_| imp.current_tool.get() == Tool::Text) {
+            return Some(region);
+        }
+        let document = imp.document.borrow();
+        imp.selected
+            .get()
+            .and_then(|index| document.layers.get(index))
+            .map(|layer| layer.annotation.bounds())
+            .or(document.crop)
+    }
+
+    /// Keep recognized words, e.g. for snapping; words from an earlier
+    /// recognition that overlap the new ones are replaced
+    pub fn add_recognized_words(&self, words: Vec<Word>) {
+        let mut recognized = self.imp().recognized_words.borrow_mut();
+        recognized.retain(|old| !words.iter().any(|new| overlaps(old.bounds(), new.bounds())));
+        recognized.extend(words);
+    }
+
+    /// File the loaded screenshot was read from, if it's still there
+    pub fn source_path(&self) -> Option<std::path::PathBuf> {
+        self.imp().screenshot.borrow().as_ref().and_then(|screenshot| screenshot.source_path.clone())
+    }
+
+    pub fn set_source_path(&self, path: Option<std::path::PathBuf>) {
+        if let Some(screenshot) = self.imp().screenshot.borrow_mut().as_mut() {
+            screenshot.source_path = path;
+        }
+    }
+
+    /// Physical pixels per screen pixel of the loaded screenshot
+    pub fn content_scale(&self) -> f64 {
+        self.imp().screenshot.borrow().as_ref().map_or(1.0, |screenshot| screenshot.scale_factor)
+    }
+
+    /// Whether HiDPI screenshots are exported at full resolution, or at 1x
+    pub fn set_export_native(&self, native: bool) {
+        self.imp().export_native.set(native);
+    }
+
+    /// Frame drawn around exported images
+    pub fn set_frame(&self, frame: FrameSettings) {
+        self.imp().frame.replace(frame);
+    }
+
+    pub fn frame(&self) -> FrameSettings {
+        self.imp().frame.borrow().clone()
+    }
+}
+
+/// Normalized (x, y, width, height) of the rectangle spanned by two points
+fn rect_between(a: Point, b: Point) -> (f64, f64, f64, f64) {
+    (a.x.min(b.x), a.y.min(b.y), (a.x - b.x).abs(), (a.y - b.y).abs())
+}
+
+/// Whether two (x, y, width, height) rectangles share any area
+fn overlaps(a: (f64, f64, f64, f64), b: (f64, f64, f64, f64)) -> bool {
+    a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3
+}
+
+/// Shrink `surface` by `factor`, smoothing as it goes
+fn downscale(surface: &cairo::ImageSurface, factor: f64) -> Result<cairo::ImageSurface, cairo::Error> {
+    let width = ((surface.width() as f64 / factor).round() as i32).max(1);
+    let height = ((surface.height() as f64 / factor).round() as i32).max(1);
+    let scaled = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+
+    let cr = cairo::Context::new(&scaled)?;
+    cr.scale(width as f64 / surface.width() as f64, height as f64 / surface.height() as f64);
+    cr.set_source_surface(surface, 0.0, 0.0)?;
+    cr.source().set_filter(cairo::Filter::Good);
+    cr.paint()?;
+    drop(cr);
+
+    scaled.flush();
+    Ok(scaled)
 }
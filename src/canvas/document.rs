@@ -0,0 +1,72 @@
+use super::annotations::Annotation;
+
+/// An annotation together with how it appears in the layers panel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub annotation: Annotation,
+    /// Name given by the user, if any
+    pub name: Option<String>,
+    /// Hidden layers are neither shown nor exported
+    pub visible: bool,
+    /// Locked layers can't be selected on the canvas, moved or restyled
+    pub locked: bool,
+}
+
+impl Layer {
+    pub fn new(annotation: Annotation) -> Self {
+        Self {
+            annotation,
+            name: None,
+            visible: true,
+            locked: false,
+        }
+    }
+
+    /// The user's name for the layer, or the kind of annotation
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.annotation.name())
+    }
+}
+
+/// Everything the user has changed on top of the screenshot
+///
+/// This is the state that history commands operate on.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Document {
+    /// Layers in drawing order, bottom first
+    pub layers: Vec<Layer>,
+    /// Part of the screenshot to keep on export as (x, y, width, height), in image coordinates
+    pub crop: Option<(f64, f64, f64, f64)>,
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn annotation(&self, index: usize) -> Option<&Annotation> {
+        self.layers.get(index).map(|layer| &layer.annotation)
+    }
+
+    /// Annotations that are drawn and exported, bottom first
+    pub fn visible_annotations(&self) -> impl Iterator<Item = &Annotation> {
+        self.layers.iter().filter(|layer| layer.visible).map(|layer| &layer.annotation)
+    }
+
+    /// Pixel area of an image of the given size that ends up in the output
+    pub fn output_rect(&self, width: i32, height: i32) -> (i32, i32, i32, i32) {
+        let Some((x, y, w, h)) = self.crop else {
+            return (0, 0, width, height);
+        };
+
+        let x0 = (x.round() as i32).clamp(0, width);
+        let y0 = (y.round() as i32).clamp(0, height);
+        let x1 = ((x + w).round() as i32).clamp(x0, width);
+        let y1 = ((y + h).round() as i32).clamp(y0, height);
+
+        if x1 - x0 < 1 || y1 - y0 < 1 {
+            return (0, 0, width, height);
+        }
+        (x0, y0, x1 - x0, y1 - y0)
+    }
+}
@@ -0,0 +1,225 @@
+use gtk::gdk::RGBA;
+use serde::{Deserialize, Serialize};
+
+use super::annotations::{rgba_serde, rounded_rectangle};
+
+/// What fills the area around a framed screenshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameBackground {
+    Solid(#[serde(with = "rgba_serde")] RGBA),
+    /// Diagonal gradient from the top left to the bottom right corner
+    Gradient {
+        #[serde(with = "rgba_serde")]
+        from: RGBA,
+        #[serde(with = "rgba_serde")]
+        to: RGBA,
+    },
+}
+
+impl FrameBackground {
+    fn paint(&self, cr: &cairo::Context, width: f64, height: f64) {
+        match self {
+            FrameBackground::Solid(color) => {
+                cr.set_source_rgba(color.red() as f64, color.green() as f64, color.blue() as f64, color.alpha() as f64);
+            }
+            FrameBackground::Gradient { from, to } => {
+                let gradient = cairo::LinearGradient::new(0.0, 0.0, width, height);
+                gradient.add_color_stop_rgba(0.0, from.red() as f64, from.green() as f64, from.blue() as f64, from.alpha() as f64);
+                gradient.add_color_stop_rgba(1.0, to.red() as f64, to.green() as f64, to.blue() as f64, to.alpha() as f64);
+                let _ = cr.set_source(&gradient);
+            }
+        }
+        let _ = cr.paint();
+    }
+}
+
+/// Named backgrounds offered in the frame panel
+pub fn frame_backgrounds() -> Vec<(&'static str, FrameBackground)> {
+    let gradient = |from: (f32, f32, f32), to: (f32, f32, f32)| FrameBackground::Gradient {
+        from: RGBA::new(from.0, from.1, from.2, 1.0),
+        to: RGBA::new(to.0, to.1, to.2, 1.0),
+    };
+
+    vec![
+        ("Sunset", gradient((1.0, 0.6, 0.4), (0.9, 0.3, 0.6))),
+        ("Ocean", gradient((0.2, 0.5, 0.95), (0.3, 0.85, 0.8))),
+        ("Meadow", gradient((0.55, 0.85, 0.45), (0.15, 0.6, 0.5))),
+        ("Lavender", gradient((0.75, 0.65, 1.0), (0.45, 0.4, 0.85))),
+        ("Graphite", gradient((0.3, 0.32, 0.36), (0.1, 0.1, 0.12))),
+        ("White", FrameBackground::Solid(RGBA::new(1.0, 1.0, 1.0, 1.0))),
+        ("Transparent", FrameBackground::Solid(RGBA::new(0.0, 0.0, 0.0, 0.0))),
+    ]
+}
+
+/// Shape of the framed image
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FrameAspect {
+    /// Just the screenshot plus padding
+    #[default]
+    Auto,
+    Widescreen,
+    Standard,
+    Square,
+}
+
+impl FrameAspect {
+    /// Width divided by height, if fixed
+    pub fn ratio(&self) -> Option<f64> {
+        match self {
+            FrameAspect::Auto => None,
+            FrameAspect::Widescreen => Some(16.0 / 9.0),
+            FrameAspect::Standard => Some(4.0 / 3.0),
+            FrameAspect::Square => Some(1.0),
+        }
+    }
+}
+
+/// Presentation frame drawn around the exported screenshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameSettings {
+    pub enabled: bool,
+    pub background: FrameBackground,
+    /// Space between the screenshot and the edge of the image, in pixels
+    pub padding: f64,
+    pub corner_radius: f64,
+    pub shadow: bool,
+    /// Draw a title bar with window buttons above the screenshot
+    pub window_chrome: bool,
+    pub aspect: FrameAspect,
+}
+
+impl Default for FrameSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            background: frame_backgrounds().remove(0).1,
+            padding: 64.0,
+            corner_radius: 12.0,
+            shadow: true,
+            window_chrome: false,
+            aspect: FrameAspect::Auto,
+        }
+    }
+}
+
+impl FrameSettings {
+    /// Height of the title bar for a screenshot of the given size, so it keeps its proportions
+    fn chrome_height(&self, width: i32) -> f64 {
+        if self.window_chrome { (width as f64 * 0.025).clamp(24.0, 72.0).round() } else { 0.0 }
+    }
+
+    /// Size of the framed image and where the screenshot's window (chrome included) goes in it
+    fn layout(&self, width: i32, height: i32) -> (i32, i32, f64, f64) {
+        let window_w = width as f64;
+        let window_h = height as f64 + self.chrome_height(width);
+
+        let mut out_w = window_w + 2.0 * self.padding;
+        let mut out_h = window_h + 2.0 * self.padding;
+        // Grow one side to reach the ratio, never cropping the screenshot
+        if let Some(ratio) = self.aspect.ratio() {
+            if out_w / out_h < ratio {
+                out_w = out_h * ratio;
+            } else {
+                out_h = out_w / ratio;
+            }
+        }
+
+        let (out_w, out_h) = (out_w.round() as i32, out_h.round() as i32);
+        let x = ((out_w as f64 - window_w) / 2.0).round();
+        let y = ((out_h as f64 - window_h) / 2.0).round();
+        (out_w, out_h, x, y)
+    }
+
+    /// Draw `content` on the frame and return the framed image
+    pub fn apply(&self, content: &cairo::ImageSurface) -> Result<cairo::ImageSurface, cairo::Error> {
+        let (width, height) = (content.width(), content.height());
+        let (out_w, out_h, x, y) = self.layout(width, height);
+        let chrome = self.chrome_height(width);
+        let (window_w, window_h) = (width as f64, height as f64 + chrome);
+        let radius = self.corner_radius.min(window_w / 2.0).min(window_h / 2.0).max(0.0);
+
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, out_w, out_h)?;
+        let cr = cairo::Context::new(&surface)?;
+
+        self.background.paint(&cr, out_w as f64, out_h as f64);
+
+        if self.shadow {
+            // Cairo can't blur, so stack faint, growing outlines offset downwards
+            let spread = (self.padding * 0.4).max(4.0);
+            let steps = 16;
+            for i in (1..=steps).rev() {
+                let grow = spread * i as f64 / steps as f64;
+                rounded_rectangle(
+                    &cr,
+                    x - grow,
+                    y - grow + spread * 0.4,
+                    window_w + 2.0 * grow,
+                    window_h + 2.0 * grow,
+                    radius + grow,
+                );
+                cr.set_source_rgba(0.0, 0.0, 0.0, 0.35 / steps as f64);
+                cr.fill()?;
+            }
+        }
+
+        cr.save()?;
+        rounded_rectangle(&cr, x, y, window_w, window_h, radius);
+        cr.clip();
+
+        if chrome > 0.0 {
+            cr.set_source_rgb(0.92, 0.92, 0.93);
+            cr.rectangle(x, y, window_w, chrome);
+            cr.fill()?;
+
+            let dot = chrome * 0.22;
+            let colors = [(1.0, 0.37, 0.34), (1.0, 0.74, 0.18), (0.16, 0.79, 0.25)];
+            for (i, (r, g, b)) in colors.into_iter().enumerate() {
+                cr.set_source_rgb(r, g, b);
+                cr.arc(x + chrome * 0.6 + i as f64 * dot * 3.2, y + chrome / 2.0, dot, 0.0, 2.0 * std::f64::consts::PI);
+                cr.fill()?;
+            }
+        }
+
+        cr.set_source_surface(content, x, y + chrome)?;
+        cr.paint()?;
+        cr.restore()?;
+
+        drop(cr);
+        surface.flush();
+        Ok(surface)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(padding: f64, aspect: FrameAspect, window_chrome: bool) -> FrameSettings {
+        FrameSettings { padding, aspect, window_chrome, ..FrameSettings::default() }
+    }
+
+    #[test]
+    fn pads_the_screenshot_evenly() {
+        assert_eq!(frame(64.0, FrameAspect::Auto, false).layout(800, 600), (928, 728, 64.0, 64.0));
+        assert_eq!(frame(0.0, FrameAspect::Auto, false).layout(800, 600), (800, 600, 0.0, 0.0));
+    }
+
+    #[test]
+    fn grows_one_side_to_reach_the_aspect_ratio() {
+        // Too tall for 16:9, so it gets wider
+        assert_eq!(frame(64.0, FrameAspect::Widescreen, false).layout(800, 600), (1294, 728, 247.0, 64.0));
+        // Too wide for 1:1, so it gets taller
+        assert_eq!(frame(0.0, FrameAspect::Square, false).layout(1000, 200), (1000, 1000, 0.0, 400.0));
+    }
+
+    #[test]
+    fn makes_room_for_the_title_bar() {
+        // The title bar is at least 24 pixels high
+        assert_eq!(frame(10.0, FrameAspect::Auto, true).layout(800, 600), (820, 644, 10.0, 10.0));
+        // and grows with the screenshot, up to 72
+        assert_eq!(frame(0.0, FrameAspect::Auto, true).layout(2000, 1000), (2000, 1050, 0.0, 0.0));
+        assert_eq!(frame(0.0, FrameAspect::Auto, true).layout(4000, 1000), (4000, 1072, 0.0, 0.0));
+    }
+}
@@ -1,30 +1,455 @@
-use super::Annotation;
+use std::any::Any;
+use std::fmt::Debug;
 
+use super::annotations::Annotation;
+use super::document::{Document, Layer};
+
+/// Number of undo steps kept unless configured otherwise
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// A reversible change to a `Document`
+///
+/// `revert` is only ever called on the state `apply` produced, so commands
+/// can store exactly what they need to put things back.
+pub trait Command: Debug + 'static {
+    fn apply(&self, document: &mut Document);
+
+    fn revert(&self, document: &mut Document);
+
+    /// Short description such as "Arrow added"
+    fn description(&self) -> String;
+
+    /// Symbolic icon shown next to the description
+    fn icon_name(&self) -> &'static str {
+        "document-edit-symbolic"
+    }
+
+    /// Absorb `next`, a follow-up of the same continuous gesture, so both
+    /// are undone in one step. Returns false if the commands don't combine.
+    fn merge(&mut self, _next: &dyn Command) -> bool {
+        false
+    }
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Insert a layer at a position in the stacking order
+#[derive(Debug, Clone)]
+pub struct AddCommand {
+    pub index: usize,
+    pub layer: Layer,
+}
+
+impl Command for AddCommand {
+    fn apply(&self, document: &mut Document) {
+        let index = self.index.min(document.layers.len());
+        document.layers.insert(index, self.layer.clone());
+    }
+
+    fn revert(&self, document: &mut Document) {
+        if self.index < document.layers.len() {
+            document.layers.remove(self.index);
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("{} added", self.layer.display_name())
+    }
+
+    fn icon_name(&self) -> &'static str {
+        "list-add-symbolic"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Delete the layer at `index`; `layer` is what was there
+#[derive(Debug, Clone)]
+pub struct RemoveCommand {
+    pub index: usize,
+    pub layer: Layer,
+}
+
+impl Command for RemoveCommand {
+    fn apply(&self, document: &mut Document) {
+        if self.index < document.layers.len() {
+            document.layers.remove(self.index);
+        }
+    }
+
+    fn revert(&self, document: &mut Document) {
+        let index = self.index.min(document.layers.len());
+        document.layers.insert(index, self.layer.clone());
+    }
+
+    fn description(&self) -> String {
+        format!("{} deleted", self.layer.display_name())
+    }
+
+    fn icon_name(&self) -> &'static str {
+        "user-trash-symbolic"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Replace the annotation at `index`, e.g. after changing its style or dragging a handle
+#[derive(Debug, Clone)]
+pub struct ModifyCommand {
+    pub index: usize,
+    pub before: Annotation,
+    pub after: Annotation,
+}
+
+impl Command for ModifyCommand {
+    fn apply(&self, document: &mut Document) {
+        if let Some(layer) = document.layers.get_mut(self.index) {
+            layer.annotation = self.after.clone();
+        }
+    }
+
+    fn revert(&self, document: &mut Document) {
+        if let Some(layer) = document.layers.get_mut(self.index) {
+            layer.annotation = self.before.clone();
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("{} changed", self.after.name())
+    }
+
+    fn merge(&mut self, next: &dyn Command) -> bool {
+        match next.as_any().downcast_ref::<ModifyCommand>() {
+            Some(next) if next.index == self.index => {
+                self.after = next.after.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Translate the annotation at `index` by (`dx`, `dy`)
+#[derive(Debug, Clone)]
+pub struct MoveCommand {
+    pub index: usize,
+    pub dx: f64,
+    pub dy: f64,
+    /// Kind of the annotation being moved, for the description
+    pub name: &'static str,
+}
+
+impl Command for MoveCommand {
+    fn apply(&self, document: &mut Document) {
+        if let Some(layer) = document.layers.get_mut(self.index) {
+            layer.annotation.translate(self.dx, self.dy);
+        }
+    }
+
+    fn revert(&self, document: &mut Document) {
+        if let Some(layer) = document.layers.get_mut(self.index) {
+            layer.annotation.translate(-self.dx, -self.dy);
+        }
+    }
+
+    fn description(&self) -> String {
+        format!("{} moved", self.name)
+    }
+
+    fn merge(&mut self, next: &dyn Command) -> bool {
+        match next.as_any().downcast_ref::<MoveCommand>() {
+            Some(next) if next.index == self.index => {
+                self.dx += next.dx;
+                self.dy += next.dy;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn icon_name(&self) -> &'static str {
+        "find-location-symbolic"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Move a layer from one position in the stacking order to another
+#[derive(Debug, Clone)]
+pub struct ReorderCommand {
+    pub from: usize,
+    pub to: usize,
+    /// Number of layers, to tell moves to the front or back from single steps
+    pub layers: usize,
+}
+
+impl ReorderCommand {
+    fn shift(document: &mut Document, from: usize, to: usize) {
+        if from < document.layers.len() {
+            let layer = document.layers.remove(from);
+            let to = to.min(document.layers.len());
+            document.layers.insert(to, layer);
+        }
+    }
+}
+
+impl Command for ReorderCommand {
+    fn apply(&self, document: &mut Document) {
+        Self::shift(document, self.from, self.to);
+    }
+
+    fn revert(&self, document: &mut Document) {
+        Self::shift(document, self.to, self.from);
+    }
+
+    fn description(&self) -> String {
+        if self.to > self.from {
+            if self.to + 1 >= self.layers { "Brought to front" } else { "Brought forward" }
+        } else if self.to == 0 {
+            "Sent to back"
+        } else {
+            "Sent backward"
+        }
+        .to_string()
+    }
+
+    fn icon_name(&self) -> &'static str {
+        "view-list-symbolic"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Change a layer's name, visibility or lock
+#[derive(Debug, Clone)]
+pub struct LayerCommand {
+    pub index: usize,
+    pub before: Layer,
+    pub after: Layer,
+}
+
+impl Command for LayerCommand {
+    fn apply(&self, document: &mut Document) {
+        if let Some(layer) = document.layers.get_mut(self.index) {
+            *layer = self.after.clone();
+        }
+    }
+
+    fn revert(&self, document: &mut Document) {
+        if let Some(layer) = document.layers.get_mut(self.index) {
+            *layer = self.before.clone();
+        }
+    }
+
+    fn description(&self) -> String {
+        let name = self.before.display_name();
+        if self.before.visible != self.after.visible {
+            format!("{} {}", name, if self.after.visible { "shown" } else { "hidden" })
+        } else if self.before.locked != self.after.locked {
+            format!("{} {}", name, if self.after.locked { "locked" } else { "unlocked" })
+        } else {
+            format!("{} renamed to {}", name, self.after.display_name())
+        }
+    }
+
+    fn icon_name(&self) -> &'static str {
+        if self.before.visible != self.after.visible {
+            if self.after.visible { "view-reveal-symbolic" } else { "view-conceal-symbolic" }
+        } else if self.before.locked != self.after.locked {
+            if self.after.locked { "changes-prevent-symbolic" } else { "changes-allow-symbolic" }
+        } else {
+            "document-edit-symbolic"
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Change the area of the screenshot kept on export
 #[derive(Debug, Clone)]
-pub enum HistoryAction {
-    Add(Annotation),
-    Remove(usize, Annotation),
+pub struct CropCommand {
+    pub before: Option<(f64, f64, f64, f64)>,
+    pub after: Option<(f64, f64, f64, f64)>,
+}
+
+impl Command for CropCommand {
+    fn apply(&self, document: &mut Document) {
+        document.crop = self.after;
+    }
+
+    fn revert(&self, document: &mut Document) {
+        document.crop = self.before;
+    }
+
+    fn description(&self) -> String {
+        if self.after.is_some() { "Cropped" } else { "Crop removed" }.to_string()
+    }
+
+    fn icon_name(&self) -> &'static str {
+        "edit-cut-symbolic"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Several commands undone and redone together as one step
+#[derive(Debug)]
+pub struct BatchCommand {
+    pub description: String,
+    pub commands: Vec<Box<dyn Command>>,
+}
+
+impl BatchCommand {
+    pub fn new(description: impl Into<String>, commands: Vec<Box<dyn Command>>) -> Self {
+        Self {
+            description: description.into(),
+            commands,
+        }
+    }
+}
+
+impl Command for BatchCommand {
+    fn apply(&self, document: &mut Document) {
+        for command in &self.commands {
+            command.apply(document);
+        }
+    }
+
+    fn revert(&self, document: &mut Document) {
+        for command in self.commands.iter().rev() {
+            command.revert(document);
+        }
+    }
+
+    fn description(&self) -> String {
+        self.description.clone()
+    }
+
+    fn icon_name(&self) -> &'static str {
+        self.commands.first().map_or("document-edit-symbolic", |command| command.icon_name())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// One step of the history, for display
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub description: String,
+    pub icon_name: &'static str,
+    /// Whether the step has been undone and can be redone
+    pub undone: bool,
 }
 
-#[derive(Debug, Default)]
+/// Undo and redo stacks of commands applied to a `Document`
+#[derive(Debug)]
 pub struct History {
-    undo_stack: Vec<HistoryAction>,
-    redo_stack: Vec<HistoryAction>,
+    undo_stack: Vec<Box<dyn Command>>,
+    redo_stack: Vec<Box<dyn Command>>,
+    limit: usize,
+    /// Whether steps were dropped to stay within `limit`
+    dropped: bool,
+    /// Whether the newest command belongs to a gesture that is still going on
+    /// and may absorb the next continuous command
+    open: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl History {
     pub fn new() -> Self {
-        Self::default()
+        Self::with_limit(DEFAULT_LIMIT)
     }
 
-    pub fn push_add(&mut self, annotation: Annotation) {
-        self.undo_stack.push(HistoryAction::Add(annotation));
-        self.redo_stack.clear();
+    /// Keep at most `limit` undo steps, dropping the oldest
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            limit: limit.max(1),
+            dropped: false,
+            open: false,
+        }
+    }
+
+    /// Keep at most `limit` undo steps from now on, dropping the oldest
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+        self.trim();
+    }
+
+    /// Apply `command` and record it as its own undo step
+    pub fn execute(&mut self, command: impl Command, document: &mut Document) {
+        command.apply(document);
+        self.push(Box::new(command));
+        self.open = false;
     }
 
-    pub fn push_remove(&mut self, index: usize, annotation: Annotation) {
-        self.undo_stack.push(HistoryAction::Remove(index, annotation));
+    /// Apply `command` as part of a continuous gesture such as a drag
+    ///
+    /// Consecutive continuous commands are merged into one undo step where
+    /// they allow it, until `seal` is called.
+    pub fn execute_continuous(&mut self, command: impl Command, document: &mut Document) {
+        command.apply(document);
+
+        if self.open {
+            if let Some(top) = self.undo_stack.last_mut() {
+                if top.merge(&command) {
+                    self.redo_stack.clear();
+                    return;
+                }
+            }
+        }
+
+        self.push(Box::new(command));
+        self.open = true;
+    }
+
+    /// End the current continuous gesture so the next command starts a new step
+    pub fn seal(&mut self) {
+        self.open = false;
+    }
+
+    fn push(&mut self, command: Box<dyn Command>) {
+        self.undo_stack.push(command);
         self.redo_stack.clear();
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        if self.undo_stack.len() > self.limit {
+            let excess = self.undo_stack.len() - self.limit;
+            self.undo_stack.drain(..excess);
+            self.dropped = true;
+        }
+    }
+
+    /// Whether the oldest steps were dropped, so undoing everything no longer
+    /// gets back to the original document
+    pub fn has_dropped_steps(&self) -> bool {
+        self.dropped
     }
 
     pub fn can_undo(&self) -> bool {
@@ -35,49 +460,298 @@ impl History {
         !self.redo_stack.is_empty()
     }
 
-    pub fn undo(&mut self, annotations: &mut Vec<Annotation>) -> bool {
-        if let Some(action) = self.undo_stack.pop() {
-            match action {
-                HistoryAction::Add(annotation) => {
-                    // Remove the last added annotation
-                    if let Some(removed) = annotations.pop() {
-                        self.redo_stack.push(HistoryAction::Add(removed));
-                    }
-                }
-                HistoryAction::Remove(index, annotation) => {
-                    // Re-add the removed annotation
-                    annotations.insert(index, annotation.clone());
-                    self.redo_stack.push(HistoryAction::Remove(index, annotation));
-                }
-            }
-            true
-        } else {
-            false
-        }
+    pub fn undo(&mut self, document: &mut Document) -> bool {
+        self.open = false;
+        let Some(command) = self.undo_stack.pop() else {
+            return false;
+        };
+        command.revert(document);
+        self.redo_stack.push(command);
+        true
     }
 
-    pub fn redo(&mut self, annotations: &mut Vec<Annotation>) -> bool {
-        if let Some(action) = self.redo_stack.pop() {
-            match action {
-                HistoryAction::Add(annotation) => {
-                    annotations.push(annotation.clone());
-                    self.undo_stack.push(HistoryAction::Add(annotation));
-                }
-                HistoryAction::Remove(index, annotation) => {
-                    if index < annotations.len() {
-                        annotations.remove(index);
-                    }
-                    self.undo_stack.push(HistoryAction::Remove(index, annotation));
-                }
-            }
-            true
-        } else {
-            false
+    pub fn redo(&mut self, document: &mut Document) -> bool {
+        self.open = false;
+        let Some(command) = self.redo_stack.pop() else {
+            return false;
+        };
+        command.apply(document);
+        self.undo_stack.push(command);
+        self.trim();
+        true
+    }
+
+    /// Every step, oldest first, including undone ones that can still be redone
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        let done = self.undo_stack.iter().map(|command| (command, false));
+        let undone = self.redo_stack.iter().rev().map(|command| (command, true));
+        done.chain(undone)
+            .map(|(command, undone)| HistoryEntry {
+                description: command.description(),
+                icon_name: command.icon_name(),
+                undone,
+            })
+            .collect()
+    }
+
+    /// Number of steps currently applied; the index into `entries` of the first undone step
+    pub fn position(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+    /// Undo or redo until exactly `position` steps are applied
+    pub fn jump_to(&mut self, position: usize, document: &mut Document) -> bool {
+        let mut changed = false;
+        while self.position() > position && self.undo(document) {
+            changed = true;
+        }
+        while self.position() < position && self.redo(document) {
+            changed = true;
         }
+        changed
     }
 
     pub fn clear(&mut self) {
         self.undo_stack.clear();
         self.redo_stack.clear();
+        self.dropped = false;
+        self.open = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{BlurAnnotation, Point};
+
+    fn blur(x: f64) -> Annotation {
+        Annotation::Blur(BlurAnnotation::new(Point::new(x, 0.0), Point::new(x + 10.0, 10.0)))
+    }
+
+    fn layer(x: f64) -> Layer {
+        Layer::new(blur(x))
+    }
+
+    fn document(xs: &[f64]) -> Document {
+        Document {
+            layers: xs.iter().map(|&x| layer(x)).collect(),
+            crop: None,
+        }
+    }
+
+    #[test]
+    fn undo_add_removes_the_added_annotation() {
+        let mut doc = document(&[1.0, 2.0]);
+        let mut history = History::new();
+
+        history.execute(AddCommand { index: 0, layer: layer(3.0) }, &mut doc);
+        assert_eq!(doc, document(&[3.0, 1.0, 2.0]));
+
+        history.undo(&mut doc);
+        assert_eq!(doc, document(&[1.0, 2.0]));
+    }
+
+    #[test]
+    fn undo_all_then_redo_all_round_trips() {
+        let mut doc = document(&[1.0, 2.0, 3.0]);
+        let initial = doc.clone();
+        let mut history = History::new();
+
+        history.execute(AddCommand { index: 1, layer: layer(4.0) }, &mut doc);
+        history.execute(RemoveCommand { index: 0, layer: layer(1.0) }, &mut doc);
+        history.execute(ModifyCommand { index: 0, before: blur(4.0), after: blur(5.0) }, &mut doc);
+        history.execute(MoveCommand { index: 1, dx: 2.0, dy: 3.0, name: "Blur" }, &mut doc);
+        history.execute(ReorderCommand { from: 2, to: 0, layers: 3 }, &mut doc);
+        history.execute(CropCommand { before: None, after: Some((0.0, 0.0, 5.0, 5.0)) }, &mut doc);
+        let last = doc.clone();
+
+        while history.undo(&mut doc) {}
+        assert_eq!(doc, initial);
+        assert!(!history.can_undo());
+
+        while history.redo(&mut doc) {}
+        assert_eq!(doc, last);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn reorder_reverts_in_both_directions() {
+        for (from, to) in [(0, 2), (2, 0), (1, 1)] {
+            let mut doc = document(&[1.0, 2.0, 3.0]);
+            let mut history = History::new();
+            history.execute(ReorderCommand { from, to, layers: 3 }, &mut doc);
+            assert_eq!(doc.layers[to], layer(1.0 + from as f64));
+            history.undo(&mut doc);
+            assert_eq!(doc, document(&[1.0, 2.0, 3.0]));
+        }
+    }
+
+    #[test]
+    fn reorder_names_moves_to_the_front_or_back() {
+        let description = |from, to| ReorderCommand { from, to, layers: 4 }.description();
+        assert_eq!(description(0, 1), "Brought forward");
+        assert_eq!(description(0, 3), "Brought to front");
+        assert_eq!(description(3, 2), "Sent backward");
+        assert_eq!(description(3, 0), "Sent to back");
+    }
+
+    #[test]
+    fn hiding_a_layer_is_undoable() {
+        let mut doc = document(&[1.0, 2.0]);
+        let mut history = History::new();
+
+        let mut hidden = layer(1.0);
+        hidden.visible = false;
+        let command = LayerCommand { index: 0, before: layer(1.0), after: hidden };
+        assert_eq!(command.description(), "Blur hidden");
+        history.execute(command, &mut doc);
+        assert_eq!(doc.visible_annotations().cloned().collect::<Vec<_>>(), [blur(2.0)]);
+
+        history.undo(&mut doc);
+        assert_eq!(doc, document(&[1.0, 2.0]));
+    }
+
+    #[test]
+    fn batch_reverts_in_reverse_order() {
+        let mut doc = document(&[1.0]);
+        let mut history = History::new();
+
+        let batch = BatchCommand::new(
+            "Two added",
+            vec![
+                Box::new(AddCommand { index: 1, layer: layer(2.0) }),
+                Box::new(ReorderCommand { from: 1, to: 0, layers: 2 }),
+            ],
+        );
+        history.execute(batch, &mut doc);
+        assert_eq!(doc, document(&[2.0, 1.0]));
+
+        assert!(history.undo(&mut doc));
+        assert_eq!(doc, document(&[1.0]));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn continuous_commands_coalesce_until_sealed() {
+        let mut doc = document(&[0.0]);
+        let mut history = History::new();
+
+        for _ in 0..5 {
+            history.execute_continuous(MoveCommand { index: 0, dx: 1.0, dy: 0.0, name: "Blur" }, &mut doc);
+        }
+        history.seal();
+        history.execute_continuous(MoveCommand { index: 0, dx: 1.0, dy: 0.0, name: "Blur" }, &mut doc);
+        assert_eq!(doc, document(&[6.0]));
+
+        history.undo(&mut doc);
+        assert_eq!(doc, document(&[5.0]));
+        history.undo(&mut doc);
+        assert_eq!(doc, document(&[0.0]));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn continuous_modify_keeps_the_first_before() {
+        let mut doc = document(&[0.0]);
+        let mut history = History::new();
+
+        history.execute_continuous(ModifyCommand { index: 0, before: blur(0.0), after: blur(1.0) }, &mut doc);
+        history.execute_continuous(ModifyCommand { index: 0, before: blur(1.0), after: blur(2.0) }, &mut doc);
+        assert_eq!(doc, document(&[2.0]));
+
+        history.undo(&mut doc);
+        assert_eq!(doc, document(&[0.0]));
+        history.redo(&mut doc);
+        assert_eq!(doc, document(&[2.0]));
+    }
+
+    #[test]
+    fn different_targets_do_not_coalesce() {
+        let mut doc = document(&[0.0, 10.0]);
+        let mut history = History::new();
+
+        history.execute_continuous(MoveCommand { index: 0, dx: 1.0, dy: 0.0, name: "Blur" }, &mut doc);
+        history.execute_continuous(MoveCommand { index: 1, dx: 1.0, dy: 0.0, name: "Blur" }, &mut doc);
+
+        history.undo(&mut doc);
+        assert_eq!(doc, document(&[1.0, 10.0]));
+    }
+
+    #[test]
+    fn jump_to_moves_through_entries() {
+        let mut doc = document(&[]);
+        let mut history = History::new();
+        for i in 0..3 {
+            history.execute(AddCommand { index: i, layer: layer(i as f64) }, &mut doc);
+        }
+
+        assert!(history.jump_to(1, &mut doc));
+        assert_eq!(doc, document(&[0.0]));
+        let entries = history.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].description, "Blur added");
+        assert_eq!(entries.iter().map(|e| e.undone).collect::<Vec<_>>(), [false, true, true]);
+
+        assert!(history.jump_to(3, &mut doc));
+        assert_eq!(doc, document(&[0.0, 1.0, 2.0]));
+        assert!(!history.jump_to(3, &mut doc));
+
+        history.jump_to(0, &mut doc);
+        assert_eq!(doc, document(&[]));
+        assert_eq!(history.position(), 0);
+    }
+
+    #[test]
+    fn new_command_clears_redo() {
+        let mut doc = document(&[]);
+        let mut history = History::new();
+
+        history.execute(AddCommand { index: 0, layer: layer(1.0) }, &mut doc);
+        history.undo(&mut doc);
+        assert!(history.can_redo());
+
+        history.execute(AddCommand { index: 0, layer: layer(2.0) }, &mut doc);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn limit_drops_oldest_steps() {
+        let mut doc = document(&[]);
+        let mut history = History::with_limit(3);
+
+        for i in 0..3 {
+            history.execute(AddCommand { index: i, layer: layer(i as f64) }, &mut doc);
+        }
+        assert!(!history.has_dropped_steps());
+        for i in 3..5 {
+            history.execute(AddCommand { index: i, layer: layer(i as f64) }, &mut doc);
+        }
+        assert!(history.has_dropped_steps());
+
+        let mut undone = 0;
+        while history.undo(&mut doc) {
+            undone += 1;
+        }
+        assert_eq!(undone, 3);
+        assert_eq!(doc, document(&[0.0, 1.0]));
+
+        history.set_limit(1);
+        while history.redo(&mut doc) {}
+        assert_eq!(doc.layers.len(), 5);
+    }
+
+    #[test]
+    fn lowering_limit_trims_existing_steps() {
+        let mut doc = document(&[]);
+        let mut history = History::new();
+        for i in 0..4 {
+            history.execute(AddCommand { index: i, layer: layer(i as f64) }, &mut doc);
+        }
+
+        history.set_limit(2);
+        history.undo(&mut doc);
+        history.undo(&mut doc);
+        assert!(!history.can_undo());
+        assert_eq!(doc, document(&[0.0, 1.0]));
     }
 }
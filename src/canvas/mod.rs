@@ -1,9 +1,17 @@
 mod annotations;
 mod canvas_widget;
+mod document;
+mod frame;
 mod history;
+mod presets;
+mod render;
+mod snapping;
 mod tools;
 
 pub use annotations::*;
 pub use canvas_widget::*;
+pub use document::{Document, Layer};
+pub use frame::{frame_backgrounds, FrameAspect, FrameBackground, FrameSettings};
 pub use history::*;
+pub use presets::{default_presets, StylePreset};
 pub use tools::Tool;
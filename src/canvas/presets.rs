@@ -0,0 +1,90 @@
+use gtk::gdk::RGBA;
+use serde::{Deserialize, Serialize};
+
+use super::annotations::{rgba_serde, ArrowStyle, DashStyle};
+use super::tools::Tool;
+
+/// A named bundle of tool and style settings that can be applied in one go
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StylePreset {
+    pub name: String,
+    pub tool: Tool,
+    #[serde(with = "rgba_serde")]
+    pub color: RGBA,
+    pub stroke_width: f64,
+    #[serde(default)]
+    pub filled: bool,
+    #[serde(default = "default_font_size")]
+    pub font_size: f64,
+    #[serde(default)]
+    pub arrow_style: ArrowStyle,
+    #[serde(default)]
+    pub dash_style: DashStyle,
+    #[serde(default)]
+    pub corner_radius: f64,
+}
+
+fn default_font_size() -> f64 {
+    16.0
+}
+
+impl StylePreset {
+    fn new(name: &str, tool: Tool, color: RGBA, stroke_width: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            tool,
+            color,
+            stroke_width,
+            filled: false,
+            font_size: default_font_size(),
+            arrow_style: ArrowStyle::Open,
+            dash_style: DashStyle::Solid,
+            corner_radius: 0.0,
+        }
+    }
+}
+
+/// Presets available before the user saves their own
+pub fn default_presets() -> Vec<StylePreset> {
+    let red = RGBA::new(1.0, 0.0, 0.0, 1.0);
+    let yellow = RGBA::new(1.0, 0.9, 0.0, 1.0);
+
+    vec![
+        StylePreset::new("Red Arrow", Tool::Arrow, red, 4.0),
+        StylePreset::new("Yellow Highlight", Tool::Highlight, yellow, 3.0),
+        StylePreset::new("Redact", Tool::Blur, red, 3.0),
+        StylePreset {
+            corner_radius: 6.0,
+            ..StylePreset::new("Red Box", Tool::Rectangle, red, 3.0)
+        },
+        StylePreset::new("Red Callout", Tool::Callout, red, 3.0),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_round_trip() {
+        let mut presets = default_presets();
+        presets.push(StylePreset {
+            filled: true,
+            font_size: 24.0,
+            arrow_style: ArrowStyle::Curved,
+            dash_style: DashStyle::Dotted,
+            ..StylePreset::new("Dotted", Tool::Line, RGBA::new(0.25, 0.5, 0.75, 0.5), 2.5)
+        });
+
+        let json = serde_json::to_string(&presets).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<StylePreset>>(&json).unwrap(), presets);
+    }
+
+    #[test]
+    fn missing_style_fields_get_defaults() {
+        let json = r#"{"name": "Old", "tool": "arrow", "color": [1.0, 0.0, 0.0, 1.0], "stroke_width": 4.0}"#;
+        let preset: StylePreset = serde_json::from_str(json).unwrap();
+
+        assert_eq!(preset, StylePreset::new("Old", Tool::Arrow, RGBA::new(1.0, 0.0, 0.0, 1.0), 4.0));
+    }
+}
@@ -0,0 +1,105 @@
+use gtk::gdk_pixbuf::Pixbuf;
+
+use super::annotations::{Annotation, SpotlightAnnotation, SpotlightEffect};
+
+/// Draw annotations on top of the screenshot, in image coordinates.
+///
+/// This is shared by the on-screen preview and the export paths so both
+/// produce the same result. Spotlights are not drawn one by one: they share a
+/// single dimmed layer that is painted where the first spotlight sits in the
+/// z-order, with every spotlight cut out of it, so overlapping spotlights
+/// don't stack their darkening. Magnifiers read the screenshot to fill
+/// their callout.
+pub fn draw_annotations<'a>(
+    cr: &cairo::Context,
+    pixbuf: &Pixbuf,
+    annotations: impl IntoIterator<Item = &'a Annotation>,
+) {
+    let annotations: Vec<&Annotation> = annotations.into_iter().collect();
+
+    let spotlights: Vec<&SpotlightAnnotation> = annotations
+        .iter()
+        .filter_map(|a| match a {
+            Annotation::Spotlight(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+
+    let mut spotlights_drawn = false;
+    for annotation in annotations {
+        if let Annotation::Spotlight(_) = annotation {
+            if !spotlights_drawn {
+                draw_spotlights(cr, pixbuf, &spotlights);
+                spotlights_drawn = true;
+            }
+            continue;
+        }
+        if let Annotation::Magnify(m) = annotation {
+            m.draw_magnified(cr, pixbuf, 1.0);
+        }
+        annotation.draw(cr, 1.0);
+    }
+}
+
+/// Dim or blur everything outside the spotlights in one shared layer. The
+/// topmost spotlight's effect and opacity apply to the whole layer, so the
+/// others only contribute their holes.
+fn draw_spotlights(cr: &cairo::Context, pixbuf: &Pixbuf, spotlights: &[&SpotlightAnnotation]) {
+    let Some(top) = spotlights.last() else { return };
+
+    cr.save().unwrap();
+    cr.rectangle(0.0, 0.0, pixbuf.width() as f64, pixbuf.height() as f64);
+    cr.clip();
+
+    cr.push_group();
+
+    if top.effect == SpotlightEffect::Blur {
+        paint_blurred(cr, pixbuf);
+    }
+
+    cr.set_source_rgba(0.0, 0.0, 0.0, top.opacity);
+    let _ = cr.paint();
+
+    // Punch every spotlight out of the layer; filling each hole separately
+    // gives their union even where they overlap
+    cr.set_operator(cairo::Operator::Clear);
+    for spotlight in spotlights {
+        spotlight.add_hole_path(cr, 1.0);
+        let _ = cr.fill();
+    }
+
+    if cr.pop_group_to_source().is_ok() {
+        let _ = cr.paint();
+    }
+
+    cr.restore().unwrap();
+}
+
+/// Paint a cheap blurred copy of the screenshot by scaling it down and back up
+fn paint_blurred(cr: &cairo::Context, pixbuf: &Pixbuf) {
+    const FACTOR: f64 = 12.0;
+
+    let width = ((pixbuf.width() as f64 / FACTOR).ceil() as i32).max(1);
+    let height = ((pixbuf.height() as f64 / FACTOR).ceil() as i32).max(1);
+
+    let Ok(small) = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height) else {
+        return;
+    };
+
+    {
+        let Ok(small_cr) = cairo::Context::new(&small) else { return };
+        small_cr.scale(1.0 / FACTOR, 1.0 / FACTOR);
+        gtk::gdk::prelude::GdkCairoContextExt::set_source_pixbuf(&small_cr, pixbuf, 0.0, 0.0);
+        let _ = small_cr.paint();
+    }
+
+    cr.save().unwrap();
+    cr.scale(FACTOR, FACTOR);
+    if cr.set_source_surface(&small, 0.0, 0.0).is_ok() {
+        let source = cr.source();
+        source.set_filter(cairo::Filter::Bilinear);
+        source.set_extend(cairo::Extend::Pad);
+        let _ = cr.paint();
+    }
+    cr.restore().unwrap();
+}
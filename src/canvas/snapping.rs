@@ -0,0 +1,212 @@
+use super::annotations::Point;
+
+/// A line drawn across the image while something is snapped to it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Guide {
+    Vertical(f64),
+    Horizontal(f64),
+}
+
+/// Positions that annotation points and edges can snap to, in image coordinates
+#[derive(Debug, Default, Clone)]
+pub struct SnapTargets {
+    pub xs: Vec<f64>,
+    pub ys: Vec<f64>,
+}
+
+impl SnapTargets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the edges and center lines of a rectangle
+    pub fn add_rect(&mut self, (x, y, w, h): (f64, f64, f64, f64)) {
+        self.xs.extend([x, x + w / 2.0, x + w]);
+        self.ys.extend([y, y + h / 2.0, y + h]);
+    }
+
+    pub fn extend(&mut self, other: &SnapTargets) {
+        self.xs.extend_from_slice(&other.xs);
+        self.ys.extend_from_slice(&other.ys);
+    }
+
+    /// Snap a point to the closest targets within `threshold` on each axis
+    pub fn snap_point(&self, point: Point, threshold: f64, guides: &mut Vec<Guide>) -> Point {
+        let mut snapped = point;
+        if let Some(x) = nearest(&self.xs, point.x, threshold) {
+            snapped.x = x;
+            guides.push(Guide::Vertical(x));
+        }
+        if let Some(y) = nearest(&self.ys, point.y, threshold) {
+            snapped.y = y;
+            guides.push(Guide::Horizontal(y));
+        }
+        snapped
+    }
+
+    /// Find how far to shift a rectangle so one of its edges or center lines lands on a target
+    pub fn snap_rect(&self, (x, y, w, h): (f64, f64, f64, f64), threshold: f64, guides: &mut Vec<Guide>) -> (f64, f64) {
+        let dx = best_offset(&self.xs, &[x, x + w / 2.0, x + w], threshold);
+        let dy = best_offset(&self.ys, &[y, y + h / 2.0, y + h], threshold);
+
+        if let Some((dx, target)) = dx {
+            guides.push(Guide::Vertical(target));
+            if let Some((dy, target)) = dy {
+                guides.push(Guide::Horizontal(target));
+                return (dx, dy);
+            }
+            return (dx, 0.0);
+        }
+        if let Some((dy, target)) = dy {
+            guides.push(Guide::Horizontal(target));
+            return (0.0, dy);
+        }
+        (0.0, 0.0)
+    }
+}
+
+fn nearest(targets: &[f64], value: f64, threshold: f64) -> Option<f64> {
+    targets
+        .iter()
+        .copied()
+        .filter(|t| (t - value).abs() <= threshold)
+        .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()))
+}
+
+/// Smallest offset that moves any of `edges` onto a target, with the target it lands on
+fn best_offset(targets: &[f64], edges: &[f64], threshold: f64) -> Option<(f64, f64)> {
+    edges
+        .iter()
+        .filter_map(|&edge| nearest(targets, edge, threshold).map(|target| (target - edge, target)))
+        .min_by(|a, b| a.0.abs().total_cmp(&b.0.abs()))
+}
+
+/// Find long straight horizontal and vertical edges in an image, such as
+/// window borders, panels and buttons.
+///
+/// `data` is 8-bit RGB or RGBA pixel data laid out like a `Pixbuf`. A column
+/// (or row) counts as an edge when the contrast with its neighbour stays high
+/// over a long enough unbroken run of pixels.
+pub fn detect_edges(data: &[u8], width: usize, height: usize, rowstride: usize, channels: usize) -> SnapTargets {
+    const CONTRAST: i32 = 40;
+    const MIN_RUN: usize = 32;
+
+    let luma = |x: usize, y: usize| -> i32 {
+        let i = y * rowstride + x * channels;
+        match data.get(i..i + 3) {
+            Some(px) => (px[0] as i32 * 299 + px[1] as i32 * 587 + px[2] as i32 * 114) / 1000,
+            None => 0,
+        }
+    };
+
+    let mut targets = SnapTargets::new();
+
+    for x in 1..width {
+        let mut run = 0;
+        let mut longest = 0;
+        for y in 0..height {
+            if (luma(x, y) - luma(x - 1, y)).abs() > CONTRAST {
+                run += 1;
+                longest = longest.max(run);
+            } else {
+                run = 0;
+            }
+        }
+        if longest >= MIN_RUN.min(height) {
+            targets.xs.push(x as f64);
+        }
+    }
+
+    for y in 1..height {
+        let mut run = 0;
+        let mut longest = 0;
+        for x in 0..width {
+            if (luma(x, y) - luma(x, y - 1)).abs() > CONTRAST {
+                run += 1;
+                longest = longest.max(run);
+            } else {
+                run = 0;
+            }
+        }
+        if longest >= MIN_RUN.min(width) {
+            targets.ys.push(y as f64);
+        }
+    }
+
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A white image with a dark box, as pixel data with `channels` per pixel
+    /// and `padding` junk bytes at the end of each row
+    fn image_with_box(
+        (width, height): (usize, usize),
+        (left, top, right, bottom): (usize, usize, usize, usize),
+        shade: u8,
+        channels: usize,
+        padding: usize,
+    ) -> (Vec<u8>, usize) {
+        let rowstride = width * channels + padding;
+        let mut data = vec![0xAB; rowstride * height];
+        for y in 0..height {
+            for x in 0..width {
+                let inside = (left..right).contains(&x) && (top..bottom).contains(&y);
+                let i = y * rowstride + x * channels;
+                data[i..i + 3].fill(if inside { shade } else { 255 });
+                if channels == 4 {
+                    data[i + 3] = 255;
+                }
+            }
+        }
+        (data, rowstride)
+    }
+
+    #[test]
+    fn finds_the_edges_of_a_box() {
+        let (data, rowstride) = image_with_box((100, 80), (20, 10, 60, 70), 30, 3, 0);
+        let targets = detect_edges(&data, 100, 80, rowstride, 3);
+
+        assert_eq!(targets.xs, [20.0, 60.0]);
+        assert_eq!(targets.ys, [10.0, 70.0]);
+    }
+
+    #[test]
+    fn reads_rgba_with_padded_rows() {
+        let (data, rowstride) = image_with_box((100, 80), (20, 10, 60, 70), 30, 4, 12);
+        let targets = detect_edges(&data, 100, 80, rowstride, 4);
+
+        assert_eq!(targets.xs, [20.0, 60.0]);
+        assert_eq!(targets.ys, [10.0, 70.0]);
+    }
+
+    #[test]
+    fn ignores_short_and_faint_edges() {
+        // Too small to be a panel or window border
+        let (data, rowstride) = image_with_box((100, 80), (20, 10, 40, 30), 30, 3, 0);
+        let targets = detect_edges(&data, 100, 80, rowstride, 3);
+        assert!(targets.xs.is_empty() && targets.ys.is_empty());
+
+        // Large but barely different from the background
+        let (data, rowstride) = image_with_box((100, 80), (20, 10, 60, 70), 230, 3, 0);
+        let targets = detect_edges(&data, 100, 80, rowstride, 3);
+        assert!(targets.xs.is_empty() && targets.ys.is_empty());
+    }
+
+    #[test]
+    fn snaps_points_within_the_threshold() {
+        let mut targets = SnapTargets::new();
+        targets.add_rect((10.0, 20.0, 100.0, 50.0));
+        let mut guides = Vec::new();
+
+        let snapped = targets.snap_point(Point::new(13.0, 44.0), 4.0, &mut guides);
+        assert_eq!(snapped, Point::new(10.0, 45.0));
+        assert_eq!(guides, [Guide::Vertical(10.0), Guide::Horizontal(45.0)]);
+
+        guides.clear();
+        assert_eq!(targets.snap_point(Point::new(30.0, 0.0), 4.0, &mut guides), Point::new(30.0, 0.0));
+        assert!(guides.is_empty());
+    }
+}
@@ -1,5 +1,9 @@
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Tool {
+    Select,
     #[default]
     Arrow,
     Rectangle,
@@ -7,17 +11,51 @@ pub enum Tool {
     Ellipse,
     Highlight,
     Blur,
+    Spotlight,
+    Magnify,
+    Callout,
+    Stamp,
+    Crop,
+    Text,
 }
 
 impl Tool {
     pub fn name(&self) -> &'static str {
         match self {
+            Tool::Select => "Select",
             Tool::Arrow => "Arrow",
             Tool::Rectangle => "Rectangle",
             Tool::Line => "Line",
             Tool::Ellipse => "Ellipse",
             Tool::Highlight => "Highlight",
             Tool::Blur => "Blur",
+            Tool::Spotlight => "Spotlight",
+            Tool::Magnify => "Magnify",
+            Tool::Callout => "Callout",
+            Tool::Stamp => "Stamp",
+            Tool::Crop => "Crop",
+            Tool::Text => "Copy Text",
         }
     }
+
+    /// Tool selected by pressing a single letter key
+    pub fn from_shortcut(key: char) -> Option<Tool> {
+        let tool = match key.to_ascii_lowercase() {
+            'v' => Tool::Select,
+            'a' => Tool::Arrow,
+            'r' => Tool::Rectangle,
+            'l' => Tool::Line,
+            'e' => Tool::Ellipse,
+            'h' => Tool::Highlight,
+            'b' => Tool::Blur,
+            's' => Tool::Spotlight,
+            'm' => Tool::Magnify,
+            't' => Tool::Callout,
+            'p' => Tool::Stamp,
+            'c' => Tool::Crop,
+            'o' => Tool::Text,
+            _ => return None,
+        };
+        Some(tool)
+    }
 }
@@ -0,0 +1,137 @@
+//! Lay several images out side by side, stacked, or in a grid.
+
+use gtk::gdk_pixbuf::Pixbuf;
+use image::{Rgba, RgbaImage};
+
+use super::stitch::{image_to_pixbuf, pixbuf_to_image};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombineLayout {
+    /// One row, left to right
+    Horizontal,
+    /// One column, top to bottom
+    Vertical,
+    /// Rows of `columns` images, filled left to right
+    Grid { columns: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CombineOptions {
+    pub layout: CombineLayout,
+    /// Space between images, in pixels
+    pub gap: u32,
+    /// Shows in the gaps and around images smaller than their cell
+    pub background: Rgba<u8>,
+}
+
+/// Place `images` in a grid where every column is as wide as its widest image
+/// and every row as tall as its tallest, each image centered in its cell
+///
+/// Returns `None` if there are no images.
+pub fn combine(images: &[RgbaImage], options: &CombineOptions) -> Option<RgbaImage> {
+    if images.is_empty() {
+        return None;
+    }
+
+    let count = images.len() as u32;
+    let columns = match options.layout {
+        CombineLayout::Horizontal => count,
+        CombineLayout::Vertical => 1,
+        CombineLayout::Grid { columns } => columns.clamp(1, count),
+    };
+    let rows = count.div_ceil(columns);
+
+    let mut widths = vec![0; columns as usize];
+    let mut heights = vec![0; rows as usize];
+    for (i, image) in images.iter().enumerate() {
+        let (column, row) = (i % columns as usize, i / columns as usize);
+        widths[column] = widths[column].max(image.width());
+        heights[row] = heights[row].max(image.height());
+    }
+
+    // Offset of each column and row, gaps included
+    let offsets = |sizes: &[u32]| -> Vec<u32> {
+        sizes
+            .iter()
+            .scan(0, |next, size| {
+                let offset = *next;
+                *next += size + options.gap;
+                Some(offset)
+            })
+            .collect()
+    };
+    let xs = offsets(&widths);
+    let ys = offsets(&heights);
+
+    let width = widths.iter().sum::<u32>() + options.gap * (columns - 1);
+    let height = heights.iter().sum::<u32>() + options.gap * (rows - 1);
+    let mut combined = RgbaImage::from_pixel(width, height, options.background);
+
+    for (i, image) in images.iter().enumerate() {
+        let (column, row) = (i % columns as usize, i / columns as usize);
+        let x = xs[column] + (widths[column] - image.width()) / 2;
+        let y = ys[row] + (heights[row] - image.height()) / 2;
+        image::imageops::overlay(&mut combined, image, x as i64, y as i64);
+    }
+
+    Some(combined)
+}
+
+pub fn combine_pixbufs(images: &[Pixbuf], options: &CombineOptions) -> Option<Pixbuf> {
+    let images: Vec<RgbaImage> = images.iter().map(pixbuf_to_image).collect();
+    combine(&images, options).map(image_to_pixbuf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+    fn solid(width: u32, height: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba([value, value, value, 255]))
+    }
+
+    fn options(layout: CombineLayout, gap: u32) -> CombineOptions {
+        CombineOptions { layout, gap, background: WHITE }
+    }
+
+    #[test]
+    fn side_by_side_centers_shorter_images() {
+        let images = [solid(30, 20, 10), solid(40, 10, 20)];
+        let combined = combine(&images, &options(CombineLayout::Horizontal, 5)).unwrap();
+
+        assert_eq!(combined.dimensions(), (75, 20));
+        assert_eq!(*combined.get_pixel(0, 0), Rgba([10, 10, 10, 255]));
+        // The gap, then the second image with background above and below it
+        assert_eq!(*combined.get_pixel(32, 10), WHITE);
+        assert_eq!(*combined.get_pixel(50, 2), WHITE);
+        assert_eq!(*combined.get_pixel(50, 10), Rgba([20, 20, 20, 255]));
+    }
+
+    #[test]
+    fn stacked_images_share_one_column() {
+        let images = [solid(30, 20, 10), solid(10, 10, 20)];
+        let combined = combine(&images, &options(CombineLayout::Vertical, 4)).unwrap();
+
+        assert_eq!(combined.dimensions(), (30, 34));
+        assert_eq!(*combined.get_pixel(15, 29), Rgba([20, 20, 20, 255]));
+        assert_eq!(*combined.get_pixel(5, 29), WHITE);
+    }
+
+    #[test]
+    fn grid_wraps_into_rows() {
+        let images = [solid(10, 10, 1), solid(20, 10, 2), solid(10, 30, 3)];
+        let combined = combine(&images, &options(CombineLayout::Grid { columns: 2 }, 2)).unwrap();
+
+        // Columns 10 and 20 wide, rows 10 and 30 tall
+        assert_eq!(combined.dimensions(), (32, 42));
+        assert_eq!(*combined.get_pixel(5, 20), Rgba([3, 3, 3, 255]));
+        assert_eq!(*combined.get_pixel(20, 20), WHITE);
+    }
+
+    #[test]
+    fn nothing_to_combine() {
+        assert!(combine(&[], &options(CombineLayout::Horizontal, 0)).is_none());
+    }
+}
@@ -1,5 +1,21 @@
+mod combine;
 mod portal;
+mod raw_file;
+mod recording;
+mod region;
+mod screencast;
+mod selection;
+mod stitch;
 mod types;
+mod window;
 
+pub use combine::{combine_pixbufs, CombineLayout, CombineOptions};
 pub use portal::*;
+pub use raw_file::{release_raw_file, unique_path, RawFilePolicy};
+pub use recording::{export_recording, prepare_frames, ExportOptions, Recording, RecordingFormat, Region};
+pub use region::{crop_to_area, desktop_scale, display_scale_factor, monitor_area, monitor_name, monitors, Area};
+pub use screencast::{open_screencast, Recorder, ScreencastStream};
+pub use selection::{CapturePreset, Constraint, Selection};
+pub use stitch::{pixbuf_to_image, stitch_pixbufs};
 pub use types::*;
+pub use window::{capture_window_path, load_window_image, window_screenshot, WindowOptions};
@@ -24,6 +24,9 @@ pub fn load_screenshot_from_path(path: PathBuf) -> anyhow::Result<Screenshot> {
         pixbuf,
         source_path: Some(path),
         capture_mode: CaptureMode::Region,
+        window: None,
+        scale_factor: super::display_scale_factor(),
+        preset: None,
     })
 }
 
@@ -34,8 +37,8 @@ pub async fn capture_interactive() -> anyhow::Result<Screenshot> {
     load_screenshot_from_path(path)
 }
 
-/// Capture the entire screen without interactive dialog
-pub async fn capture_fullscreen() -> anyhow::Result<Screenshot> {
+/// Capture the entire screen without interactive dialog (returns path only, for thread safety)
+pub async fn capture_fullscreen_path() -> anyhow::Result<PathBuf> {
     let response = PortalScreenshot::request()
         .interactive(false)
         .modal(false)
@@ -44,14 +47,21 @@ pub async fn capture_fullscreen() -> anyhow::Result<Screenshot> {
         .response()?;
 
     let uri = response.uri();
-    let path = uri_to_path(uri)?;
+    uri_to_path(uri)
+}
 
+/// Load a fullscreen capture from a file path (must be called on GTK thread)
+pub fn load_fullscreen_from_path(path: PathBuf) -> anyhow::Result<Screenshot> {
     let pixbuf = Pixbuf::from_file(&path)?;
+    let scale_factor = super::desktop_scale(pixbuf.width());
 
     Ok(Screenshot {
         pixbuf,
         source_path: Some(path),
         capture_mode: CaptureMode::Fullscreen,
+        window: None,
+        scale_factor,
+        preset: None,
     })
 }
 
@@ -0,0 +1,131 @@
+//! What happens to the raw file a capture was loaded from.
+//!
+//! The portal writes every capture into the Pictures folder before we load
+//! it, so once the annotated image is saved there would be two copies. The
+//! `raw_files` setting picks whether the raw one is deleted, moved into an
+//! `Originals` folder next to the saved image, or left where it is, which is
+//! the default so nothing is removed unless asked for.
+
+use std::path::{Path, PathBuf};
+
+/// Folder next to the saved image that raw files are moved into
+const ORIGINALS_DIR: &str = "Originals";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RawFilePolicy {
+    #[default]
+    Keep,
+    Delete,
+    Move,
+}
+
+impl RawFilePolicy {
+    /// Read the `raw_files` setting: `keep`, `delete` or `move`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "keep" => Some(RawFilePolicy::Keep),
+            "delete" => Some(RawFilePolicy::Delete),
+            "move" => Some(RawFilePolicy::Move),
+            _ => None,
+        }
+    }
+}
+
+/// Apply `policy` to the raw capture at `raw` now that it was saved as `saved`;
+/// returns where the raw file is afterwards, if it still exists
+pub fn release_raw_file(raw: &Path, saved: &Path, policy: RawFilePolicy) -> anyhow::Result<Option<PathBuf>> {
+    // Saved over the raw file, or already gone
+    if raw == saved || !raw.exists() {
+        return Ok(None);
+    }
+
+    match policy {
+        RawFilePolicy::Keep => Ok(Some(raw.to_path_buf())),
+        RawFilePolicy::Delete => {
+            std::fs::remove_file(raw)?;
+            Ok(None)
+        }
+        RawFilePolicy::Move => {
+            let dir = saved.parent().unwrap_or(Path::new(".")).join(ORIGINALS_DIR);
+            std::fs::create_dir_all(&dir)?;
+            let file_name = raw.file_name().ok_or_else(|| anyhow::anyhow!("Raw capture has no file name"))?;
+            let target = unique_path(&dir, Path::new(file_name));
+            // Renaming fails across file systems, so fall back to copying
+            if std::fs::rename(raw, &target).is_err() {
+                std::fs::copy(raw, &target)?;
+                std::fs::remove_file(raw)?;
+            }
+            Ok(Some(target))
+        }
+    }
+}
+
+/// `file_name` in `dir`, numbered like "Screenshot (2).png" if that's taken
+pub fn unique_path(dir: &Path, file_name: &Path) -> PathBuf {
+    let stem = file_name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = file_name.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    std::iter::once(dir.join(file_name))
+        .chain((2..).map(|n| dir.join(format!("{} ({}){}", stem, n, extension))))
+        .find(|path| !path.exists())
+        .expect("some numbered name is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh folder with a raw capture and a saved screenshot in it
+    fn setup(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("gnome-shot-raw-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let raw = dir.join("Screenshot from today.png");
+        let saved = dir.join("screenshot-annotated.png");
+        std::fs::write(&raw, b"raw").unwrap();
+        std::fs::write(&saved, b"saved").unwrap();
+        (dir, raw, saved)
+    }
+
+    #[test]
+    fn keeps_or_deletes_the_raw_file() {
+        let (dir, raw, saved) = setup("delete");
+
+        assert_eq!(release_raw_file(&raw, &saved, RawFilePolicy::Keep).unwrap(), Some(raw.clone()));
+        assert!(raw.exists());
+
+        assert_eq!(release_raw_file(&raw, &saved, RawFilePolicy::Delete).unwrap(), None);
+        assert!(!raw.exists());
+        assert!(saved.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn moves_the_raw_file_next_to_the_saved_one() {
+        let (dir, raw, saved) = setup("move");
+
+        let moved = release_raw_file(&raw, &saved, RawFilePolicy::Move).unwrap().unwrap();
+        assert_eq!(moved, dir.join("Originals").join("Screenshot from today.png"));
+        assert_eq!(std::fs::read(&moved).unwrap(), b"raw");
+        assert!(!raw.exists());
+
+        // A second raw file with the same name doesn't replace the first
+        std::fs::write(&raw, b"raw again").unwrap();
+        let second = release_raw_file(&raw, &saved, RawFilePolicy::Move).unwrap().unwrap();
+        assert_eq!(second, dir.join("Originals").join("Screenshot from today (2).png"));
+        assert_eq!(std::fs::read(&moved).unwrap(), b"raw");
+        assert_eq!(std::fs::read(&second).unwrap(), b"raw again");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn never_touches_the_saved_file() {
+        let (dir, _, saved) = setup("same");
+
+        assert_eq!(release_raw_file(&saved, &saved, RawFilePolicy::Delete).unwrap(), None);
+        assert!(saved.exists());
+        assert_eq!(RawFilePolicy::parse("move"), Some(RawFilePolicy::Move));
+        assert_eq!(RawFilePolicy::parse("shred"), None);
+        assert_eq!(RawFilePolicy::default(), RawFilePolicy::Keep);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,431 @@
+//! Turn recorded screencast frames into animated GIF, APNG or WebM files.
+//!
+//! Raw frames are written to a temporary file as they arrive, so a recording
+//! is limited by disk space rather than memory, and read back one at a time
+//! while encoding. They arrive whenever the screen changes, so they are
+//! resampled to a fixed frame rate first; ticks that show the same recorded
+//! frame are merged into one longer frame, which keeps still stretches small
+//! in GIF and APNG.
+
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::FilterType;
+use image::{Delay, Rgba, RgbaImage};
+
+/// Where a recorded frame is in the file and when it was shown, relative to
+/// the start of the recording
+#[derive(Debug, Clone, Copy)]
+struct StoredFrame {
+    offset: u64,
+    width: u32,
+    height: u32,
+    time: Duration,
+}
+
+#[derive(Debug)]
+pub struct Recording {
+    /// Raw RGBA frames one after another; already unlinked, so it goes away with the recording
+    file: File,
+    /// Frames in the order they were shown
+    frames: Vec<StoredFrame>,
+    /// Bytes written to the file so far
+    size: u64,
+    /// When recording stopped, so the last frame keeps showing until then
+    pub length: Duration,
+    /// Why recording stopped before it was asked to, if it did
+    pub interrupted: Option<String>,
+}
+
+impl Recording {
+    /// An empty recording keeping its frames in a temporary file in `dir`
+    pub fn new(dir: &Path) -> std::io::Result<Self> {
+        static COUNT: AtomicU32 = AtomicU32::new(0);
+
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!(
+            "recording-{}-{}.rgba",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::options().read(true).write(true).create_new(true).open(&path)?;
+        // The open file stays usable, and nothing is left behind if we crash
+        std::fs::remove_file(&path)?;
+
+        Ok(Self { file, frames: Vec::new(), size: 0, length: Duration::ZERO, interrupted: None })
+    }
+
+    /// Add a frame shown at `time`, after all the others
+    pub fn push(&mut self, image: &RgbaImage, time: Duration) -> std::io::Result<()> {
+        self.file.write_all_at(image.as_raw(), self.size)?;
+        self.frames.push(StoredFrame { offset: self.size, width: image.width(), height: image.height(), time });
+        self.size += image.as_raw().len() as u64;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Size of the first frame
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.frames.first().map(|frame| (frame.width, frame.height))
+    }
+
+    /// Read the frame at `index` back from the file
+    fn image(&self, index: usize) -> anyhow::Result<RgbaImage> {
+        let frame = self.frames[index];
+        let mut pixels = vec![0; frame.width as usize * frame.height as usize * 4];
+        self.file.read_exact_at(&mut pixels, frame.offset)?;
+        RgbaImage::from_raw(frame.width, frame.height, pixels).ok_or_else(|| anyhow::anyhow!("Recorded frame is damaged"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Gif,
+    Apng,
+    Webm,
+}
+
+impl RecordingFormat {
+    pub const ALL: [RecordingFormat; 3] = [RecordingFormat::Gif, RecordingFormat::Apng, RecordingFormat::Webm];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "GIF",
+            RecordingFormat::Apng => "Animated PNG",
+            RecordingFormat::Webm => "WebM Video",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Apng => "png",
+            RecordingFormat::Webm => "webm",
+        }
+    }
+}
+
+/// Part of the frame to keep, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportOptions {
+    pub format: RecordingFormat,
+    pub fps: u32,
+    /// Trim everything before this point
+    pub start: Duration,
+    /// Trim everything after this point, if set
+    pub end: Option<Duration>,
+    pub region: Option<Region>,
+}
+
+/// A frame ready for encoding and how long it stays on screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedImage {
+    pub image: RgbaImage,
+    pub duration: Duration,
+}
+
+/// Trim, resample to `options.fps` and crop the recording
+///
+/// Each tick shows the latest frame recorded at or before it. Consecutive
+/// ticks showing the same frame become one frame lasting that many ticks.
+/// Frames are read from disk as the iterator gets to them. The encoders need
+/// every frame to be the same size, so frames of another size, such as after
+/// a recorded window was resized, are scaled and padded to the first one's.
+pub fn prepare_frames<'a>(
+    recording: &'a Recording,
+    options: &ExportOptions,
+) -> impl ExactSizeIterator<Item = anyhow::Result<TimedImage>> + 'a {
+    let tick = Duration::from_secs(1) / options.fps.max(1);
+    let region = options.region;
+    let runs = frame_runs(recording, options);
+    let size = runs.first().map(|&(index, _)| {
+        let frame = recording.frames[index];
+        match region {
+            Some(region) => {
+                let (_, _, width, height) = crop_rect(frame.width, frame.height, region);
+                (width, height)
+            }
+            None => (frame.width, frame.height),
+        }
+    });
+    runs.into_iter().map(move |(index, count)| {
+        let image = recording.image(index)?;
+        let image = match region {
+            Some(region) => crop(&image, region),
+            None => image,
+        };
+        let image = match size {
+            Some(size) => fit(image, size),
+            None => image,
+        };
+        Ok(TimedImage { image, duration: tick * count })
+    })
+}
+
+/// (index of the recorded frame, number of ticks it shows for) for each
+/// exported frame
+fn frame_runs(recording: &Recording, options: &ExportOptions) -> Vec<(usize, u32)> {
+    let end = options.end.unwrap_or(recording.length).min(recording.length);
+    if recording.is_empty() || options.fps == 0 || end <= options.start {
+        return Vec::new();
+    }
+
+    let tick = Duration::from_secs(1) / options.fps;
+    let ticks = (end - options.start).as_nanos().div_ceil(tick.as_nanos()).max(1) as u32;
+
+    let mut runs: Vec<(usize, u32)> = Vec::new();
+    for i in 0..ticks {
+        let time = options.start + tick * i;
+        let index = recording.frames.partition_point(|frame| frame.time <= time).saturating_sub(1);
+        match runs.last_mut() {
+            Some((last, count)) if *last == index => *count += 1,
+            _ => runs.push((index, 1)),
+        }
+    }
+    runs
+}
+
+/// `region` clamped to a frame of the given size, as (x, y, width, height)
+fn crop_rect(width: u32, height: u32, region: Region) -> (u32, u32, u32, u32) {
+    let x = region.x.min(width.saturating_sub(1));
+    let y = region.y.min(height.saturating_sub(1));
+    (x, y, region.width.clamp(1, width - x), region.height.clamp(1, height - y))
+}
+
+/// Cut `region` out of `image`, clamped to the image
+fn crop(image: &RgbaImage, region: Region) -> RgbaImage {
+    let (x, y, width, height) = crop_rect(image.width(), image.height(), region);
+    image::imageops::crop_imm(image, x, y, width, height).to_image()
+}
+
+/// Scale `image` to fit in `width` × `height`, keeping its aspect ratio, and
+/// center it on black
+fn fit(image: RgbaImage, (width, height): (u32, u32)) -> RgbaImage {
+    if image.dimensions() == (width, height) {
+        return image;
+    }
+    let scale = (width as f64 / image.width() as f64).min(height as f64 / image.height() as f64);
+    let scaled_width = ((image.width() as f64 * scale).round() as u32).clamp(1, width);
+    let scaled_height = ((image.height() as f64 * scale).round() as u32).clamp(1, height);
+    let scaled = image::imageops::resize(&image, scaled_width, scaled_height, FilterType::Triangle);
+
+    let mut fitted = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    let x = (width - scaled_width) / 2;
+    let y = (height - scaled_height) / 2;
+    image::imageops::replace(&mut fitted, &scaled, x as i64, y as i64);
+    fitted
+}
+
+/// Encode frames as a looping GIF
+pub fn encode_gif(frames: impl Iterator<Item = anyhow::Result<TimedImage>>, writer: impl Write) -> anyhow::Result<()> {
+    let mut encoder = GifEncoder::new_with_speed(writer, 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    for frame in frames {
+        let frame = frame?;
+        encoder.encode_frame(image::Frame::from_parts(
+            frame.image,
+            0,
+            0,
+            Delay::from_saturating_duration(frame.duration),
+        ))?;
+    }
+    Ok(())
+}
+
+/// Encode frames as a looping animated PNG
+pub fn encode_apng(
+    mut frames: impl ExactSizeIterator<Item = anyhow::Result<TimedImage>>,
+    writer: impl Write,
+) -> anyhow::Result<()> {
+    let count = frames.len() as u32;
+    let Some(first) = frames.next().transpose()? else {
+        anyhow::bail!("No frames to encode");
+    };
+    let (width, height) = first.image.dimensions();
+
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(count, 0)?;
+
+    let mut writer = encoder.write_header()?;
+    for frame in std::iter::once(Ok(first)).chain(frames) {
+        let frame = frame?;
+        let millis = frame.duration.as_millis().min(u16::MAX as u128) as u16;
+        writer.set_frame_delay(millis, 1000)?;
+        writer.write_image_data(frame.image.as_raw())?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Export the recording to `path` in the format chosen in `options`
+pub fn export_recording(recording: &Recording, options: &ExportOptions, path: &Path) -> anyhow::Result<()> {
+    let frames = prepare_frames(recording, options);
+    if frames.len() == 0 {
+        anyhow::bail!("Nothing left to export after trimming");
+    }
+
+    match options.format {
+        RecordingFormat::Gif => encode_gif(frames, std::io::BufWriter::new(std::fs::File::create(path)?)),
+        RecordingFormat::Apng => encode_apng(frames, std::io::BufWriter::new(std::fs::File::create(path)?)),
+        RecordingFormat::Webm => super::screencast::encode_webm(frames, options.fps, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::AnimationDecoder;
+
+    fn solid(value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(8, 6, Rgba([value, 255 - value, 0, 255]))
+    }
+
+    /// Frames of different colors at the given times, in milliseconds
+    fn recording(times: &[u64], length: u64) -> Recording {
+        let mut recording = Recording::new(&std::env::temp_dir()).unwrap();
+        for (i, &ms) in times.iter().enumerate() {
+            recording.push(&solid(i as u8 * 40), Duration::from_millis(ms)).unwrap();
+        }
+        recording.length = Duration::from_millis(length);
+        recording
+    }
+
+    fn prepare(recording: &Recording, options: &ExportOptions) -> Vec<TimedImage> {
+        prepare_frames(recording, options).collect::<anyhow::Result<_>>().unwrap()
+    }
+
+    fn options(fps: u32) -> ExportOptions {
+        ExportOptions { format: RecordingFormat::Gif, fps, start: Duration::ZERO, end: None, region: None }
+    }
+
+    #[test]
+    fn resamples_and_merges_repeated_ticks() {
+        let recording = recording(&[0, 250, 300], 1000);
+        let frames = prepare(&recording, &options(10));
+
+        // Ticks at 0, 100, 200 show frame 0; 300 and on show frame 2; frame 1 falls between ticks
+        let durations: Vec<Duration> = frames.iter().map(|frame| frame.duration).collect();
+        assert_eq!(durations, [Duration::from_millis(300), Duration::from_millis(700)]);
+        assert_eq!(frames[0].image, solid(0));
+        assert_eq!(frames[1].image, solid(80));
+    }
+
+    #[test]
+    fn trims_to_the_chosen_range() {
+        let recording = recording(&[0, 500, 1000], 1500);
+        let options = ExportOptions { start: Duration::from_millis(600), end: Some(Duration::from_millis(1200)), ..options(10) };
+        let frames = prepare(&recording, &options);
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].image, solid(40));
+        assert_eq!(frames[0].duration, Duration::from_millis(400));
+        assert_eq!(frames[1].image, solid(80));
+        assert_eq!(frames[1].duration, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn crops_to_the_region() {
+        let recording = recording(&[0], 100);
+        let region = Region { x: 2, y: 1, width: 4, height: 20 };
+        let frames = prepare(&recording, &ExportOptions { region: Some(region), ..options(10) });
+
+        assert_eq!(frames[0].image.dimensions(), (4, 5));
+    }
+
+    #[test]
+    fn fits_frames_to_the_first_size() {
+        // A recorded window that got narrower, then wider
+        let mut recording = Recording::new(&std::env::temp_dir()).unwrap();
+        recording.push(&solid(0), Duration::ZERO).unwrap();
+        recording.push(&RgbaImage::from_pixel(4, 6, Rgba([9, 9, 9, 255])), Duration::from_millis(100)).unwrap();
+        recording.push(&RgbaImage::from_pixel(16, 6, Rgba([7, 7, 7, 255])), Duration::from_millis(200)).unwrap();
+        recording.length = Duration::from_millis(300);
+
+        let frames = prepare(&recording, &options(10));
+        assert!(frames.iter().all(|frame| frame.image.dimensions() == (8, 6)));
+        // Centered, with black either side
+        assert_eq!(*frames[1].image.get_pixel(4, 3), Rgba([9, 9, 9, 255]));
+        assert_eq!(*frames[1].image.get_pixel(0, 3), Rgba([0, 0, 0, 255]));
+
+        // Cropped frames match the first cropped frame, even where the crop is clamped
+        let region = Region { x: 2, y: 0, width: 5, height: 6 };
+        let frames = prepare(&recording, &ExportOptions { region: Some(region), ..options(10) });
+        assert!(frames.iter().all(|frame| frame.image.dimensions() == (5, 6)));
+
+        for format in RecordingFormat::ALL.into_iter().filter(|format| *format != RecordingFormat::Webm) {
+            let mut bytes = Vec::new();
+            let frames = prepare_frames(&recording, &ExportOptions { format, ..options(10) });
+            match format {
+                RecordingFormat::Gif => encode_gif(frames, &mut bytes).unwrap(),
+                _ => encode_apng(frames, &mut bytes).unwrap(),
+            }
+        }
+    }
+
+    #[test]
+    fn empty_range_gives_no_frames() {
+        let recording = recording(&[0], 1000);
+        let options = ExportOptions { start: Duration::from_millis(1000), ..options(10) };
+
+        assert!(prepare(&recording, &options).is_empty());
+        assert!(prepare(&self::recording(&[], 1000), &self::options(10)).is_empty());
+    }
+
+    #[test]
+    fn frames_live_in_an_unlinked_file() {
+        let dir = std::env::temp_dir().join(format!("gnome-shot-recording-{}", std::process::id()));
+        let mut recording = Recording::new(&dir).unwrap();
+        recording.push(&solid(0), Duration::ZERO).unwrap();
+        recording.push(&RgbaImage::from_pixel(3, 2, Rgba([1, 2, 3, 4])), Duration::from_millis(100)).unwrap();
+        recording.length = Duration::from_millis(200);
+
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        let frames = prepare(&recording, &options(10));
+        assert_eq!(frames[0].image, solid(0));
+        assert_eq!(*frames[1].image.get_pixel(4, 3), Rgba([1, 2, 3, 4]));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn gif_round_trips() {
+        let recording = recording(&[0, 500], 1000);
+        let mut bytes = Vec::new();
+        encode_gif(prepare_frames(&recording, &options(10)), &mut bytes).unwrap();
+
+        let decoder = image::codecs::gif::GifDecoder::new(std::io::Cursor::new(bytes)).unwrap();
+        let decoded = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].delay(), Delay::from_saturating_duration(Duration::from_millis(500)));
+        assert_eq!(decoded[1].buffer().dimensions(), (8, 6));
+    }
+
+    #[test]
+    fn apng_round_trips() {
+        let recording = recording(&[0, 200, 400], 600);
+        let mut bytes = Vec::new();
+        encode_apng(prepare_frames(&recording, &options(5)), &mut bytes).unwrap();
+
+        let decoder = image::codecs::png::PngDecoder::new(std::io::Cursor::new(bytes)).unwrap();
+        let decoded = decoder.apng().unwrap().into_frames().collect_frames().unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[2].buffer(), &solid(80));
+    }
+}
@@ -0,0 +1,164 @@
+//! Remember where a region capture was taken so the same area can be captured again.
+//!
+//! The portal only hands back the cropped image, so areas come from GNOME
+//! Shot's own region selector, which knows where the selection is. Areas are
+//! kept in logical pixels relative to the top left of the desktop, which stay
+//! the same when a monitor's scale factor changes.
+
+use gtk::gdk;
+use gtk::prelude::*;
+use super::{CaptureMode, Screenshot};
+
+/// A rectangle of the desktop in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Area {
+    /// Read an area written by `to_setting`, such as `10,20,300,200`
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split(',').map(|part| part.trim().parse::<f64>().ok());
+        let area = Area {
+            x: parts.next()??,
+            y: parts.next()??,
+            width: parts.next()??,
+            height: parts.next()??,
+        };
+        (parts.next().is_none() && area.width > 0.0 && area.height > 0.0).then_some(area)
+    }
+
+    pub fn to_setting(&self) -> String {
+        format!("{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+
+    /// The area in an image of the desktop with `scale` image pixels per
+    /// logical pixel, clamped to an image of the given size
+    pub fn to_pixels(&self, scale: f64, image_width: u32, image_height: u32) -> Option<(u32, u32, u32, u32)> {
+        let left = (self.x * scale).round().max(0.0) as u32;
+        let top = (self.y * scale).round().max(0.0) as u32;
+        let right = (((self.x + self.width) * scale).round().max(0.0) as u32).min(image_width);
+        let bottom = (((self.y + self.height) * scale).round().max(0.0) as u32).min(image_height);
+        (right > left && bottom > top).then(|| (left, top, right - left, bottom - top))
+    }
+
+    pub fn from_pixels(x: u32, y: u32, width: u32, height: u32, scale: f64) -> Self {
+        Area {
+            x: x as f64 / scale,
+            y: y as f64 / scale,
+            width: width as f64 / scale,
+            height: height as f64 / scale,
+        }
+    }
+}
+
+/// All connected monitors, in the display's order
+pub fn monitors() -> Vec<gdk::Monitor> {
+    gdk::Display::default()
+        .map(|display| display.monitors().iter::<gdk::Monitor>().flatten().collect())
+        .unwrap_or_default()
+}
+
+/// Logical bounds (left, top, width, height) of the whole desktop, from the union of all monitors
+fn desktop_bounds() -> Option<(i32, i32, i32, i32)> {
+    let (mut left, mut top, mut right, mut bottom) = (i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+    for monitor in monitors() {
+        let geometry = monitor.geometry();
+        left = left.min(geometry.x());
+        top = top.min(geometry.y());
+        right = right.max(geometry.x() + geometry.width());
+        bottom = bottom.max(geometry.y() + geometry.height());
+    }
+    (right > left && bottom > top).then(|| (left, top, right - left, bottom - top))
+}
+
+/// Image pixels per logical pixel in a fullscreen capture of the given width
+pub fn desktop_scale(image_width: i32) -> f64 {
+    match desktop_bounds() {
+        Some((_, _, width, _)) => image_width as f64 / width as f64,
+        None => display_scale_factor(),
+    }
+}
+
+/// Image pixels per logical pixel for a capture whose monitor isn't known,
+/// such as a region picked in the portal dialog
+///
+/// GNOME Shell captures an area at the largest scale of the monitors under
+/// it, so this is exact with a single monitor or with matching scales.
+pub fn display_scale_factor() -> f64 {
+    monitors().iter().map(|monitor| monitor.scale()).fold(1.0, f64::max)
+}
+
+/// A monitor's area of the desktop
+pub fn monitor_area(monitor: &gdk::Monitor) -> Area {
+    let (left, top) = desktop_bounds().map_or((0, 0), |(left, top, _, _)| (left, top));
+    let geometry = monitor.geometry();
+    Area {
+        x: (geometry.x() - left) as f64,
+        y: (geometry.y() - top) as f64,
+        width: geometry.width() as f64,
+        height: geometry.height() as f64,
+    }
+}
+
+/// Name for a monitor in menus, such as "DELL U2720Q (DP-1)"
+pub fn monitor_name(monitor: &gdk::Monitor) -> String {
+    let connector = monitor.connector().map(|c| c.to_string()).unwrap_or_default();
+    match monitor.model().or_else(|| monitor.description()) {
+        Some(model) if !connector.is_empty() => format!("{} ({})", model, connector),
+        Some(model) => model.to_string(),
+        None => connector,
+    }
+}
+
+/// Crop a fullscreen capture to `area`
+///
+/// The capture's file is left as it is, holding the whole desktop; the
+/// `raw_files` setting decides what happens to it once the crop is saved.
+pub fn crop_to_area(fullscreen: Screenshot, area: Area, capture_mode: CaptureMode) -> anyhow::Result<Screenshot> {
+    let scale_factor = fullscreen.scale_factor;
+    let (x, y, width, height) = area
+        .to_pixels(scale_factor, fullscreen.width() as u32, fullscreen.height() as u32)
+        .ok_or_else(|| anyhow::anyhow!("The area is off screen"))?;
+    let pixbuf = fullscreen
+        .pixbuf
+        .new_subpixbuf(x as i32, y as i32, width as i32, height as i32)
+        .copy()
+        .ok_or_else(|| anyhow::anyhow!("Failed to copy the area"))?;
+
+    Ok(Screenshot {
+        pixbuf,
+        source_path: fullscreen.source_path,
+        capture_mode,
+        window: None,
+        scale_factor,
+        preset: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_maps_between_scales() {
+        // Found at 2x, repeated at 1x
+        let area = Area::from_pixels(200, 100, 640, 480, 2.0);
+        assert_eq!(area, Area { x: 100.0, y: 50.0, width: 320.0, height: 240.0 });
+        assert_eq!(area.to_pixels(1.0, 1920, 1080), Some((100, 50, 320, 240)));
+        // Clamped to the screen, and nothing left if entirely off it
+        assert_eq!(area.to_pixels(1.0, 300, 200), Some((100, 50, 200, 150)));
+        assert_eq!(area.to_pixels(1.0, 80, 40), None);
+    }
+
+    #[test]
+    fn area_round_trips_through_settings() {
+        let area = Area { x: 10.5, y: 20.0, width: 300.0, height: 200.25 };
+        assert_eq!(Area::parse(&area.to_setting()), Some(area));
+        assert_eq!(Area::parse("1,2,3"), None);
+        assert_eq!(Area::parse("1,2,0,4"), None);
+    }
+}
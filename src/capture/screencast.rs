@@ -0,0 +1,264 @@
+//! Screen recording through the XDG ScreenCast portal.
+//!
+//! The portal asks the user for a monitor or window and hands back a PipeWire
+//! stream, which a GStreamer pipeline turns into RGBA frames that are written
+//! straight to the recording's temporary file. The same GStreamer setup
+//! encodes WebM on export.
+
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ashpd::desktop::screencast::{CursorMode, Screencast, SourceType};
+use ashpd::desktop::PersistMode;
+use futures::channel::oneshot;
+use gstreamer as gst;
+use gstreamer::prelude::*;
+use gstreamer_app as gst_app;
+use gstreamer_video as gst_video;
+use gstreamer_video::prelude::*;
+use image::RgbaImage;
+
+use super::recording::{Recording, TimedImage};
+
+/// Frames past this rate are dropped while recording, as no export uses more
+const MAX_RECORDING_FPS: u32 = 30;
+
+/// A PipeWire stream shared through the portal
+///
+/// The portal session stays open until this is dropped.
+pub struct ScreencastStream {
+    fd: OwnedFd,
+    node_id: u32,
+    _close: oneshot::Sender<()>,
+}
+
+/// Ask the user what to record and open the stream (run on the Tokio runtime)
+pub async fn open_screencast() -> anyhow::Result<ScreencastStream> {
+    let proxy = Screencast::new().await?;
+    let session = proxy.create_session().await?;
+
+    proxy
+        .select_sources(
+            &session,
+            CursorMode::Embedded,
+            SourceType::Monitor | SourceType::Window,
+            false,
+            None,
+            PersistMode::DoNot,
+        )
+        .await?;
+
+    let response = proxy.start(&session, None).await?.response()?;
+    let stream = response
+        .streams()
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("Nothing was selected to record"))?;
+    let node_id = stream.pipe_wire_node_id();
+    let fd = proxy.open_pipe_wire_remote(&session).await?;
+
+    // Hold the session until the stream is dropped, then close it
+    let (close, closed) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let _ = closed.await;
+        if let Err(e) = session.close().await {
+            eprintln!("Failed to close screencast session: {}", e);
+        }
+        drop(proxy);
+    });
+
+    Ok(ScreencastStream { fd, node_id, _close: close })
+}
+
+/// Collects frames from a screencast stream until stopped
+pub struct Recorder {
+    pipeline: gst::Pipeline,
+    /// Taken out when stopped
+    recording: Arc<Mutex<Option<Recording>>>,
+    interrupted: Arc<AtomicBool>,
+    started: Instant,
+    _stream: ScreencastStream,
+}
+
+impl Recorder {
+    pub fn start(stream: ScreencastStream) -> anyhow::Result<Self> {
+        gst::init()?;
+
+        let description = format!(
+            "pipewiresrc fd={} path={} always-copy=true ! videorate drop-only=true max-rate={} ! \
+             videoconvert ! video/x-raw,format=RGBA ! appsink name=sink sync=false max-buffers=2 drop=true",
+            stream.fd.as_raw_fd(),
+            stream.node_id,
+            MAX_RECORDING_FPS
+        );
+        let pipeline = gst::parse::launch(&description)?
+            .downcast::<gst::Pipeline>()
+            .map_err(|_| anyhow::anyhow!("Recording pipeline is not a pipeline"))?;
+        let sink = pipeline
+            .by_name("sink")
+            .and_downcast::<gst_app::AppSink>()
+            .ok_or_else(|| anyhow::anyhow!("Recording pipeline has no sink"))?;
+
+        // Frames go to disk rather than memory, in our cache as /tmp may be in memory too
+        let recording = Recording::new(&gtk::glib::user_cache_dir().join("gnome-shot"))?;
+        let recording = Arc::new(Mutex::new(Some(recording)));
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let started = Instant::now();
+
+        let recording_for_sink = recording.clone();
+        let interrupted_for_sink = interrupted.clone();
+        sink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |sink| {
+                    let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    let time = started.elapsed();
+                    if interrupted_for_sink.load(Ordering::Relaxed) {
+                        return Ok(gst::FlowSuccess::Ok);
+                    }
+
+                    let image = sample_to_image(&sample).ok_or(gst::FlowError::Error)?;
+                    let mut recording = recording_for_sink.lock().unwrap();
+                    let Some(recording) = recording.as_mut() else { return Err(gst::FlowError::Eos) };
+                    if let Err(e) = recording.push(&image, time) {
+                        // Most likely the disk is full; keep what was recorded so far
+                        recording.interrupted = Some(format!("Couldn't store more frames ({})", e));
+                        interrupted_for_sink.store(true, Ordering::Relaxed);
+                    }
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        );
+
+        pipeline.set_state(gst::State::Playing)?;
+
+        Ok(Self { pipeline, recording, interrupted, started, _stream: stream })
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Whether frames could no longer be stored and aren't being collected anymore
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Relaxed)
+    }
+
+    /// Whether the stream ended on its own, for example because the recorded window closed
+    pub fn has_ended(&self) -> bool {
+        let Some(bus) = self.pipeline.bus() else { return true };
+        match bus.pop_filtered(&[gst::MessageType::Eos, gst::MessageType::Error]) {
+            Some(message) => {
+                if let gst::MessageView::Error(err) = message.view() {
+                    eprintln!("Recording failed: {}", err.error());
+                    if let Some(recording) = self.recording.lock().unwrap().as_mut() {
+                        recording.interrupted = Some(format!("The stream failed ({})", err.error()));
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn stop(self) -> Recording {
+        let length = self.elapsed();
+        if let Err(e) = self.pipeline.set_state(gst::State::Null) {
+            eprintln!("Failed to stop recording: {}", e);
+        }
+        let mut recording = self.recording.lock().unwrap().take().expect("a recorder is only stopped once");
+        recording.length = length;
+        recording
+    }
+}
+
+/// Copy an RGBA sample into an image, dropping row padding
+fn sample_to_image(sample: &gst::Sample) -> Option<RgbaImage> {
+    let buffer = sample.buffer()?;
+    let info = gst_video::VideoInfo::from_caps(sample.caps()?).ok()?;
+    let frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, &info).ok()?;
+
+    let (width, height) = (frame.width(), frame.height());
+    let stride = frame.plane_stride()[0] as usize;
+    let data = frame.plane_data(0).ok()?;
+    let row_len = width as usize * 4;
+
+    let mut pixels = Vec::with_capacity(row_len * height as usize);
+    for row in data.chunks(stride).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+    RgbaImage::from_raw(width, height, pixels)
+}
+
+/// Encode frames as a VP8 WebM video at `path`
+pub fn encode_webm(
+    mut frames: impl Iterator<Item = anyhow::Result<TimedImage>>,
+    fps: u32,
+    path: &Path,
+) -> anyhow::Result<()> {
+    gst::init()?;
+
+    let Some(first) = frames.next().transpose()? else {
+        anyhow::bail!("No frames to encode");
+    };
+    let (width, height) = first.image.dimensions();
+
+    let pipeline = gst::parse::launch(
+        "appsrc name=src format=time ! videoconvert ! vp8enc deadline=1 ! webmmux ! filesink name=sink",
+    )?
+    .downcast::<gst::Pipeline>()
+    .map_err(|_| anyhow::anyhow!("Encoding pipeline is not a pipeline"))?;
+    let src = pipeline
+        .by_name("src")
+        .and_downcast::<gst_app::AppSrc>()
+        .ok_or_else(|| anyhow::anyhow!("Encoding pipeline has no source"))?;
+    let sink = pipeline
+        .by_name("sink")
+        .ok_or_else(|| anyhow::anyhow!("Encoding pipeline has no sink"))?;
+    sink.set_property("location", path.to_string_lossy().as_ref());
+
+    let info = gst_video::VideoInfo::builder(gst_video::VideoFormat::Rgba, width, height)
+        .fps(gst::Fraction::new(fps as i32, 1))
+        .build()?;
+    src.set_caps(Some(&info.to_caps()?));
+
+    pipeline.set_state(gst::State::Playing)?;
+
+    let mut pts = gst::ClockTime::ZERO;
+    for frame in std::iter::once(Ok(first)).chain(frames) {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(e) => {
+                let _ = pipeline.set_state(gst::State::Null);
+                return Err(e);
+            }
+        };
+        let duration = gst::ClockTime::from_nseconds(frame.duration.as_nanos() as u64);
+        let mut buffer = gst::Buffer::from_mut_slice(frame.image.into_raw());
+        {
+            let buffer = buffer.get_mut().expect("new buffer is writable");
+            buffer.set_pts(pts);
+            buffer.set_duration(duration);
+        }
+        src.push_buffer(buffer)?;
+        pts += duration;
+    }
+    src.end_of_stream()?;
+
+    let result = wait_for_end(&pipeline);
+    pipeline.set_state(gst::State::Null)?;
+    result
+}
+
+fn wait_for_end(pipeline: &gst::Pipeline) -> anyhow::Result<()> {
+    let bus = pipeline.bus().ok_or_else(|| anyhow::anyhow!("Pipeline has no bus"))?;
+    for message in bus.iter_timed(gst::ClockTime::NONE) {
+        match message.view() {
+            gst::MessageView::Eos(..) => return Ok(()),
+            gst::MessageView::Error(err) => anyhow::bail!("Encoding failed: {}", err.error()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,336 @@
+//! Geometry of the built-in region selector, and the capture presets that constrain it.
+//!
+//! Selections are kept in pixels of the fullscreen capture, so their edges
+//! always fall on whole pixels however the overlay is zoomed.
+
+/// Limit on the shape of a selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constraint {
+    Free,
+    /// Width to height ratio
+    Aspect(u32, u32),
+    /// Exact size in pixels
+    Size(u32, u32),
+}
+
+impl Constraint {
+    /// Read `free`, a size such as `1280x800` or a ratio such as `16:9`
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("free") {
+            return Some(Constraint::Free);
+        }
+        let number = |part: &str| part.trim().parse::<u32>().ok().filter(|&n| n > 0);
+        if let Some((w, h)) = value.split_once(['x', 'X', '×']) {
+            return Some(Constraint::Size(number(w)?, number(h)?));
+        }
+        let (w, h) = value.split_once(':')?;
+        Some(Constraint::Aspect(number(w)?, number(h)?))
+    }
+
+    /// The form `parse` reads
+    pub fn to_setting(&self) -> String {
+        match self {
+            Constraint::Free => "free".to_string(),
+            Constraint::Aspect(w, h) => format!("{}:{}", w, h),
+            Constraint::Size(w, h) => format!("{}x{}", w, h),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Constraint::Free => "Free".to_string(),
+            Constraint::Aspect(w, h) => format!("{}:{}", w, h),
+            Constraint::Size(w, h) => format!("{} × {}", w, h),
+        }
+    }
+}
+
+/// A named constraint to capture with, such as "App Store" for 1280×800
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturePreset {
+    pub name: String,
+    pub constraint: Constraint,
+}
+
+impl CapturePreset {
+    /// Presets that come with GNOME Shot, named by their constraint
+    pub fn built_in() -> Vec<CapturePreset> {
+        [
+            Constraint::Free,
+            Constraint::Aspect(16, 9),
+            Constraint::Aspect(4, 3),
+            Constraint::Aspect(1, 1),
+            Constraint::Size(1280, 800),
+            Constraint::Size(1280, 720),
+            Constraint::Size(1920, 1080),
+        ]
+        .into_iter()
+        .map(|constraint| CapturePreset { name: constraint.to_setting(), constraint })
+        .collect()
+    }
+
+    /// Read presets written as `name:constraint`, separated by commas,
+    /// such as `App Store:1280x800, Docs:16:9`
+    pub fn parse_list(value: &str) -> Vec<CapturePreset> {
+        value
+            .split(',')
+            .filter_map(|entry| {
+                let (name, constraint) = entry.split_once(':')?;
+                let name = name.trim();
+                if name.is_empty() {
+                    return None;
+                }
+                Some(CapturePreset { name: name.to_string(), constraint: Constraint::parse(constraint)? })
+            })
+            .collect()
+    }
+
+    /// The preset called `query` among `presets`, or one made from `query`
+    /// itself if it's a constraint such as `1024x768`
+    pub fn find(presets: &[CapturePreset], query: &str) -> Option<CapturePreset> {
+        let query = query.trim();
+        presets
+            .iter()
+            .find(|preset| preset.name.eq_ignore_ascii_case(query))
+            .cloned()
+            .or_else(|| Constraint::parse(query).map(|constraint| CapturePreset { name: query.to_string(), constraint }))
+    }
+
+    /// Name for menus, with the constraint unless the name already is one
+    pub fn label(&self) -> String {
+        if Constraint::parse(&self.name) == Some(self.constraint) {
+            self.constraint.label()
+        } else {
+            format!("{} ({})", self.name, self.constraint.label())
+        }
+    }
+}
+
+/// A rectangle of the capture in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Selection {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Selection {
+    /// The selection made by dragging from `start` to `end`, given in
+    /// (fractional) pixels of a capture of size `bounds`
+    ///
+    /// Fixed sizes are centered on `end` so the box follows the pointer.
+    pub fn from_drag(start: (f64, f64), end: (f64, f64), constraint: Constraint, bounds: (u32, u32)) -> Self {
+        let (max_x, max_y) = (bounds.0 as f64, bounds.1 as f64);
+        let start = (start.0.clamp(0.0, max_x), start.1.clamp(0.0, max_y));
+        let end = (end.0.clamp(0.0, max_x), end.1.clamp(0.0, max_y));
+
+        match constraint {
+            Constraint::Free => {
+                let left = start.0.min(end.0).round() as u32;
+                let top = start.1.min(end.1).round() as u32;
+                let right = start.0.max(end.0).round() as u32;
+                let bottom = start.1.max(end.1).round() as u32;
+                Selection { x: left, y: top, width: right - left, height: bottom - top }
+            }
+            Constraint::Aspect(a, b) => {
+                let (a, b) = (a.max(1) as f64, b.max(1) as f64);
+                let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+                // Room from the start point in the direction of the drag
+                let room_x = if dx >= 0.0 { max_x - start.0 } else { start.0 };
+                let room_y = if dy >= 0.0 { max_y - start.1 } else { start.1 };
+                let width = dx.abs().max(dy.abs() * a / b).min(room_x).min(room_y * a / b).floor();
+                let height = (width * b / a).round();
+                let x = if dx >= 0.0 { start.0 } else { start.0 - width };
+                let y = if dy >= 0.0 { start.1 } else { start.1 - height };
+                Selection { x: x.round() as u32, y: y.round() as u32, width: width as u32, height: height as u32 }
+                    .clamped(bounds)
+            }
+            Constraint::Size(width, height) => {
+                let x = end.0 - width as f64 / 2.0;
+                let y = end.1 - height as f64 / 2.0;
+                Selection { x: x.max(0.0).round() as u32, y: y.max(0.0).round() as u32, width, height }.clamped(bounds)
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Move by (dx, dy) pixels, staying inside the capture
+    pub fn nudge(&self, dx: i32, dy: i32, bounds: (u32, u32)) -> Self {
+        let x = (self.x as i64 + dx as i64).clamp(0, bounds.0.saturating_sub(self.width) as i64);
+        let y = (self.y as i64 + dy as i64).clamp(0, bounds.1.saturating_sub(self.height) as i64);
+        Selection { x: x as u32, y: y as u32, ..*self }
+    }
+
+    /// Grow or shrink the right and bottom edges by (dw, dh) pixels; an
+    /// aspect ratio follows the width when it changes, fixed sizes don't change
+    pub fn resize(&self, dw: i32, dh: i32, constraint: Constraint, bounds: (u32, u32)) -> Self {
+        let max_width = bounds.0.saturating_sub(self.x);
+        let max_height = bounds.1.saturating_sub(self.y);
+        let grow = |size: u32, delta: i32, max: u32| (size as i64 + delta as i64).clamp(1, max.max(1) as i64) as u32;
+
+        match constraint {
+            Constraint::Free => Selection {
+                width: grow(self.width, dw, max_width),
+                height: grow(self.height, dh, max_height),
+                ..*self
+            },
+            Constraint::Aspect(a, b) => {
+                let (a, b) = (a.max(1), b.max(1));
+                let width = if dw != 0 {
+                    grow(self.width, dw, max_width)
+                } else {
+                    // Step the height and let the width follow, at least one pixel
+                    let height = grow(self.height, dh, max_height) as f64;
+                    let width = (height * a as f64 / b as f64).round() as u32;
+                    if width == self.width { grow(self.width, dh.signum(), max_width) } else { width }
+                };
+                self.with_aspect(width, a, b, bounds)
+            }
+            Constraint::Size(..) => *self,
+        }
+    }
+
+    /// Reshape an existing selection to `constraint`, keeping its top left
+    /// corner, or its center for fixed sizes
+    pub fn constrain(&self, constraint: Constraint, bounds: (u32, u32)) -> Self {
+        match constraint {
+            Constraint::Free => *self,
+            Constraint::Aspect(a, b) => self.with_aspect(self.width.max(1), a.max(1), b.max(1), bounds),
+            Constraint::Size(width, height) => {
+                let center_x = self.x as f64 + self.width as f64 / 2.0;
+                let center_y = self.y as f64 + self.height as f64 / 2.0;
+                Selection::from_drag((center_x, center_y), (center_x, center_y), Constraint::Size(width, height), bounds)
+            }
+        }
+    }
+
+    /// Same top left corner with the given width, or less if the height wouldn't fit
+    fn with_aspect(&self, width: u32, a: u32, b: u32, bounds: (u32, u32)) -> Self {
+        let max_width = bounds.0.saturating_sub(self.x) as f64;
+        let max_height = bounds.1.saturating_sub(self.y) as f64;
+        let width = (width as f64).min(max_width).min(max_height * a as f64 / b as f64).floor();
+        let height = (width * b as f64 / a as f64).round();
+        Selection { width: width as u32, height: height as u32, ..*self }
+    }
+
+    /// Shifted, then cut down if still needed, to lie inside the capture
+    fn clamped(&self, bounds: (u32, u32)) -> Self {
+        let width = self.width.min(bounds.0);
+        let height = self.height.min(bounds.1);
+        Selection {
+            x: self.x.min(bounds.0 - width),
+            y: self.y.min(bounds.1 - height),
+            width,
+            height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: (u32, u32) = (1920, 1080);
+
+    #[test]
+    fn free_drag_in_any_direction() {
+        let forward = Selection::from_drag((10.2, 20.0), (110.6, 70.4), Constraint::Free, SCREEN);
+        assert_eq!(forward, Selection { x: 10, y: 20, width: 101, height: 50 });
+
+        let backward = Selection::from_drag((110.6, 70.4), (10.2, 20.0), Constraint::Free, SCREEN);
+        assert_eq!(backward, forward);
+
+        // Dragging off the edge stops at it
+        let clamped = Selection::from_drag((1900.0, 1000.0), (2500.0, -40.0), Constraint::Free, SCREEN);
+        assert_eq!(clamped, Selection { x: 1900, y: 0, width: 20, height: 1000 });
+    }
+
+    #[test]
+    fn aspect_follows_the_longer_side_and_fits_the_screen() {
+        let wide = Selection::from_drag((100.0, 100.0), (260.0, 110.0), Constraint::Aspect(16, 9), SCREEN);
+        assert_eq!(wide, Selection { x: 100, y: 100, width: 160, height: 90 });
+
+        let tall = Selection::from_drag((100.0, 100.0), (110.0, 190.0), Constraint::Aspect(16, 9), SCREEN);
+        assert_eq!(tall, Selection { x: 100, y: 100, width: 160, height: 90 });
+
+        let up_left = Selection::from_drag((500.0, 500.0), (400.0, 480.0), Constraint::Aspect(1, 1), SCREEN);
+        assert_eq!(up_left, Selection { x: 400, y: 400, width: 100, height: 100 });
+
+        // Only 80 pixels below the start, so the square can't be wider than that
+        let limited = Selection::from_drag((100.0, 1000.0), (600.0, 1080.0), Constraint::Aspect(1, 1), SCREEN);
+        assert_eq!(limited, Selection { x: 100, y: 1000, width: 80, height: 80 });
+    }
+
+    #[test]
+    fn fixed_size_follows_the_pointer() {
+        let centered = Selection::from_drag((0.0, 0.0), (960.0, 540.0), Constraint::Size(800, 600), SCREEN);
+        assert_eq!(centered, Selection { x: 560, y: 240, width: 800, height: 600 });
+
+        let corner = Selection::from_drag((0.0, 0.0), (1900.0, 10.0), Constraint::Size(800, 600), SCREEN);
+        assert_eq!(corner, Selection { x: 1120, y: 0, width: 800, height: 600 });
+
+        let too_big = Selection::from_drag((0.0, 0.0), (10.0, 10.0), Constraint::Size(2560, 1440), SCREEN);
+        assert_eq!(too_big, Selection { x: 0, y: 0, width: 1920, height: 1080 });
+    }
+
+    #[test]
+    fn keyboard_moves_and_resizes_by_single_pixels() {
+        let selection = Selection { x: 10, y: 10, width: 100, height: 50 };
+
+        assert_eq!(selection.nudge(-1, 0, SCREEN).x, 9);
+        assert_eq!(selection.nudge(-20, 5000, SCREEN), Selection { x: 0, y: 1030, ..selection });
+
+        let wider = selection.resize(1, 0, Constraint::Free, SCREEN);
+        assert_eq!((wider.width, wider.height), (101, 50));
+        let never_empty = selection.resize(0, -100, Constraint::Free, SCREEN);
+        assert_eq!(never_empty.height, 1);
+
+        let square = Selection { x: 10, y: 10, width: 100, height: 100 };
+        assert_eq!(square.resize(0, 1, Constraint::Aspect(1, 1), SCREEN), Selection { width: 101, height: 101, ..square });
+        assert_eq!(square.resize(5, 0, Constraint::Size(100, 100), SCREEN), square);
+    }
+
+    #[test]
+    fn choosing_a_preset_reshapes_the_selection() {
+        let selection = Selection { x: 100, y: 100, width: 320, height: 100 };
+
+        assert_eq!(selection.constrain(Constraint::Aspect(4, 3), SCREEN), Selection { width: 320, height: 240, ..selection });
+        assert_eq!(
+            selection.constrain(Constraint::Size(200, 200), SCREEN),
+            Selection { x: 160, y: 50, width: 200, height: 200 }
+        );
+        assert_eq!(Constraint::Size(1280, 720).label(), "1280 × 720");
+    }
+
+    #[test]
+    fn presets_are_read_from_settings() {
+        let presets = CapturePreset::parse_list("App Store:1280x800, Docs:16:9, broken:12, :4:3");
+        assert_eq!(
+            presets,
+            [
+                CapturePreset { name: "App Store".into(), constraint: Constraint::Size(1280, 800) },
+                CapturePreset { name: "Docs".into(), constraint: Constraint::Aspect(16, 9) },
+            ]
+        );
+        assert_eq!(presets[0].label(), "App Store (1280 × 800)");
+        assert_eq!(CapturePreset::built_in()[4].label(), "1280 × 800");
+
+        for constraint in [Constraint::Free, Constraint::Aspect(4, 3), Constraint::Size(800, 600)] {
+            assert_eq!(Constraint::parse(&constraint.to_setting()), Some(constraint));
+        }
+    }
+
+    #[test]
+    fn presets_are_found_by_name_or_constraint() {
+        let presets = CapturePreset::parse_list("App Store:1280x800");
+
+        assert_eq!(CapturePreset::find(&presets, "app store").map(|p| p.constraint), Some(Constraint::Size(1280, 800)));
+        assert_eq!(CapturePreset::find(&presets, "1024×768").map(|p| p.constraint), Some(Constraint::Size(1024, 768)));
+        assert_eq!(CapturePreset::find(&presets, "Banner"), None);
+    }
+}
@@ -0,0 +1,288 @@
+//! Stitch the frames of a scrolling capture into one tall image.
+//!
+//! Consecutive frames are matched row by row: each row is reduced to a hash,
+//! rows that stay put in every frame (sticky headers and footers) are set
+//! aside, and the scroll distance between two frames is the shift that lines
+//! up the most rows of the part in between.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use gtk::gdk_pixbuf::{Colorspace, Pixbuf};
+use gtk::glib;
+use image::RgbaImage;
+
+/// Rows that must line up for two frames to count as overlapping
+const MIN_OVERLAP: usize = 8;
+
+/// Share of overlapping rows that must match exactly
+const MIN_MATCH_RATIO: f64 = 0.9;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StitchError {
+    #[error("no frames to stitch")]
+    NoFrames,
+    #[error("frame {index} is {width}x{height}, but the first frame is {expected_width}x{expected_height}")]
+    SizeMismatch {
+        index: usize,
+        width: u32,
+        height: u32,
+        expected_width: u32,
+        expected_height: u32,
+    },
+    #[error("frame {index} doesn't overlap the frame before it")]
+    NoOverlap { index: usize },
+}
+
+/// Per-row summary of a frame
+struct Rows {
+    hashes: Vec<u64>,
+    /// Rows that aren't a single flat color, and so say something about position
+    informative: Vec<bool>,
+}
+
+impl Rows {
+    fn new(image: &RgbaImage) -> Self {
+        let row_len = image.width() as usize * 4;
+        let (hashes, informative) = image
+            .as_raw()
+            .chunks_exact(row_len.max(4))
+            .map(|row| {
+                let mut hasher = DefaultHasher::new();
+                row.hash(&mut hasher);
+                let flat = row.chunks_exact(4).all(|pixel| pixel == &row[..4]);
+                (hasher.finish(), !flat)
+            })
+            .unzip();
+        Self { hashes, informative }
+    }
+
+    fn len(&self) -> usize {
+        self.hashes.len()
+    }
+}
+
+/// Number of leading rows equal in both frames, at most a third of the height
+fn fixed_header(a: &Rows, b: &Rows) -> usize {
+    let limit = a.len() / 3;
+    a.hashes.iter().zip(&b.hashes).take(limit).take_while(|(x, y)| x == y).count()
+}
+
+/// Number of trailing rows equal in both frames, at most a third of the height
+fn fixed_footer(a: &Rows, b: &Rows) -> usize {
+    let limit = a.len() / 3;
+    a.hashes.iter().rev().zip(b.hashes.iter().rev()).take(limit).take_while(|(x, y)| x == y).count()
+}
+
+/// How far the content between `header` and `footer` scrolled from `a` to `b`
+///
+/// Returns `Some(0)` if nothing moved, and `None` if no shift lines the frames up.
+fn scroll_distance(a: &Rows, b: &Rows, header: usize, footer: usize) -> Option<usize> {
+    let body = header..a.len() - footer;
+    let (a_body, b_body) = (&a.hashes[body.clone()], &b.hashes[body.clone()]);
+    let a_informative = &a.informative[body];
+    let n = a_body.len();
+
+    if a_body == b_body {
+        return Some(0);
+    }
+
+    let mut best: Option<(f64, usize)> = None;
+    for distance in 1..n {
+        let overlap = n - distance;
+        if overlap < MIN_OVERLAP {
+            break;
+        }
+
+        let mut matches = 0;
+        let mut informative_matches = 0;
+        for i in 0..overlap {
+            if a_body[distance + i] == b_body[i] {
+                matches += 1;
+                if a_informative[distance + i] {
+                    informative_matches += 1;
+                }
+            }
+        }
+
+        // Blank rows match anywhere, so they alone don't prove a position
+        let ratio = matches as f64 / overlap as f64;
+        if informative_matches == 0 || ratio < MIN_MATCH_RATIO {
+            continue;
+        }
+        // Prefer the better match, and the larger overlap among equals
+        if best.is_none_or(|(best_ratio, _)| ratio > best_ratio) {
+            best = Some((ratio, distance));
+        }
+    }
+
+    best.map(|(_, distance)| distance)
+}
+
+/// Stitch frames captured top to bottom while scrolling into one image
+///
+/// All frames must be the same size. Frames that show nothing new, such as a
+/// repeated capture at the end of the page, are skipped.
+pub fn stitch(frames: &[RgbaImage]) -> Result<RgbaImage, StitchError> {
+    let first = frames.first().ok_or(StitchError::NoFrames)?;
+    let (width, height) = first.dimensions();
+
+    for (index, frame) in frames.iter().enumerate() {
+        if frame.dimensions() != (width, height) {
+            return Err(StitchError::SizeMismatch {
+                index,
+                width: frame.width(),
+                height: frame.height(),
+                expected_width: width,
+                expected_height: height,
+            });
+        }
+    }
+
+    let rows: Vec<Rows> = frames.iter().map(Rows::new).collect();
+
+    // Only rows that stay put across every pair are treated as sticky
+    let pairs = || rows.windows(2).map(|pair| (&pair[0], &pair[1]));
+    let header = pairs().map(|(a, b)| fixed_header(a, b)).min().unwrap_or(0);
+    let footer = pairs().map(|(a, b)| fixed_footer(a, b)).min().unwrap_or(0);
+
+    let mut distances = Vec::with_capacity(frames.len() - 1);
+    for (i, (a, b)) in pairs().enumerate() {
+        let distance = scroll_distance(a, b, header, footer).ok_or(StitchError::NoOverlap { index: i + 1 })?;
+        distances.push(distance);
+    }
+
+    // Everything above the footer of the first frame, the newly scrolled-in
+    // rows of each later frame, then the footer of the last
+    let height = height as usize;
+    let row_len = width as usize * 4;
+    let total = height + distances.iter().sum::<usize>();
+    let mut pixels = Vec::with_capacity(total * row_len);
+
+    let body_end = height - footer;
+    pixels.extend_from_slice(&first.as_raw()[..body_end * row_len]);
+    for (frame, &distance) in frames[1..].iter().zip(&distances) {
+        pixels.extend_from_slice(&frame.as_raw()[(body_end - distance) * row_len..body_end * row_len]);
+    }
+    let last = frames.last().unwrap_or(first);
+    pixels.extend_from_slice(&last.as_raw()[body_end * row_len..]);
+
+    Ok(RgbaImage::from_raw(width, total as u32, pixels).expect("stitched buffer matches its size"))
+}
+
+/// Copy a pixbuf into an RGBA image, adding alpha if needed
+pub fn pixbuf_to_image(pixbuf: &Pixbuf) -> RgbaImage {
+    let (width, height) = (pixbuf.width() as u32, pixbuf.height() as u32);
+    let channels = pixbuf.n_channels() as usize;
+    let stride = pixbuf.rowstride() as usize;
+    let bytes = pixbuf.read_pixel_bytes();
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let offset = y as usize * stride + x as usize * channels;
+        let pixel = &bytes[offset..offset + channels];
+        let alpha = if channels == 4 { pixel[3] } else { 255 };
+        image::Rgba([pixel[0], pixel[1], pixel[2], alpha])
+    })
+}
+
+pub fn image_to_pixbuf(image: RgbaImage) -> Pixbuf {
+    let (width, height) = (image.width() as i32, image.height() as i32);
+    let bytes = glib::Bytes::from_owned(image.into_raw());
+    Pixbuf::from_bytes(&bytes, Colorspace::Rgb, true, 8, width, height, width * 4)
+}
+
+/// Stitch captured frames, given top to bottom, into one pixbuf
+pub fn stitch_pixbufs(frames: &[Pixbuf]) -> Result<Pixbuf, StitchError> {
+    let images: Vec<RgbaImage> = frames.iter().map(pixbuf_to_image).collect();
+    stitch(&images).map(image_to_pixbuf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 40;
+
+    /// A long "page" whose rows are all different, with flat margins between blocks
+    fn page(height: u32) -> RgbaImage {
+        RgbaImage::from_fn(WIDTH, height, |x, y| {
+            if y % 50 < 10 {
+                image::Rgba([255, 255, 255, 255])
+            } else {
+                let v = (y.wrapping_mul(2654435761) ^ x.wrapping_mul(40503)) as u8;
+                image::Rgba([v, (y % 251) as u8, (y / 251) as u8, 255])
+            }
+        })
+    }
+
+    /// Rows `top..top + height` of `page`
+    fn view(page: &RgbaImage, top: u32, height: u32) -> RgbaImage {
+        image::imageops::crop_imm(page, 0, top, WIDTH, height).to_image()
+    }
+
+    /// Paint a sticky bar over the first (or last) `rows` rows of a frame
+    fn with_bar(mut frame: RgbaImage, rows: u32, at_bottom: bool) -> RgbaImage {
+        let height = frame.height();
+        for y in 0..rows {
+            let y = if at_bottom { height - 1 - y } else { y };
+            for x in 0..WIDTH {
+                frame.put_pixel(x, y, image::Rgba([(x * 6) as u8, 20, 90, 255]));
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn stitches_overlapping_frames_back_into_the_page() {
+        let page = page(600);
+        let frames = vec![view(&page, 0, 200), view(&page, 150, 200), view(&page, 260, 200), view(&page, 400, 200)];
+
+        assert_eq!(stitch(&frames).unwrap(), page);
+    }
+
+    #[test]
+    fn keeps_sticky_header_and_footer_once() {
+        let page = page(500);
+        let frame = |top| with_bar(with_bar(view(&page, top, 200), 20, false), 12, true);
+        let frames = vec![frame(0), frame(100), frame(200)];
+
+        let stitched = stitch(&frames).unwrap();
+
+        // Header from the first frame, the scrolled content, footer from the last
+        assert_eq!(stitched.height(), 200 + 100 + 100);
+        assert_eq!(view(&stitched, 0, 188), view(&frame(0), 0, 188));
+        let content_end = stitched.height() - 12;
+        assert_eq!(view(&stitched, content_end - 100, 100), view(&page, 388 - 100, 100));
+    }
+
+    #[test]
+    fn skips_frames_that_show_nothing_new() {
+        let page = page(400);
+        let frames = vec![view(&page, 0, 200), view(&page, 100, 200), view(&page, 100, 200)];
+
+        assert_eq!(stitch(&frames).unwrap(), view(&page, 0, 300));
+    }
+
+    #[test]
+    fn single_frame_is_returned_unchanged() {
+        let frame = view(&page(200), 0, 200);
+        assert_eq!(stitch(std::slice::from_ref(&frame)).unwrap(), frame);
+    }
+
+    #[test]
+    fn frames_without_overlap_are_an_error() {
+        let page = page(600);
+        let frames = vec![view(&page, 0, 200), view(&page, 300, 200)];
+
+        assert_eq!(stitch(&frames), Err(StitchError::NoOverlap { index: 1 }));
+    }
+
+    #[test]
+    fn frames_of_different_sizes_are_an_error() {
+        let page = page(600);
+        let frames = vec![view(&page, 0, 200), view(&page, 100, 180)];
+
+        assert!(matches!(stitch(&frames), Err(StitchError::SizeMismatch { index: 1, .. })));
+        assert_eq!(stitch(&[]), Err(StitchError::NoFrames));
+    }
+}
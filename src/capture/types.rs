@@ -6,6 +6,17 @@ pub enum CaptureMode {
     Region,
     Window,
     Fullscreen,
+    /// Several frames stitched into one long image
+    Scrolling,
+    /// Several images laid out side by side or in a grid
+    Combined,
+}
+
+/// The window a window capture came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowInfo {
+    pub title: String,
+    pub app_id: String,
 }
 
 #[derive(Debug)]
@@ -13,6 +24,12 @@ pub struct Screenshot {
     pub pixbuf: Pixbuf,
     pub source_path: Option<PathBuf>,
     pub capture_mode: CaptureMode,
+    /// Set for window captures when the window is known
+    pub window: Option<WindowInfo>,
+    /// Image pixels per logical screen pixel, 2.0 for a capture from a 2x display
+    pub scale_factor: f64,
+    /// Capture preset the region was selected with, if any
+    pub preset: Option<super::CapturePreset>,
 }
 
 impl Screenshot {
@@ -0,0 +1,236 @@
+//! Capture a single window through the GNOME Shot shell extension.
+//!
+//! The extension lets the user click a window, captures it with GNOME
+//! Shell's own screenshot API and reports its title and app id. Without the extension the portal dialog
+//! is shown instead, where the user picks the window but no details come back.
+
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+
+use super::stitch::image_to_pixbuf;
+use super::{CaptureMode, Screenshot, WindowInfo};
+
+/// Opacity of the darkest part of the drop shadow
+const SHADOW_ALPHA: u32 = 110;
+
+/// Blur radius of the drop shadow, in pixels
+const SHADOW_RADIUS: u32 = 24;
+
+/// Error the extension returns when the user presses Escape instead of picking a window
+const CANCELLED_ERROR: &str = "org.gnome.Shell.Extensions.GnomeShot.Error.Cancelled";
+
+#[zbus::proxy(
+    interface = "org.gnome.Shell.Extensions.GnomeShot",
+    default_service = "org.gnome.Shell.Extensions.GnomeShot",
+    default_path = "/org/gnome/Shell/Extensions/GnomeShot"
+)]
+trait ShellExtension {
+    /// Let the user pick a window and capture it; returns a descriptor to
+    /// read the PNG from, the window title, the app id and the scale of the
+    /// window's monitor
+    fn capture_window(&self) -> zbus::Result<(zbus::zvariant::OwnedFd, String, String, f64)>;
+}
+
+/// How a captured window is presented
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowOptions {
+    /// Add a soft drop shadow around the window
+    pub shadow: bool,
+    /// Keep the window's rounded corners transparent rather than filling them in
+    pub rounded_corners: bool,
+}
+
+impl Default for WindowOptions {
+    fn default() -> Self {
+        Self { shadow: true, rounded_corners: true }
+    }
+}
+
+/// Capture a window and return the file with the window's details and scale,
+/// if known (run on the Tokio runtime)
+pub async fn capture_window_path() -> anyhow::Result<(PathBuf, Option<WindowInfo>, Option<f64>)> {
+    match capture_with_extension().await {
+        Ok((image, info, scale_factor)) => Ok((save_window_capture(image)?, Some(info), Some(scale_factor))),
+        Err(zbus::Error::MethodError(name, _, _)) if name.as_str() == CANCELLED_ERROR => {
+            anyhow::bail!("Window capture cancelled")
+        }
+        Err(e) => {
+            eprintln!("Shell extension unavailable ({}), using the portal", e);
+            Ok((super::capture_interactive_path().await?, None, None))
+        }
+    }
+}
+
+async fn capture_with_extension() -> zbus::Result<(zbus::zvariant::OwnedFd, WindowInfo, f64)> {
+    let connection = zbus::Connection::session().await?;
+    let proxy = ShellExtensionProxy::new(&connection).await?;
+    let (image, title, app_id, scale_factor) = proxy.capture_window().await?;
+    Ok((image, WindowInfo { title, app_id }, scale_factor))
+}
+
+/// Copy the PNG the extension handed over next to the portal's captures in
+/// Pictures, so the `raw_files` setting applies to it like any other capture
+fn save_window_capture(image: zbus::zvariant::OwnedFd) -> anyhow::Result<PathBuf> {
+    let dir = gtk::glib::user_special_dir(gtk::glib::UserDirectory::Pictures)
+        .ok_or_else(|| anyhow::anyhow!("No Pictures folder"))?
+        .join("Screenshots");
+    std::fs::create_dir_all(&dir)?;
+    let time = gtk::glib::DateTime::now_local()?.format("%Y-%m-%d %H-%M-%S")?;
+    let path = super::unique_path(&dir, Path::new(&format!("Window from {}.png", time)));
+    let mut source = std::fs::File::from(std::os::fd::OwnedFd::from(image));
+    std::io::copy(&mut source, &mut std::fs::File::create(&path)?)?;
+    Ok(path)
+}
+
+/// Load a window capture and apply `options`; the shadow takes a moment,
+/// so this is meant to run off the GTK thread
+pub fn load_window_image(path: &Path, options: WindowOptions, scale_factor: f64) -> anyhow::Result<RgbaImage> {
+    let image = image::open(path)?.to_rgba8();
+    Ok(apply_window_options(image, options, scale_factor))
+}
+
+/// Screenshot of a window loaded with `load_window_image` (must be called on GTK thread)
+pub fn window_screenshot(
+    path: PathBuf,
+    window: Option<WindowInfo>,
+    image: RgbaImage,
+    scale_factor: f64,
+) -> Screenshot {
+    Screenshot {
+        pixbuf: image_to_pixbuf(image),
+        source_path: Some(path),
+        capture_mode: CaptureMode::Window,
+        window,
+        scale_factor,
+        preset: None,
+    }
+}
+
+/// Trim any captured shadow, then fill in the corners and add our own shadow as asked,
+/// sized for a capture with `scale_factor` image pixels per screen pixel
+pub fn apply_window_options(image: RgbaImage, options: WindowOptions, scale_factor: f64) -> RgbaImage {
+    let mut image = match opaque_bounds(&image) {
+        Some((x, y, width, height)) => image::imageops::crop_imm(&image, x, y, width, height).to_image(),
+        None => image,
+    };
+    if !options.rounded_corners {
+        square_corners(&mut image);
+    }
+    if options.shadow {
+        image = add_shadow(&image, (SHADOW_RADIUS as f64 * scale_factor).round() as u32);
+    }
+    image
+}
+
+/// Smallest rectangle holding every fully opaque pixel
+///
+/// Shadows and the area outside rounded corners are translucent, so this is the window itself.
+fn opaque_bounds(image: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[3] == 255 {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+    }
+    (left <= right).then(|| (left, top, right - left + 1, bottom - top + 1))
+}
+
+/// Make the translucent pixels at the ends of each row opaque, blending them
+/// over the nearest opaque pixel of the row
+fn square_corners(image: &mut RgbaImage) {
+    let width = image.width();
+    for y in 0..image.height() {
+        let opaque = |x: &u32| image.get_pixel(*x, y)[3] == 255;
+        let (Some(first), Some(last)) = ((0..width).find(opaque), (0..width).rev().find(opaque)) else {
+            continue;
+        };
+
+        let (left_edge, right_edge) = (*image.get_pixel(first, y), *image.get_pixel(last, y));
+        for x in (0..first).chain(last + 1..width) {
+            let edge = if x < first { left_edge } else { right_edge };
+            let pixel = image.get_pixel_mut(x, y);
+            let alpha = pixel[3] as u32;
+            for c in 0..3 {
+                pixel[c] = ((pixel[c] as u32 * alpha + edge[c] as u32 * (255 - alpha)) / 255) as u8;
+            }
+            pixel[3] = 255;
+        }
+    }
+}
+
+/// Put the window on a transparent canvas with a soft shadow below it
+fn add_shadow(image: &RgbaImage, radius: u32) -> RgbaImage {
+    let margin = radius * 2;
+    let drop = radius / 2;
+    let (width, height) = image.dimensions();
+
+    let mut shadow = RgbaImage::new(width + 2 * margin, height + 2 * margin);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let alpha = (pixel[3] as u32 * SHADOW_ALPHA / 255) as u8;
+        shadow.put_pixel(x + margin, y + margin + drop, Rgba([0, 0, 0, alpha]));
+    }
+
+    let mut output = image::imageops::blur(&shadow, radius as f32 / 2.0);
+    image::imageops::overlay(&mut output, image, margin as i64, margin as i64);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([200, 0, 0, 255]);
+
+    /// An opaque red window at (x, y) on a faint translucent shadow
+    fn captured_window(x: u32, y: u32, width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(40, 30, |px, py| {
+            if (x..x + width).contains(&px) && (y..y + height).contains(&py) {
+                RED
+            } else {
+                Rgba([0, 0, 0, 60])
+            }
+        })
+    }
+
+    #[test]
+    fn trims_captured_shadow() {
+        let options = WindowOptions { shadow: false, rounded_corners: true };
+        let window = apply_window_options(captured_window(5, 6, 20, 10), options, 1.0);
+
+        assert_eq!(window.dimensions(), (20, 10));
+        assert!(window.pixels().all(|pixel| *pixel == RED));
+    }
+
+    #[test]
+    fn fills_in_rounded_corners() {
+        let mut window = RgbaImage::from_pixel(6, 4, RED);
+        window.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        window.put_pixel(5, 3, Rgba([255, 255, 255, 128]));
+
+        let rounded = apply_window_options(window.clone(), WindowOptions { shadow: false, rounded_corners: true }, 1.0);
+        assert_eq!(rounded, window);
+
+        let square = apply_window_options(window, WindowOptions { shadow: false, rounded_corners: false }, 1.0);
+        assert_eq!(*square.get_pixel(0, 0), RED);
+        assert_eq!(square.get_pixel(5, 3)[3], 255);
+        assert!(square.get_pixel(5, 3)[1] > 100);
+    }
+
+    #[test]
+    fn shadow_surrounds_the_window() {
+        let window = RgbaImage::from_pixel(20, 10, RED);
+        let shadowed = add_shadow(&window, 8);
+
+        assert_eq!(shadowed.dimensions(), (20 + 32, 10 + 32));
+        assert_eq!(*shadowed.get_pixel(16, 16), RED);
+        // Darker below the window than above it
+        let below = shadowed.get_pixel(26, 16 + 10 + 4)[3];
+        let above = shadowed.get_pixel(26, 16 - 4)[3];
+        assert!(below > above);
+        assert_eq!(shadowed.get_pixel(0, 0)[3], 0);
+    }
+}
@@ -0,0 +1,379 @@
+use adw::prelude::*;
+use gtk::gdk_pixbuf::{Colorspace, Pixbuf};
+use gtk::{gdk, gio, glib};
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
+
+use crate::capture::{self, CombineLayout, CombineOptions};
+
+/// Dialog that collects images and lays them out into one
+///
+/// Images come from files, recent captures, the clipboard or the screenshot
+/// open in the editor; the combined image is handed to `on_combined`.
+pub struct CombineDialog {
+    window: adw::Window,
+    list: gtk::ListBox,
+    combine_btn: gtk::Button,
+    layout_dropdown: gtk::DropDown,
+    columns_spin: gtk::SpinButton,
+    gap_spin: gtk::SpinButton,
+    background_btn: gtk::ColorDialogButton,
+    images: RefCell<Vec<(String, Pixbuf)>>,
+    on_combined: Box<dyn Fn(Pixbuf)>,
+}
+
+impl CombineDialog {
+    pub fn present(
+        parent: &impl IsA<gtk::Window>,
+        recent: &[PathBuf],
+        current: Option<gdk::Texture>,
+        on_combined: impl Fn(Pixbuf) + 'static,
+    ) {
+        let list = gtk::ListBox::new();
+        list.set_selection_mode(gtk::SelectionMode::None);
+        list.add_css_class("boxed-list");
+
+        let placeholder = gtk::Label::builder()
+            .label("Add two or more images")
+            .margin_top(24)
+            .margin_bottom(24)
+            .build();
+        placeholder.add_css_class("dim-label");
+        list.set_placeholder(Some(&placeholder));
+
+        let combine_btn = gtk::Button::builder()
+            .label("Combine")
+            .sensitive(false)
+            .build();
+        combine_btn.add_css_class("suggested-action");
+
+        let layout_dropdown = gtk::DropDown::from_strings(&["Side by Side", "Stacked", "Grid"]);
+        layout_dropdown.set_tooltip_text(Some("Layout"));
+
+        let columns_spin = gtk::SpinButton::with_range(1.0, 8.0, 1.0);
+        columns_spin.set_value(2.0);
+        columns_spin.set_tooltip_text(Some("Columns"));
+        columns_spin.set_sensitive(false);
+
+        let gap_spin = gtk::SpinButton::with_range(0.0, 256.0, 4.0);
+        gap_spin.set_value(16.0);
+        gap_spin.set_tooltip_text(Some("Gap between images"));
+
+        let background_btn = gtk::ColorDialogButton::new(Some(gtk::ColorDialog::new()));
+        background_btn.set_rgba(&gdk::RGBA::new(1.0, 1.0, 1.0, 1.0));
+        background_btn.set_tooltip_text(Some("Background"));
+
+        let header = adw::HeaderBar::new();
+        header.pack_end(&combine_btn);
+
+        let window = adw::Window::builder()
+            .title("Combine Images")
+            .modal(true)
+            .transient_for(parent)
+            .default_width(440)
+            .default_height(560)
+            .build();
+
+        let dialog = Rc::new(Self {
+            window: window.clone(),
+            list: list.clone(),
+            combine_btn: combine_btn.clone(),
+            layout_dropdown: layout_dropdown.clone(),
+            columns_spin: columns_spin.clone(),
+            gap_spin: gap_spin.clone(),
+            background_btn: background_btn.clone(),
+            images: RefCell::new(Vec::new()),
+            on_combined: Box::new(on_combined),
+        });
+
+        // Ways to add images
+        let open_btn = gtk::Button::with_label("Open…");
+        let weak = Rc::downgrade(&dialog);
+        open_btn.connect_clicked(move |_| {
+            if let Some(dialog) = weak.upgrade() {
+                dialog.open_files();
+            }
+        });
+
+        let paste_btn = gtk::Button::with_label("Paste");
+        paste_btn.set_tooltip_text(Some("Add the image on the clipboard"));
+        let weak = Rc::downgrade(&dialog);
+        paste_btn.connect_clicked(move |_| {
+            if let Some(dialog) = weak.upgrade() {
+                dialog.paste();
+            }
+        });
+
+        let recent_btn = gtk::MenuButton::builder()
+            .label("Recent")
+            .tooltip_text("Add a recent capture")
+            .sensitive(!recent.is_empty())
+            .popover(&dialog.recent_popover(recent))
+            .build();
+
+        let add_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        add_row.set_homogeneous(true);
+        add_row.append(&open_btn);
+        add_row.append(&recent_btn);
+        add_row.append(&paste_btn);
+
+        // The screenshot being edited, annotations included
+        if let Some(pixbuf) = current.as_ref().and_then(texture_to_pixbuf) {
+            let current_btn = gtk::Button::with_label("Current");
+            current_btn.set_tooltip_text(Some("Add the screenshot open in the editor"));
+            let weak = Rc::downgrade(&dialog);
+            current_btn.connect_clicked(move |_| {
+                if let Some(dialog) = weak.upgrade() {
+                    dialog.add("Current screenshot".to_string(), pixbuf.clone());
+                }
+            });
+            add_row.append(&current_btn);
+        }
+
+        let columns_for_layout = columns_spin.clone();
+        layout_dropdown.connect_selected_notify(move |dropdown| {
+            columns_for_layout.set_sensitive(dropdown.selected() == 2);
+        });
+
+        let options_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        options_row.append(&layout_dropdown);
+        options_row.append(&columns_spin);
+        options_row.append(&gap_spin);
+        options_row.append(&background_btn);
+
+        let weak = Rc::downgrade(&dialog);
+        combine_btn.connect_clicked(move |_| {
+            if let Some(dialog) = weak.upgrade() {
+                dialog.combine();
+            }
+        });
+
+        let scrolled = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .vexpand(true)
+            .child(&list)
+            .build();
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        content.set_margin_start(12);
+        content.set_margin_end(12);
+        content.set_margin_top(12);
+        content.set_margin_bottom(12);
+        content.append(&scrolled);
+        content.append(&add_row);
+        content.append(&options_row);
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&header);
+        toolbar_view.set_content(Some(&content));
+        window.set_content(Some(&toolbar_view));
+
+        // The window's signal handlers only hold weak references, so keep
+        // the dialog alive for as long as the window is open
+        let keep_alive = RefCell::new(Some(dialog));
+        window.connect_close_request(move |_| {
+            keep_alive.take();
+            glib::Propagation::Proceed
+        });
+
+        window.present();
+    }
+
+    fn recent_popover(self: &Rc<Self>, recent: &[PathBuf]) -> gtk::Popover {
+        let popover = gtk::Popover::new();
+        let recent_box = gtk::Box::new(gtk::Orientation::Vertical, 2);
+
+        for path in recent.iter().rev() {
+            let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            let btn = gtk::Button::with_label(&name);
+            btn.add_css_class("flat");
+
+            let weak = Rc::downgrade(self);
+            let path = path.clone();
+            btn.connect_clicked(glib::clone!(
+                #[weak]
+                popover,
+                move |_| {
+                    popover.popdown();
+                    let Some(dialog) = weak.upgrade() else { return };
+                    match Pixbuf::from_file(&path) {
+                        Ok(pixbuf) => dialog.add(name.clone(), pixbuf),
+                        Err(e) => eprintln!("Failed to load {}: {}", path.display(), e),
+                    }
+                }
+            ));
+            recent_box.append(&btn);
+        }
+
+        popover.set_child(Some(&recent_box));
+        popover
+    }
+
+    fn add(self: &Rc<Self>, name: String, pixbuf: Pixbuf) {
+        self.images.borrow_mut().push((name, pixbuf));
+        self.refresh();
+    }
+
+    fn open_files(self: &Rc<Self>) {
+        let filter = gtk::FileFilter::new();
+        filter.add_pixbuf_formats();
+        filter.set_name(Some("Images"));
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+
+        let file_dialog = gtk::FileDialog::builder()
+            .title("Add Images")
+            .modal(true)
+            .filters(&filters)
+            .build();
+
+        let weak = Rc::downgrade(self);
+        file_dialog.open_multiple(Some(&self.window), gio::Cancellable::NONE, move |result| {
+            let (Ok(files), Some(dialog)) = (result, weak.upgrade()) else { return };
+            for path in files.iter::<gio::File>().filter_map(|file| file.ok()?.path()) {
+                let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                match Pixbuf::from_file(&path) {
+                    Ok(pixbuf) => dialog.images.borrow_mut().push((name, pixbuf)),
+                    Err(e) => eprintln!("Failed to load {}: {}", path.display(), e),
+                }
+            }
+            dialog.refresh();
+        });
+    }
+
+    fn paste(self: &Rc<Self>) {
+        let weak = Rc::downgrade(self);
+        self.window.clipboard().read_texture_async(gio::Cancellable::NONE, move |result| {
+            let Some(dialog) = weak.upgrade() else { return };
+            match result {
+                Ok(Some(texture)) => {
+                    if let Some(pixbuf) = texture_to_pixbuf(&texture) {
+                        dialog.add("Pasted image".to_string(), pixbuf);
+                    }
+                }
+                Ok(None) => eprintln!("No image on the clipboard"),
+                Err(e) => eprintln!("Failed to paste image: {}", e),
+            }
+        });
+    }
+
+    /// Rebuild the list of images
+    fn refresh(self: &Rc<Self>) {
+        while let Some(row) = self.list.first_child() {
+            self.list.remove(&row);
+        }
+
+        let images = self.images.borrow();
+        for (index, (name, pixbuf)) in images.iter().enumerate() {
+            let thumbnail = gtk::Picture::builder()
+                .paintable(&gdk::Texture::for_pixbuf(pixbuf))
+                .can_shrink(true)
+                .width_request(64)
+                .height_request(48)
+                .build();
+
+            let label = gtk::Label::builder()
+                .label(name)
+                .xalign(0.0)
+                .hexpand(true)
+                .ellipsize(gtk::pango::EllipsizeMode::Middle)
+                .build();
+            let size = gtk::Label::builder()
+                .label(format!("{}×{}", pixbuf.width(), pixbuf.height()))
+                .xalign(0.0)
+                .build();
+            size.add_css_class("dim-label");
+            size.add_css_class("caption");
+
+            let labels = gtk::Box::new(gtk::Orientation::Vertical, 2);
+            labels.set_valign(gtk::Align::Center);
+            labels.append(&label);
+            labels.append(&size);
+
+            let row_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            row_box.set_margin_start(6);
+            row_box.set_margin_end(6);
+            row_box.set_margin_top(6);
+            row_box.set_margin_bottom(6);
+            row_box.append(&thumbnail);
+            row_box.append(&labels);
+
+            let buttons = [
+                ("go-up-symbolic", "Move up", index > 0),
+                ("go-down-symbolic", "Move down", index + 1 < images.len()),
+                ("user-trash-symbolic", "Remove", true),
+            ];
+            for (icon, tooltip, sensitive) in buttons {
+                let btn = gtk::Button::builder()
+                    .icon_name(icon)
+                    .tooltip_text(tooltip)
+                    .sensitive(sensitive)
+                    .valign(gtk::Align::Center)
+                    .build();
+                btn.add_css_class("flat");
+
+                let weak: Weak<Self> = Rc::downgrade(self);
+                btn.connect_clicked(move |_| {
+                    let Some(dialog) = weak.upgrade() else { return };
+                    {
+                        let mut images = dialog.images.borrow_mut();
+                        match icon {
+                            "go-up-symbolic" => images.swap(index, index - 1),
+                            "go-down-symbolic" => images.swap(index, index + 1),
+                            _ => {
+                                images.remove(index);
+                            }
+                        }
+                    }
+                    dialog.refresh();
+                });
+                row_box.append(&btn);
+            }
+
+            self.list.append(&row_box);
+        }
+
+        self.combine_btn.set_sensitive(images.len() >= 2);
+    }
+
+    fn options(&self) -> CombineOptions {
+        let layout = match self.layout_dropdown.selected() {
+            1 => CombineLayout::Vertical,
+            2 => CombineLayout::Grid { columns: self.columns_spin.value() as u32 },
+            _ => CombineLayout::Horizontal,
+        };
+        let color = self.background_btn.rgba();
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        CombineOptions {
+            layout,
+            gap: self.gap_spin.value() as u32,
+            background: image::Rgba([channel(color.red()), channel(color.green()), channel(color.blue()), channel(color.alpha())]),
+        }
+    }
+
+    fn combine(&self) {
+        let pixbufs: Vec<Pixbuf> = self.images.borrow().iter().map(|(_, pixbuf)| pixbuf.clone()).collect();
+        if let Some(combined) = capture::combine_pixbufs(&pixbufs, &self.options()) {
+            (self.on_combined)(combined);
+            self.window.close();
+        }
+    }
+}
+
+/// Copy a texture, such as one from the clipboard, into a pixbuf
+fn texture_to_pixbuf(texture: &gdk::Texture) -> Option<Pixbuf> {
+    let mut downloader = gdk::TextureDownloader::new(texture);
+    downloader.set_format(gdk::MemoryFormat::R8g8b8a8);
+    let (bytes, stride) = downloader.download_bytes();
+    Some(Pixbuf::from_bytes(
+        &bytes,
+        Colorspace::Rgb,
+        true,
+        8,
+        texture.width(),
+        texture.height(),
+        i32::try_from(stride).ok()?,
+    ))
+}
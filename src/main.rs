@@ -1,6 +1,11 @@
 mod application;
 mod canvas;
 mod capture;
+mod combine_dialog;
+mod ocr;
+mod recording_dialog;
+mod region_selector;
+mod settings;
 mod window;
 
 use application::GnomeShotApplication;
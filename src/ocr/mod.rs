@@ -0,0 +1,152 @@
+//! Offline text recognition.
+//!
+//! Engines sit behind `OcrBackend` so they can be swapped or mocked; the
+//! default runs the system's Tesseract. Recognized words keep their boxes in
+//! screenshot pixels so other features, such as snapping, can use them.
+
+mod tesseract;
+
+pub use tesseract::TesseractBackend;
+
+use image::RgbaImage;
+
+use crate::capture::Region;
+
+/// A recognized word and where it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    pub text: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// How sure the engine is, from 0 to 100
+    pub confidence: f32,
+    /// Block, paragraph and line the word belongs to, for laying the text out again
+    pub block: u32,
+    pub paragraph: u32,
+    pub line: u32,
+}
+
+impl Word {
+    /// Bounds as (x, y, width, height) in image coordinates
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        (self.x as f64, self.y as f64, self.width as f64, self.height as f64)
+    }
+}
+
+/// A text recognition engine
+pub trait OcrBackend: Send + Sync {
+    /// Words found in `image`, in reading order, with boxes in its pixels
+    fn recognize(&self, image: &RgbaImage) -> anyhow::Result<Vec<Word>>;
+}
+
+/// Recognize the text in `region` of `image`, or in all of it; word boxes
+/// are in pixels of the whole image either way
+pub fn recognize(backend: &dyn OcrBackend, image: &RgbaImage, region: Option<Region>) -> anyhow::Result<Vec<Word>> {
+    let Some(region) = region else {
+        return backend.recognize(image);
+    };
+
+    let x = region.x.min(image.width());
+    let y = region.y.min(image.height());
+    let width = region.width.min(image.width() - x);
+    let height = region.height.min(image.height() - y);
+    if width == 0 || height == 0 {
+        return Ok(Vec::new());
+    }
+
+    let part = image::imageops::crop_imm(image, x, y, width, height).to_image();
+    let mut words = backend.recognize(&part)?;
+    for word in &mut words {
+        word.x += x;
+        word.y += y;
+    }
+    Ok(words)
+}
+
+/// Lay words out as plain text: a line for each recognized line, with a
+/// blank line between paragraphs
+pub fn to_text(words: &[Word]) -> String {
+    let mut text = String::new();
+    let mut previous: Option<&Word> = None;
+    for word in words {
+        if let Some(previous) = previous {
+            if (previous.block, previous.paragraph) != (word.block, word.paragraph) {
+                text.push_str("\n\n");
+            } else if previous.line != word.line {
+                text.push('\n');
+            } else {
+                text.push(' ');
+            }
+        }
+        text.push_str(&word.text);
+        previous = Some(word);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Reports fixed words and remembers the size of each image it was given
+    struct MockBackend {
+        words: Vec<Word>,
+        seen: Mutex<Vec<(u32, u32)>>,
+    }
+
+    impl OcrBackend for MockBackend {
+        fn recognize(&self, image: &RgbaImage) -> anyhow::Result<Vec<Word>> {
+            self.seen.lock().unwrap().push(image.dimensions());
+            Ok(self.words.clone())
+        }
+    }
+
+    fn word(text: &str, x: u32, (block, paragraph, line): (u32, u32, u32)) -> Word {
+        Word { text: text.to_string(), x, y: 4, width: 30, height: 12, confidence: 90.0, block, paragraph, line }
+    }
+
+    fn mock(words: Vec<Word>) -> MockBackend {
+        MockBackend { words, seen: Mutex::new(Vec::new()) }
+    }
+
+    #[test]
+    fn whole_image_is_passed_through() {
+        let backend = mock(vec![word("Hello", 10, (1, 1, 1))]);
+        let image = RgbaImage::new(200, 100);
+
+        let words = recognize(&backend, &image, None).unwrap();
+        assert_eq!(words[0].bounds(), (10.0, 4.0, 30.0, 12.0));
+        assert_eq!(*backend.seen.lock().unwrap(), [(200, 100)]);
+    }
+
+    #[test]
+    fn region_is_cropped_and_boxes_moved_back() {
+        let backend = mock(vec![word("Hello", 10, (1, 1, 1))]);
+        let image = RgbaImage::new(200, 100);
+        let region = Region { x: 50, y: 20, width: 400, height: 30 };
+
+        let words = recognize(&backend, &image, Some(region)).unwrap();
+        assert_eq!((words[0].x, words[0].y), (60, 24));
+        // Clamped to the image
+        assert_eq!(*backend.seen.lock().unwrap(), [(150, 30)]);
+
+        let outside = Region { x: 300, y: 0, width: 10, height: 10 };
+        assert!(recognize(&backend, &image, Some(outside)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn text_keeps_lines_and_paragraphs() {
+        let words = [
+            word("Save", 0, (1, 1, 1)),
+            word("changes?", 40, (1, 1, 1)),
+            word("Unsaved", 0, (1, 1, 2)),
+            word("Cancel", 0, (2, 1, 1)),
+        ];
+
+        assert_eq!(to_text(&words), "Save changes?\nUnsaved\n\nCancel");
+        assert_eq!(to_text(&[]), "");
+    }
+}
@@ -0,0 +1,125 @@
+//! Text recognition with the `tesseract` command.
+//!
+//! The image is piped in as PNG and words come back in Tesseract's TSV
+//! output, one row per word with its box and confidence.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use image::RgbaImage;
+
+use super::{OcrBackend, Word};
+
+/// TSV level of a row describing a single word
+const WORD_LEVEL: &str = "5";
+
+/// Runs the system's Tesseract; install it and a language pack, such as
+/// `tesseract` and `tesseract-langpack-eng`
+#[derive(Debug, Clone)]
+pub struct TesseractBackend {
+    /// Tesseract language codes, such as `eng` or `eng+deu`
+    pub language: String,
+}
+
+impl Default for TesseractBackend {
+    fn default() -> Self {
+        Self { language: "eng".to_string() }
+    }
+}
+
+impl TesseractBackend {
+    /// Whether the `tesseract` command can be run, such as in a Flatpak that
+    /// doesn't bundle it; checked once
+    pub fn is_available() -> bool {
+        static AVAILABLE: OnceLock<bool> = OnceLock::new();
+        *AVAILABLE.get_or_init(|| {
+            Command::new("tesseract")
+                .arg("--version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        })
+    }
+}
+
+impl OcrBackend for TesseractBackend {
+    fn recognize(&self, image: &RgbaImage) -> anyhow::Result<Vec<Word>> {
+        let mut png = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+
+        let mut child = Command::new("tesseract")
+            .args(["stdin", "stdout", "-l", &self.language, "tsv"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Couldn't run tesseract ({}); is it installed?", e))?;
+
+        // Closing stdin tells Tesseract the image is complete
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&png)?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            anyhow::bail!("tesseract failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+
+        Ok(parse_tsv(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// Words from Tesseract's TSV output, skipping the header, rows for
+/// blocks and lines, and empty words
+pub fn parse_tsv(tsv: &str) -> Vec<Word> {
+    tsv.lines()
+        .filter_map(|line| {
+            // level page block paragraph line word left top width height confidence text
+            let fields: Vec<&str> = line.splitn(12, '\t').collect();
+            if fields.len() < 12 || fields[0] != WORD_LEVEL {
+                return None;
+            }
+            let text = fields[11].trim();
+            if text.is_empty() {
+                return None;
+            }
+            let number = |index: usize| fields[index].trim().parse::<u32>().ok();
+            Some(Word {
+                text: text.to_string(),
+                x: number(6)?,
+                y: number(7)?,
+                width: number(8)?,
+                height: number(9)?,
+                confidence: fields[10].trim().parse().unwrap_or(0.0),
+                block: number(2)?,
+                paragraph: number(3)?,
+                line: number(4)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_words_from_tsv() {
+        let tsv = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext\n\
+                   1\t1\t0\t0\t0\t0\t0\t0\t640\t480\t-1\t\n\
+                   4\t1\t1\t1\t1\t0\t12\t8\t180\t20\t-1\t\n\
+                   5\t1\t1\t1\t1\t1\t12\t8\t60\t20\t96.5\tOpen\n\
+                   5\t1\t1\t1\t1\t2\t80\t8\t112\t20\t91\tSettings…\n\
+                   5\t1\t1\t1\t1\t3\t200\t8\t4\t20\t95\t \n\
+                   5\t1\t2\t1\t1\t1\t12\t60\t40\t18\t88\tOK\n";
+
+        let words = parse_tsv(tsv);
+        let texts: Vec<&str> = words.iter().map(|word| word.text.as_str()).collect();
+        assert_eq!(texts, ["Open", "Settings…", "OK"]);
+        assert_eq!(words[1].bounds(), (80.0, 8.0, 112.0, 20.0));
+        assert_eq!(words[0].confidence, 96.5);
+        assert_eq!((words[2].block, words[2].paragraph, words[2].line), (2, 1, 1));
+        assert_eq!(super::super::to_text(&words), "Open Settings…\n\nOK");
+    }
+}
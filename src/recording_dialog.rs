@@ -0,0 +1,380 @@
+use adw::prelude::*;
+use gtk::{gdk, gio, glib};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::capture::{self, ExportOptions, Recorder, Recording, RecordingFormat, Region};
+
+/// Frame rates offered for export
+const FRAME_RATES: [u32; 5] = [5, 10, 15, 24, 30];
+
+fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Small window showing the elapsed time with a Stop button
+///
+/// Stops on its own if the stream ends or frames can no longer be stored, and
+/// hands what was recorded to `on_stopped`.
+pub fn present_recording_controls(
+    parent: &impl IsA<gtk::Window>,
+    recorder: Recorder,
+    on_stopped: impl Fn(Recording) + 'static,
+) {
+    let time_label = gtk::Label::new(Some("0:00"));
+    time_label.add_css_class("title-1");
+    time_label.add_css_class("numeric");
+
+    let stop_btn = gtk::Button::builder()
+        .label("Stop Recording")
+        .halign(gtk::Align::Center)
+        .build();
+    stop_btn.add_css_class("destructive-action");
+    stop_btn.add_css_class("pill");
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 12);
+    content.set_margin_start(24);
+    content.set_margin_end(24);
+    content.set_margin_top(12);
+    content.set_margin_bottom(24);
+    content.append(&time_label);
+    content.append(&stop_btn);
+
+    let toolbar_view = adw::ToolbarView::new();
+    toolbar_view.add_top_bar(&adw::HeaderBar::new());
+    toolbar_view.set_content(Some(&content));
+
+    let window = adw::Window::builder()
+        .title("Recording")
+        .transient_for(parent)
+        .resizable(false)
+        .content(&toolbar_view)
+        .build();
+
+    let recorder = Rc::new(RefCell::new(Some(recorder)));
+    let stop = Rc::new(glib::clone!(
+        #[weak]
+        window,
+        #[strong]
+        recorder,
+        move || {
+            let Some(recorder) = recorder.take() else { return };
+            window.close();
+            on_stopped(recorder.stop());
+        }
+    ));
+
+    let stop_for_btn = stop.clone();
+    stop_btn.connect_clicked(move |_| stop_for_btn());
+
+    // Closing the window stops the recording too
+    let stop_for_close = stop.clone();
+    window.connect_close_request(move |_| {
+        stop_for_close();
+        glib::Propagation::Proceed
+    });
+
+    glib::timeout_add_local(Duration::from_millis(250), move || {
+        let finished = match recorder.borrow().as_ref() {
+            Some(recorder) => {
+                time_label.set_label(&format_time(recorder.elapsed()));
+                recorder.is_interrupted() || recorder.has_ended()
+            }
+            None => return glib::ControlFlow::Break,
+        };
+        if finished {
+            stop();
+            return glib::ControlFlow::Break;
+        }
+        glib::ControlFlow::Continue
+    });
+
+    window.present();
+}
+
+/// Dialog to trim, crop and export a finished recording
+pub struct RecordingDialog {
+    window: adw::Window,
+    recording: Arc<Recording>,
+    preview: gtk::Picture,
+    start_scale: gtk::Scale,
+    end_scale: gtk::Scale,
+    fps_dropdown: gtk::DropDown,
+    format_dropdown: gtk::DropDown,
+    crop_switch: gtk::Switch,
+    crop_spins: [gtk::SpinButton; 4],
+    export_btn: gtk::Button,
+}
+
+impl RecordingDialog {
+    pub fn present(parent: &impl IsA<gtk::Window>, recording: Recording) {
+        let Some((width, height)) = recording.dimensions() else {
+            eprintln!("Nothing was recorded");
+            return;
+        };
+        let length = recording.length.as_secs_f64();
+
+        let preview = gtk::Picture::builder()
+            .can_shrink(true)
+            .vexpand(true)
+            .build();
+
+        let time_scale = || {
+            let scale = gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.0, length.max(0.1), 0.1);
+            scale.set_hexpand(true);
+            scale.set_draw_value(true);
+            scale.set_digits(1);
+            scale
+        };
+        let start_scale = time_scale();
+        start_scale.set_value(0.0);
+        start_scale.set_tooltip_text(Some("Start (seconds)"));
+        let end_scale = time_scale();
+        end_scale.set_value(length);
+        end_scale.set_tooltip_text(Some("End (seconds)"));
+
+        let fps_labels: Vec<String> = FRAME_RATES.iter().map(|fps| format!("{} fps", fps)).collect();
+        let fps_labels: Vec<&str> = fps_labels.iter().map(String::as_str).collect();
+        let fps_dropdown = gtk::DropDown::from_strings(&fps_labels);
+        fps_dropdown.set_selected(2);
+        fps_dropdown.set_tooltip_text(Some("Frame rate"));
+
+        let format_names: Vec<&str> = RecordingFormat::ALL.iter().map(|format| format.name()).collect();
+        let format_dropdown = gtk::DropDown::from_strings(&format_names);
+        format_dropdown.set_tooltip_text(Some("Format"));
+
+        let crop_switch = gtk::Switch::builder().valign(gtk::Align::Center).build();
+        let crop_spins = [
+            ("X", 0.0, width),
+            ("Y", 0.0, height),
+            ("Width", width as f64, width),
+            ("Height", height as f64, height),
+        ]
+        .map(|(tooltip, value, max)| {
+            let spin = gtk::SpinButton::with_range(0.0, max as f64, 1.0);
+            spin.set_value(value);
+            spin.set_tooltip_text(Some(tooltip));
+            spin.set_sensitive(false);
+            spin
+        });
+
+        let export_btn = gtk::Button::with_label("Export…");
+        export_btn.add_css_class("suggested-action");
+
+        let header = adw::HeaderBar::new();
+        header.pack_end(&export_btn);
+
+        let window = adw::Window::builder()
+            .title("Export Recording")
+            .modal(true)
+            .transient_for(parent)
+            .default_width(640)
+            .default_height(560)
+            .build();
+
+        let dialog = Rc::new(Self {
+            window: window.clone(),
+            recording: Arc::new(recording),
+            preview: preview.clone(),
+            start_scale: start_scale.clone(),
+            end_scale: end_scale.clone(),
+            fps_dropdown: fps_dropdown.clone(),
+            format_dropdown: format_dropdown.clone(),
+            crop_switch: crop_switch.clone(),
+            crop_spins: crop_spins.clone(),
+            export_btn: export_btn.clone(),
+        });
+
+        let labeled = |label: &str, widget: &gtk::Widget| {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            let label = gtk::Label::builder().label(label).width_chars(6).xalign(0.0).build();
+            row.append(&label);
+            row.append(widget);
+            row
+        };
+
+        let crop_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        crop_row.append(&crop_switch);
+        for spin in &crop_spins {
+            crop_row.append(spin);
+        }
+
+        let options_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        options_row.append(&format_dropdown);
+        options_row.append(&fps_dropdown);
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        content.set_margin_start(12);
+        content.set_margin_end(12);
+        content.set_margin_top(12);
+        content.set_margin_bottom(12);
+        content.append(&preview);
+        content.append(&labeled("Start", start_scale.upcast_ref()));
+        content.append(&labeled("End", end_scale.upcast_ref()));
+        content.append(&labeled("Crop", crop_row.upcast_ref()));
+        content.append(&options_row);
+
+        // Keep start before end and show the first frame that will be exported
+        let weak = Rc::downgrade(&dialog);
+        start_scale.connect_value_changed(move |scale| {
+            let Some(dialog) = weak.upgrade() else { return };
+            if dialog.end_scale.value() < scale.value() {
+                dialog.end_scale.set_value(scale.value());
+            }
+            dialog.update_preview();
+        });
+        let weak = Rc::downgrade(&dialog);
+        end_scale.connect_value_changed(move |scale| {
+            let Some(dialog) = weak.upgrade() else { return };
+            if dialog.start_scale.value() > scale.value() {
+                dialog.start_scale.set_value(scale.value());
+            }
+        });
+
+        let weak = Rc::downgrade(&dialog);
+        crop_switch.connect_active_notify(move |switch| {
+            let Some(dialog) = weak.upgrade() else { return };
+            for spin in &dialog.crop_spins {
+                spin.set_sensitive(switch.is_active());
+            }
+            dialog.update_preview();
+        });
+        for spin in &crop_spins {
+            let weak = Rc::downgrade(&dialog);
+            spin.connect_value_changed(move |_| {
+                if let Some(dialog) = weak.upgrade() {
+                    dialog.update_preview();
+                }
+            });
+        }
+
+        let weak = Rc::downgrade(&dialog);
+        export_btn.connect_clicked(move |_| {
+            if let Some(dialog) = weak.upgrade() {
+                dialog.choose_file();
+            }
+        });
+
+        let toolbar_view = adw::ToolbarView::new();
+        toolbar_view.add_top_bar(&header);
+        // Say so if the recording ended before Stop was pressed
+        if let Some(reason) = &dialog.recording.interrupted {
+            let banner = adw::Banner::new(&format!("Recording stopped early: {}", reason));
+            banner.set_revealed(true);
+            toolbar_view.add_top_bar(&banner);
+        }
+        toolbar_view.set_content(Some(&content));
+        window.set_content(Some(&toolbar_view));
+
+        dialog.update_preview();
+
+        // The window's signal handlers only hold weak references, so keep
+        // the dialog alive for as long as the window is open
+        let keep_alive = RefCell::new(Some(dialog));
+        window.connect_close_request(move |_| {
+            keep_alive.take();
+            glib::Propagation::Proceed
+        });
+
+        window.present();
+    }
+
+    fn region(&self) -> Option<Region> {
+        if !self.crop_switch.is_active() {
+            return None;
+        }
+        let [x, y, width, height] = self.crop_spins.each_ref().map(|spin| spin.value() as u32);
+        Some(Region { x, y, width, height })
+    }
+
+    fn options(&self) -> ExportOptions {
+        let end = Duration::from_secs_f64(self.end_scale.value());
+        ExportOptions {
+            format: RecordingFormat::ALL[self.format_dropdown.selected() as usize],
+            fps: FRAME_RATES[self.fps_dropdown.selected() as usize],
+            start: Duration::from_secs_f64(self.start_scale.value()),
+            end: (end < self.recording.length).then_some(end),
+            region: self.region(),
+        }
+    }
+
+    fn update_preview(&self) {
+        let mut options = self.options();
+        // Just the first frame
+        options.end = Some(options.start + Duration::from_secs(1) / options.fps);
+        let Some(frame) = capture::prepare_frames(&self.recording, &options).next().and_then(Result::ok) else { return };
+
+        let (width, height) = frame.image.dimensions();
+        let bytes = glib::Bytes::from_owned(frame.image.into_raw());
+        let texture = gdk::MemoryTexture::new(
+            width as i32,
+            height as i32,
+            gdk::MemoryFormat::R8g8b8a8,
+            &bytes,
+            width as usize * 4,
+        );
+        self.preview.set_paintable(Some(&texture));
+    }
+
+    fn choose_file(self: &Rc<Self>) {
+        let options = self.options();
+        let now = glib::DateTime::now_local().unwrap();
+        let filename = format!(
+            "recording-{}.{}",
+            now.format("%Y%m%d-%H%M%S").unwrap(),
+            options.format.extension()
+        );
+
+        let file_dialog = gtk::FileDialog::builder()
+            .title("Export Recording")
+            .modal(true)
+            .initial_name(filename)
+            .build();
+
+        let weak = Rc::downgrade(self);
+        file_dialog.save(Some(&self.window), gio::Cancellable::NONE, move |result| {
+            let (Ok(file), Some(dialog)) = (result, weak.upgrade()) else { return };
+            if let Some(path) = file.path() {
+                dialog.export(options, path);
+            }
+        });
+    }
+
+    /// Encode on a worker thread, closing the dialog once done
+    fn export(self: &Rc<Self>, options: ExportOptions, path: std::path::PathBuf) {
+        self.export_btn.set_sensitive(false);
+        self.export_btn.set_label("Exporting…");
+
+        let (sender, receiver) = std::sync::mpsc::channel::<anyhow::Result<()>>();
+        let recording = self.recording.clone();
+        let target = path.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(capture::export_recording(&recording, &options, &target));
+        });
+
+        let weak = Rc::downgrade(self);
+        glib::timeout_add_local(Duration::from_millis(100), move || {
+            let result = match receiver.try_recv() {
+                Ok(result) => result,
+                Err(std::sync::mpsc::TryRecvError::Empty) => return glib::ControlFlow::Continue,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => Err(anyhow::anyhow!("Export thread stopped")),
+            };
+            let Some(dialog) = weak.upgrade() else { return glib::ControlFlow::Break };
+            match result {
+                Ok(()) => {
+                    eprintln!("Saved recording to: {}", path.display());
+                    dialog.window.close();
+                }
+                Err(e) => {
+                    eprintln!("Failed to export recording: {}", e);
+                    dialog.export_btn.set_sensitive(true);
+                    dialog.export_btn.set_label("Export…");
+                }
+            }
+            glib::ControlFlow::Break
+        });
+    }
+}
@@ -0,0 +1,426 @@
+use adw::prelude::*;
+use gtk::gdk_pixbuf::Pixbuf;
+use gtk::{gdk, glib};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::canvas::rounded_rectangle;
+use crate::capture::{CapturePreset, Constraint, Selection};
+
+/// Radius of the loupe, in screen pixels
+const LOUPE_RADIUS: f64 = 64.0;
+
+/// Screen pixels per capture pixel inside the loupe
+const LOUPE_ZOOM: f64 = 8.0;
+
+/// Pixels moved per arrow key press while Ctrl is held
+const LARGE_STEP: i32 = 10;
+
+/// Fullscreen overlay for picking a region of a fullscreen capture
+///
+/// Shows the capture dimmed outside the selection, with a loupe around the
+/// pointer and the selection's size in pixels. Drag to select, drag inside
+/// the selection to move it, use the arrow keys to move it a pixel at a time
+/// and Shift with the arrow keys to resize it. Capture presets limit the
+/// selection to an aspect ratio or a fixed size. Enter captures, Escape cancels;
+/// either way `on_done` is called once, with the selection and the preset it
+/// was made with, or `None` when cancelled.
+pub struct RegionSelector {
+    window: gtk::Window,
+    area: gtk::DrawingArea,
+    capture_btn: gtk::Button,
+    pixbuf: Pixbuf,
+    selection: Cell<Selection>,
+    presets: Vec<CapturePreset>,
+    /// Index of the chosen preset
+    preset: Cell<usize>,
+    /// Capture pixel where the current drag started, and the selection when
+    /// the drag is moving it rather than drawing a new one
+    drag: Cell<Option<((f64, f64), Option<Selection>)>>,
+    /// Pointer position in widget coordinates, for the loupe
+    pointer: Cell<Option<(f64, f64)>>,
+    on_done: RefCell<Option<Box<dyn FnOnce(Option<(Selection, CapturePreset)>)>>>,
+}
+
+impl RegionSelector {
+    pub fn present(
+        parent: &impl IsA<gtk::Window>,
+        pixbuf: Pixbuf,
+        presets: Vec<CapturePreset>,
+        initial_preset: usize,
+        on_done: impl FnOnce(Option<(Selection, CapturePreset)>) + 'static,
+    ) {
+        let area = gtk::DrawingArea::builder()
+            .hexpand(true)
+            .vexpand(true)
+            .build();
+        area.set_cursor_from_name(Some("crosshair"));
+
+        let labels: Vec<String> = presets.iter().map(CapturePreset::label).collect();
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        let preset_dropdown = gtk::DropDown::from_strings(&labels);
+        preset_dropdown.set_tooltip_text(Some("Capture preset"));
+
+        let hint = gtk::Label::new(Some("Arrows move · Shift+Arrows resize · Enter captures · Esc cancels"));
+        hint.add_css_class("dim-label");
+
+        let cancel_btn = gtk::Button::with_label("Cancel");
+        let capture_btn = gtk::Button::builder()
+            .label("Capture")
+            .sensitive(false)
+            .build();
+        capture_btn.add_css_class("suggested-action");
+
+        let toolbar = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        toolbar.add_css_class("osd");
+        toolbar.add_css_class("toolbar");
+        toolbar.set_halign(gtk::Align::Center);
+        toolbar.set_valign(gtk::Align::Start);
+        toolbar.set_margin_top(24);
+        toolbar.append(&preset_dropdown);
+        toolbar.append(&hint);
+        toolbar.append(&cancel_btn);
+        toolbar.append(&capture_btn);
+
+        let overlay = gtk::Overlay::new();
+        overlay.set_child(Some(&area));
+        overlay.add_overlay(&toolbar);
+
+        // Not added to the application, so its Escape shortcut doesn't quick-save
+        let window = gtk::Window::builder()
+            .title("Select Region")
+            .transient_for(parent)
+            .modal(true)
+            .decorated(false)
+            .child(&overlay)
+            .build();
+
+        let selector = Rc::new(Self {
+            window: window.clone(),
+            area: area.clone(),
+            capture_btn: capture_btn.clone(),
+            pixbuf,
+            selection: Cell::new(Selection::default()),
+            presets,
+            preset: Cell::new(0),
+            drag: Cell::new(None),
+            pointer: Cell::new(None),
+            on_done: RefCell::new(Some(Box::new(on_done))),
+        });
+
+        let weak = Rc::downgrade(&selector);
+        area.set_draw_func(move |_, cr, width, height| {
+            if let Some(selector) = weak.upgrade() {
+                selector.draw(cr, width as f64, height as f64);
+            }
+        });
+
+        let drag = gtk::GestureDrag::new();
+        let weak = Rc::downgrade(&selector);
+        drag.connect_drag_begin(move |_, x, y| {
+            if let Some(selector) = weak.upgrade() {
+                selector.drag_begin(x, y);
+            }
+        });
+        let weak = Rc::downgrade(&selector);
+        drag.connect_drag_update(move |gesture, dx, dy| {
+            if let (Some(selector), Some((x, y))) = (weak.upgrade(), gesture.start_point()) {
+                selector.drag_update(x + dx, y + dy);
+            }
+        });
+        let weak = Rc::downgrade(&selector);
+        drag.connect_drag_end(move |_, _, _| {
+            if let Some(selector) = weak.upgrade() {
+                selector.drag.set(None);
+            }
+        });
+        area.add_controller(drag);
+
+        let motion = gtk::EventControllerMotion::new();
+        let weak = Rc::downgrade(&selector);
+        motion.connect_motion(move |_, x, y| {
+            if let Some(selector) = weak.upgrade() {
+                selector.pointer.set(Some((x, y)));
+                selector.area.queue_draw();
+            }
+        });
+        let weak = Rc::downgrade(&selector);
+        motion.connect_leave(move |_| {
+            if let Some(selector) = weak.upgrade() {
+                selector.pointer.set(None);
+                selector.area.queue_draw();
+            }
+        });
+        area.add_controller(motion);
+
+        // Handled before the toolbar sees them, so arrows always adjust the selection
+        let keys = gtk::EventControllerKey::new();
+        keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let weak = Rc::downgrade(&selector);
+        keys.connect_key_pressed(move |_, key, _, modifiers| match weak.upgrade() {
+            Some(selector) => selector.key_pressed(key, modifiers),
+            None => glib::Propagation::Proceed,
+        });
+        window.add_controller(keys);
+
+        let weak = Rc::downgrade(&selector);
+        preset_dropdown.connect_selected_notify(move |dropdown| {
+            if let Some(selector) = weak.upgrade() {
+                selector.set_preset(dropdown.selected() as usize);
+            }
+        });
+        if initial_preset < selector.presets.len() {
+            preset_dropdown.set_selected(initial_preset as u32);
+            selector.set_preset(initial_preset);
+        }
+
+        cancel_btn.connect_clicked(glib::clone!(
+            #[weak]
+            window,
+            move |_| window.close()
+        ));
+
+        let weak = Rc::downgrade(&selector);
+        capture_btn.connect_clicked(move |_| {
+            if let Some(selector) = weak.upgrade() {
+                selector.accept();
+            }
+        });
+
+        // The window's handlers only hold weak references, so keep the selector alive with it
+        let keep_alive = RefCell::new(Some(selector));
+        window.connect_close_request(move |_| {
+            // Closed without accepting
+            if let Some(on_done) = keep_alive.take().and_then(|selector| selector.on_done.take()) {
+                on_done(None);
+            }
+            glib::Propagation::Proceed
+        });
+
+        window.fullscreen();
+        window.present();
+    }
+
+    fn bounds(&self) -> (u32, u32) {
+        (self.pixbuf.width() as u32, self.pixbuf.height() as u32)
+    }
+
+    /// Scale and offset that fit the whole capture into the overlay
+    fn view(&self) -> (f64, f64, f64) {
+        let (width, height) = (self.area.width() as f64, self.area.height() as f64);
+        let (image_w, image_h) = (self.pixbuf.width() as f64, self.pixbuf.height() as f64);
+        let scale = (width / image_w).min(height / image_h);
+        (scale, (width - image_w * scale) / 2.0, (height - image_h * scale) / 2.0)
+    }
+
+    /// Capture pixel under a point of the overlay
+    fn to_image(&self, x: f64, y: f64) -> (f64, f64) {
+        let (scale, offset_x, offset_y) = self.view();
+        ((x - offset_x) / scale, (y - offset_y) / scale)
+    }
+
+    fn set_selection(&self, selection: Selection) {
+        self.selection.set(selection);
+        self.capture_btn.set_sensitive(!selection.is_empty());
+        self.area.queue_draw();
+    }
+
+    /// Constraint of the chosen preset
+    fn constraint(&self) -> Constraint {
+        self.presets.get(self.preset.get()).map_or(Constraint::Free, |preset| preset.constraint)
+    }
+
+    fn set_preset(&self, index: usize) {
+        self.preset.set(index);
+        let constraint = self.constraint();
+        let selection = self.selection.get();
+        if !selection.is_empty() {
+            self.set_selection(selection.constrain(constraint, self.bounds()));
+        } else if let Constraint::Size(..) = constraint {
+            // Start with the fixed size in the middle of the screen
+            let (width, height) = self.bounds();
+            let center = (width as f64 / 2.0, height as f64 / 2.0);
+            self.set_selection(Selection::from_drag(center, center, constraint, self.bounds()));
+        }
+    }
+
+    fn drag_begin(&self, x: f64, y: f64) {
+        let point = self.to_image(x, y);
+        let selection = self.selection.get();
+        let inside = !selection.is_empty()
+            && (selection.x as f64..(selection.x + selection.width) as f64).contains(&point.0)
+            && (selection.y as f64..(selection.y + selection.height) as f64).contains(&point.1);
+
+        if inside {
+            self.drag.set(Some((point, Some(selection))));
+        } else {
+            self.drag.set(Some((point, None)));
+            self.set_selection(Selection::from_drag(point, point, self.constraint(), self.bounds()));
+        }
+    }
+
+    fn drag_update(&self, x: f64, y: f64) {
+        let Some((start, moving)) = self.drag.get() else { return };
+        let end = self.to_image(x, y);
+        self.pointer.set(Some((x, y)));
+
+        let selection = match moving {
+            Some(original) => {
+                let (dx, dy) = ((end.0 - start.0).round() as i32, (end.1 - start.1).round() as i32);
+                original.nudge(dx, dy, self.bounds())
+            }
+            None => Selection::from_drag(start, end, self.constraint(), self.bounds()),
+        };
+        self.set_selection(selection);
+    }
+
+    fn key_pressed(&self, key: gdk::Key, modifiers: gdk::ModifierType) -> glib::Propagation {
+        let step = if modifiers.contains(gdk::ModifierType::CONTROL_MASK) { LARGE_STEP } else { 1 };
+        let (dx, dy) = match key {
+            gdk::Key::Escape => {
+                self.window.close();
+                return glib::Propagation::Stop;
+            }
+            gdk::Key::Return | gdk::Key::KP_Enter => {
+                self.accept();
+                return glib::Propagation::Stop;
+            }
+            gdk::Key::Left => (-step, 0),
+            gdk::Key::Right => (step, 0),
+            gdk::Key::Up => (0, -step),
+            gdk::Key::Down => (0, step),
+            _ => return glib::Propagation::Proceed,
+        };
+
+        let selection = self.selection.get();
+        if !selection.is_empty() {
+            let selection = if modifiers.contains(gdk::ModifierType::SHIFT_MASK) {
+                selection.resize(dx, dy, self.constraint(), self.bounds())
+            } else {
+                selection.nudge(dx, dy, self.bounds())
+            };
+            self.set_selection(selection);
+        }
+        glib::Propagation::Stop
+    }
+
+    fn accept(&self) {
+        let selection = self.selection.get();
+        if selection.is_empty() {
+            return;
+        }
+        let preset = self.presets.get(self.preset.get()).cloned().unwrap_or_else(|| CapturePreset {
+            name: Constraint::Free.to_setting(),
+            constraint: Constraint::Free,
+        });
+        let on_done = self.on_done.take();
+        self.window.close();
+        if let Some(on_done) = on_done {
+            on_done(Some((selection, preset)));
+        }
+    }
+
+    fn draw(&self, cr: &gtk::cairo::Context, width: f64, height: f64) {
+        let (scale, offset_x, offset_y) = self.view();
+        let selection = self.selection.get();
+
+        cr.set_source_rgb(0.0, 0.0, 0.0);
+        let _ = cr.paint();
+
+        // The capture, fitted to the overlay
+        cr.save().unwrap();
+        cr.translate(offset_x, offset_y);
+        cr.scale(scale, scale);
+        gtk::gdk::prelude::GdkCairoContextExt::set_source_pixbuf(cr, &self.pixbuf, 0.0, 0.0);
+        let filter = if scale < 1.0 { gtk::cairo::Filter::Good } else { gtk::cairo::Filter::Nearest };
+        cr.source().set_filter(filter);
+        let _ = cr.paint();
+
+        // Dim everything but the selection
+        cr.set_fill_rule(gtk::cairo::FillRule::EvenOdd);
+        cr.rectangle(0.0, 0.0, self.pixbuf.width() as f64, self.pixbuf.height() as f64);
+        if !selection.is_empty() {
+            cr.rectangle(selection.x as f64, selection.y as f64, selection.width as f64, selection.height as f64);
+        }
+        cr.set_source_rgba(0.0, 0.0, 0.0, 0.45);
+        let _ = cr.fill();
+        cr.restore().unwrap();
+
+        if !selection.is_empty() {
+            let x = offset_x + selection.x as f64 * scale;
+            let y = offset_y + selection.y as f64 * scale;
+            let (w, h) = (selection.width as f64 * scale, selection.height as f64 * scale);
+            cr.set_source_rgb(1.0, 1.0, 1.0);
+            cr.set_line_width(1.0);
+            cr.rectangle(x.round() + 0.5, y.round() + 0.5, w.round() - 1.0, h.round() - 1.0);
+            let _ = cr.stroke();
+
+            // Size readout below the selection, or inside it at the bottom of the screen
+            let text = format!("{} × {}", selection.width, selection.height);
+            let label_y = if y + h + 32.0 < height { y + h + 8.0 } else { y + h - 32.0 };
+            draw_label(cr, &text, x, label_y);
+        }
+
+        if let Some(pointer) = self.pointer.get() {
+            self.draw_loupe(cr, pointer, width, height);
+        }
+    }
+
+    /// Enlarged view of the pixels around the pointer, with the pixel under it outlined
+    fn draw_loupe(&self, cr: &gtk::cairo::Context, (x, y): (f64, f64), width: f64, height: f64) {
+        let (image_x, image_y) = self.to_image(x, y);
+        let (image_w, image_h) = (self.pixbuf.width() as f64, self.pixbuf.height() as f64);
+        if image_x < 0.0 || image_y < 0.0 || image_x >= image_w || image_y >= image_h {
+            return;
+        }
+        let (pixel_x, pixel_y) = (image_x.floor(), image_y.floor());
+
+        // Beside the pointer, on whichever side has room
+        let gap = LOUPE_RADIUS + 24.0;
+        let center_x = if x + gap + LOUPE_RADIUS < width { x + gap } else { x - gap };
+        let center_y = if y + gap + LOUPE_RADIUS + 32.0 < height { y + gap } else { y - gap };
+
+        cr.save().unwrap();
+        cr.arc(center_x, center_y, LOUPE_RADIUS, 0.0, std::f64::consts::TAU);
+        cr.clip();
+        cr.set_source_rgb(0.0, 0.0, 0.0);
+        let _ = cr.paint();
+
+        cr.translate(center_x, center_y);
+        cr.scale(LOUPE_ZOOM, LOUPE_ZOOM);
+        cr.translate(-(pixel_x + 0.5), -(pixel_y + 0.5));
+        gtk::gdk::prelude::GdkCairoContextExt::set_source_pixbuf(cr, &self.pixbuf, 0.0, 0.0);
+        cr.source().set_filter(gtk::cairo::Filter::Nearest);
+        let _ = cr.paint();
+
+        cr.rectangle(pixel_x, pixel_y, 1.0, 1.0);
+        cr.set_source_rgb(1.0, 0.2, 0.2);
+        cr.set_line_width(1.5 / LOUPE_ZOOM);
+        let _ = cr.stroke();
+        cr.restore().unwrap();
+
+        cr.arc(center_x, center_y, LOUPE_RADIUS, 0.0, std::f64::consts::TAU);
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        cr.set_line_width(2.0);
+        let _ = cr.stroke();
+
+        let text = format!("{}, {}", pixel_x, pixel_y);
+        draw_label(cr, &text, center_x - LOUPE_RADIUS / 2.0, center_y + LOUPE_RADIUS + 8.0);
+    }
+}
+
+/// White text on a dark rounded background with its top left at (x, y)
+fn draw_label(cr: &gtk::cairo::Context, text: &str, x: f64, y: f64) {
+    let layout = pangocairo::functions::create_layout(cr);
+    layout.set_text(text);
+    let (_, logical) = layout.pixel_extents();
+    let (width, height) = (logical.width() as f64, logical.height() as f64);
+
+    rounded_rectangle(cr, x, y, width + 12.0, height + 6.0, 4.0);
+    cr.set_source_rgba(0.0, 0.0, 0.0, 0.75);
+    let _ = cr.fill();
+
+    cr.move_to(x + 6.0, y + 3.0);
+    cr.set_source_rgb(1.0, 1.0, 1.0);
+    pangocairo::functions::show_layout(cr, &layout);
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::canvas::{default_presets, FrameSettings, StylePreset, DEFAULT_LIMIT};
+use crate::capture::{Area, CapturePreset, RawFilePolicy};
+
+/// Directory holding all GNOME Shot settings, `~/.config/gnome-shot`
+pub fn config_dir() -> PathBuf {
+    gtk::glib::user_config_dir().join("gnome-shot")
+}
+
+/// Plain `key=value` settings kept in `~/.config/gnome-shot/config`
+#[derive(Debug, Default, Clone)]
+pub struct Settings {
+    entries: Vec<(String, String)>,
+}
+
+impl Settings {
+    /// Read the config file; a missing or unreadable file gives empty settings
+    pub fn load() -> Self {
+        let content = std::fs::read_to_string(config_dir().join("config")).unwrap_or_default();
+        let entries = content
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+        Self { entries }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let dir = config_dir();
+        std::fs::create_dir_all(&dir)?;
+        let content: String = self.entries.iter().map(|(k, v)| format!("{}={}\n", k, v)).collect();
+        std::fs::write(dir.join("config"), content)
+    }
+}
+
+/// Built-in capture presets followed by the user's own from the `capture_presets` setting
+pub fn load_capture_presets() -> Vec<CapturePreset> {
+    let mut presets = CapturePreset::built_in();
+    if let Some(value) = Settings::load().get("capture_presets") {
+        presets.extend(CapturePreset::parse_list(value));
+    }
+    presets
+}
+
+/// What to do with raw capture files, from the `raw_files` setting
+pub fn raw_file_policy() -> RawFilePolicy {
+    Settings::load().get("raw_files").and_then(RawFilePolicy::parse).unwrap_or_default()
+}
+
+/// How many undo steps the editor keeps, from the `history_limit` setting
+pub fn history_limit() -> usize {
+    Settings::load()
+        .get("history_limit")
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(DEFAULT_LIMIT)
+}
+
+/// The area "Capture Previous Area" repeats, if a region was selected before
+pub fn last_area() -> Option<Area> {
+    Settings::load().get("last_area").and_then(Area::parse)
+}
+
+/// Set when the presets file exists but couldn't be read, so saving doesn't replace it
+static PRESETS_UNREADABLE: AtomicBool = AtomicBool::new(false);
+
+fn presets_file() -> PathBuf {
+    config_dir().join("presets.json")
+}
+
+/// The user's style presets, or the built-in ones if none were saved
+///
+/// A file that can't be read or parsed is left alone for the user to fix:
+/// the built-in presets are used, but never saved over it.
+pub fn load_presets() -> Vec<StylePreset> {
+    match import_presets(&presets_file()) {
+        Ok(presets) => presets,
+        Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) => {
+            default_presets()
+        }
+        Err(e) => {
+            eprintln!("Failed to load {}, using the built-in presets: {}", presets_file().display(), e);
+            PRESETS_UNREADABLE.store(true, Ordering::Relaxed);
+            default_presets()
+        }
+    }
+}
+
+pub fn save_presets(presets: &[StylePreset]) -> anyhow::Result<()> {
+    if PRESETS_UNREADABLE.load(Ordering::Relaxed) {
+        anyhow::bail!("{} couldn't be loaded, so it's left as it is", presets_file().display());
+    }
+    std::fs::create_dir_all(config_dir())?;
+    export_presets(&presets_file(), presets)
+}
+
+/// Write presets to a file that can be shared and imported elsewhere
+pub fn export_presets(path: &Path, presets: &[StylePreset]) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(presets)?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+pub fn import_presets(path: &Path) -> anyhow::Result<Vec<StylePreset>> {
+    let json = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn frame_file() -> PathBuf {
+    config_dir().join("frame.json")
+}
+
+/// Frame settings from last time, or the defaults (frame off)
+pub fn load_frame() -> FrameSettings {
+    std::fs::read_to_string(frame_file())
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_frame(frame: &FrameSettings) -> anyhow::Result<()> {
+    std::fs::create_dir_all(config_dir())?;
+    std::fs::write(frame_file(), serde_json::to_string_pretty(frame)?)?;
+    Ok(())
+}
@@ -1,11 +1,17 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 use gtk::{gio, glib, gdk};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 
 use crate::application::GnomeShotApplication;
-use crate::canvas::{CanvasWidget, Tool};
-use crate::capture::Screenshot;
+use crate::canvas::{
+    frame_backgrounds, ArrowStyle, CalloutStyle, CanvasWidget, DashStyle, FrameAspect, FrameSettings, MagnifyShape, SpotlightEffect, SpotlightShape,
+    StampContent, StampImage, StampSymbol, StylePreset, Tool,
+};
+use crate::capture::{self, Screenshot};
+use crate::ocr;
+use crate::settings;
 
 mod imp {
     use super::*;
@@ -13,6 +19,7 @@ mod imp {
     #[derive(Default)]
     pub struct GnomeShotWindow {
         pub canvas: RefCell<Option<CanvasWidget>>,
+        pub presets: RefCell<Vec<StylePreset>>,
     }
 
     #[glib::object_subclass]
@@ -54,14 +61,29 @@ impl GnomeShotWindow {
         self.set_title(Some("GNOME Shot"));
         self.set_default_size(900, 600);
 
+        self.imp().presets.replace(settings::load_presets());
+
+        // Ctrl+? shows the keyboard shortcuts
+        let builder = gtk::Builder::from_string(include_str!("../data/ui/shortcuts.ui"));
+        let help_overlay: gtk::ShortcutsWindow = builder.object("help_overlay").expect("help_overlay in shortcuts.ui");
+        self.set_help_overlay(Some(&help_overlay));
+
+        // Number keys apply style presets and letters pick tools. Handled after
+        // the focused widget so typing into text fields still works.
+        let keys = gtk::EventControllerKey::new();
+        keys.connect_key_pressed(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, keyval, _, state| window.on_key_pressed(keyval, state)
+        ));
+        self.add_controller(keys);
+
         // Create header bar
         let header = adw::HeaderBar::new();
 
-        let capture_btn = gtk::Button::builder()
-            .label("Capture")
-            .action_name("app.capture")
-            .build();
-        capture_btn.add_css_class("suggested-action");
+        let capture_btn = create_capture_button();
         header.pack_start(&capture_btn);
 
         // Create status page for empty state
@@ -86,15 +108,21 @@ impl GnomeShotWindow {
         let canvas = CanvasWidget::new();
         canvas.set_hexpand(true);
         canvas.set_vexpand(true);
+        canvas.set_frame(settings::load_frame());
 
         // Create header bar
         let header = adw::HeaderBar::new();
 
-        let capture_btn = gtk::Button::builder()
-            .label("Capture")
-            .action_name("app.capture")
-            .build();
-        capture_btn.add_css_class("suggested-action");
+        // Name the captured window, when known
+        if let Some(window) = &screenshot.window {
+            let title = adw::WindowTitle::new("GNOME Shot", &window.title);
+            title.set_tooltip_text(Some(&window.app_id));
+            header.set_title_widget(Some(&title));
+        } else if let Some(preset) = &screenshot.preset {
+            header.set_title_widget(Some(&adw::WindowTitle::new("GNOME Shot", &preset.label())));
+        }
+
+        let capture_btn = create_capture_button();
         header.pack_start(&capture_btn);
 
         // Copy button
@@ -114,10 +142,32 @@ impl GnomeShotWindow {
             }
         });
 
+        // Exporting HiDPI captures at full resolution or at 1x
+        let native = settings::Settings::load().get("export_native") != Some("false");
+        canvas.set_export_native(native);
+        let export_native = gio::SimpleAction::new_stateful("export-native", None, &native.to_variant());
+        export_native.set_enabled(screenshot.scale_factor > 1.0);
+        export_native.connect_activate(glib::clone!(
+            #[weak]
+            canvas,
+            move |action, _| {
+                crate::application::toggle_setting(action, "export_native");
+                canvas.set_export_native(action.state().and_then(|state| state.get::<bool>()).unwrap_or(true));
+            }
+        ));
+        self.add_action(&export_native);
+
+        let save_menu = gio::Menu::new();
+        save_menu.append(
+            Some(&format!("Save at Full Resolution ({}×)", screenshot.scale_factor)),
+            Some("win.export-native"),
+        );
+
         // Save button
-        let save_btn = gtk::Button::builder()
+        let save_btn = adw::SplitButton::builder()
             .icon_name("document-save-symbolic")
             .tooltip_text("Save screenshot (Ctrl+S)")
+            .menu_model(&save_menu)
             .build();
 
         let canvas_for_save = canvas.clone();
@@ -145,6 +195,7 @@ impl GnomeShotWindow {
             filters.append(&filter);
             dialog.set_filters(Some(&filters));
 
+            let saved_window = window.clone();
             dialog.save(Some(&window), gio::Cancellable::NONE, move |result: Result<gio::File, glib::Error>| {
                 if let Ok(file) = result {
                     if let Some(path) = file.path() {
@@ -152,22 +203,84 @@ impl GnomeShotWindow {
                             eprintln!("Failed to save: {}", e);
                         } else {
                             eprintln!("Saved to: {}", path.display());
+                            saved_window.screenshot_saved(&path);
                         }
                     }
                 }
             });
         });
 
+        // Undo history side panel
+        let history_panel = self.create_history_panel(&canvas);
+
+        let history_btn = gtk::ToggleButton::builder()
+            .icon_name("document-open-recent-symbolic")
+            .tooltip_text("Show history")
+            .build();
+        history_btn
+            .bind_property("active", &history_panel, "reveal-child")
+            .sync_create()
+            .build();
+
+        // Layers side panel
+        let layers_panel = self.create_layers_panel(&canvas);
+
+        let layers_btn = gtk::ToggleButton::builder()
+            .icon_name("view-paged-symbolic")
+            .tooltip_text("Show layers")
+            .build();
+        layers_btn
+            .bind_property("active", &layers_panel, "reveal-child")
+            .sync_create()
+            .build();
+
+        // Presentation frame side panel
+        let frame_panel = self.create_frame_panel(&canvas);
+
+        let frame_btn = gtk::ToggleButton::builder()
+            .icon_name("image-x-generic-symbolic")
+            .tooltip_text("Frame")
+            .build();
+        frame_btn
+            .bind_property("active", &frame_panel, "reveal-child")
+            .sync_create()
+            .build();
+
+        // Copy the text in the selected annotation's area, the crop area or the whole screenshot
+        let copy_text_btn = gtk::Button::builder()
+            .icon_name("insert-text-symbolic")
+            .tooltip_text("Copy text (Ctrl+Shift+T)")
+            .action_name("app.copy-text")
+            .build();
+
         header.pack_end(&save_btn);
         header.pack_end(&copy_btn);
+        header.pack_end(&copy_text_btn);
+        header.pack_end(&history_btn);
+        header.pack_end(&layers_btn);
+        header.pack_end(&frame_btn);
+
+        let shortcuts_btn = gtk::Button::builder()
+            .icon_name("preferences-desktop-keyboard-shortcuts-symbolic")
+            .tooltip_text("Keyboard shortcuts (Ctrl+?)")
+            .action_name("win.show-help-overlay")
+            .build();
+        header.pack_start(&shortcuts_btn);
 
         // Create annotation toolbar
         let toolbar = self.create_annotation_toolbar(&canvas);
 
+        // Canvas with the history panel beside it
+        let canvas_box = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        canvas_box.append(&canvas);
+        canvas_box.append(&layers_panel);
+        canvas_box.append(&history_panel);
+        canvas_box.append(&frame_panel);
+
         // Main content box
         let content_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
         content_box.append(&toolbar);
-        content_box.append(&canvas);
+        content_box.append(&canvas_box);
 
         // Create toolbar view
         let toolbar_view = adw::ToolbarView::new();
@@ -191,45 +304,98 @@ impl GnomeShotWindow {
         toolbar.set_margin_bottom(6);
 
         // Tool buttons with labels
+        let select_btn = gtk::ToggleButton::builder()
+            .label("Select")
+            .tooltip_text("Select tool (V, move and reshape annotations)")
+            .build();
+
         let arrow_btn = gtk::ToggleButton::builder()
             .label("Arrow")
-            .tooltip_text("Arrow tool (draw arrows)")
+            .tooltip_text("Arrow tool (A, draw arrows)")
             .active(true)
             .build();
 
         let rect_btn = gtk::ToggleButton::builder()
             .label("Rect")
-            .tooltip_text("Rectangle tool (draw rectangles)")
+            .tooltip_text("Rectangle tool (R, draw rectangles)")
             .build();
 
         let line_btn = gtk::ToggleButton::builder()
             .label("Line")
-            .tooltip_text("Line tool (draw lines)")
+            .tooltip_text("Line tool (L, draw lines)")
             .build();
 
         let ellipse_btn = gtk::ToggleButton::builder()
             .label("Ellipse")
-            .tooltip_text("Ellipse tool (draw ellipses)")
+            .tooltip_text("Ellipse tool (E, draw ellipses)")
             .build();
 
         let highlight_btn = gtk::ToggleButton::builder()
             .label("Highlight")
-            .tooltip_text("Highlight tool (semi-transparent)")
+            .tooltip_text("Highlight tool (H, semi-transparent)")
             .build();
 
         let blur_btn = gtk::ToggleButton::builder()
             .label("Blur")
-            .tooltip_text("Blur tool (redact sensitive info)")
+            .tooltip_text("Blur tool (B, redact sensitive info)")
+            .build();
+
+        let spotlight_btn = gtk::ToggleButton::builder()
+            .label("Spotlight")
+            .tooltip_text("Spotlight tool (S, dim everything else)")
+            .build();
+
+        let magnify_btn = gtk::ToggleButton::builder()
+            .label("Magnify")
+            .tooltip_text("Magnify tool (M, enlarge a region in a callout)")
+            .build();
+
+        let callout_btn = gtk::ToggleButton::builder()
+            .label("Callout")
+            .tooltip_text("Callout tool (T, drag from the target to where the text goes)")
             .build();
 
+        let stamp_btn = gtk::ToggleButton::builder()
+            .label("Stamp")
+            .tooltip_text("Stamp tool (P, click to place, drag to size)")
+            .build();
+
+        let crop_btn = gtk::ToggleButton::builder()
+            .label("Crop")
+            .tooltip_text("Crop tool (C, drag the area to keep, click to remove the crop)")
+            .build();
+
+        let text_btn = gtk::ToggleButton::builder()
+            .label("Copy Text")
+            .tooltip_text("Text tool (O, drag over text to copy it)")
+            .build();
+        if !ocr::TesseractBackend::is_available() {
+            text_btn.set_sensitive(false);
+            text_btn.set_tooltip_text(Some("Install Tesseract to copy text"));
+        }
+
         // Group the toggle buttons
+        select_btn.set_group(Some(&arrow_btn));
         rect_btn.set_group(Some(&arrow_btn));
         line_btn.set_group(Some(&arrow_btn));
         ellipse_btn.set_group(Some(&arrow_btn));
         highlight_btn.set_group(Some(&arrow_btn));
         blur_btn.set_group(Some(&arrow_btn));
+        spotlight_btn.set_group(Some(&arrow_btn));
+        magnify_btn.set_group(Some(&arrow_btn));
+        callout_btn.set_group(Some(&arrow_btn));
+        stamp_btn.set_group(Some(&arrow_btn));
+        crop_btn.set_group(Some(&arrow_btn));
+        text_btn.set_group(Some(&arrow_btn));
 
         // Connect tool buttons
+        let canvas_for_select = canvas.clone();
+        select_btn.connect_toggled(move |btn| {
+            if btn.is_active() {
+                canvas_for_select.set_tool(Tool::Select);
+            }
+        });
+
         let canvas_for_arrow = canvas.clone();
         arrow_btn.connect_toggled(move |btn| {
             if btn.is_active() {
@@ -272,17 +438,62 @@ impl GnomeShotWindow {
             }
         });
 
+        let canvas_for_spotlight = canvas.clone();
+        spotlight_btn.connect_toggled(move |btn| {
+            if btn.is_active() {
+                canvas_for_spotlight.set_tool(Tool::Spotlight);
+            }
+        });
+
+        let canvas_for_magnify = canvas.clone();
+        magnify_btn.connect_toggled(move |btn| {
+            if btn.is_active() {
+                canvas_for_magnify.set_tool(Tool::Magnify);
+            }
+        });
+
+        let canvas_for_callout = canvas.clone();
+        callout_btn.connect_toggled(move |btn| {
+            if btn.is_active() {
+                canvas_for_callout.set_tool(Tool::Callout);
+            }
+        });
+
+        let canvas_for_stamp = canvas.clone();
+        stamp_btn.connect_toggled(move |btn| {
+            if btn.is_active() {
+                canvas_for_stamp.set_tool(Tool::Stamp);
+            }
+        });
+
+        // Picks what the stamp tool places
+        let stamps_btn = self.create_stamps_button(canvas);
+
+        let canvas_for_crop = canvas.clone();
+        crop_btn.connect_toggled(move |btn| {
+            if btn.is_active() {
+                canvas_for_crop.set_tool(Tool::Crop);
+            }
+        });
+
+        let canvas_for_text = canvas.clone();
+        text_btn.connect_toggled(move |btn| {
+            if btn.is_active() {
+                canvas_for_text.set_tool(Tool::Text);
+            }
+        });
+
         // Color button using MenuButton with color indicator
         let color_indicator = gtk::DrawingArea::builder()
             .width_request(20)
             .height_request(20)
             .build();
 
-        // Set initial color (red)
-        let initial_color = gdk::RGBA::new(1.0, 0.0, 0.0, 1.0);
+        // Show the canvas's current color
+        let canvas_for_indicator = canvas.clone();
         color_indicator.set_draw_func({
-            let color = initial_color;
             move |_, cr, width, height| {
+                let color = canvas_for_indicator.current_color();
                 cr.set_source_rgba(
                     color.red() as f64,
                     color.green() as f64,
@@ -345,24 +556,6 @@ impl GnomeShotWindow {
             let indicator_clone = color_indicator.clone();
             btn.connect_clicked(move |_| {
                 canvas_clone.set_color(color);
-                // Update the color indicator
-                indicator_clone.set_draw_func({
-                    move |_, cr, width, height| {
-                        cr.set_source_rgba(
-                            color.red() as f64,
-                            color.green() as f64,
-                            color.blue() as f64,
-                            color.alpha() as f64,
-                        );
-                        cr.rectangle(0.0, 0.0, width as f64, height as f64);
-                        let _ = cr.fill();
-                        // Draw border
-                        cr.set_source_rgba(0.3, 0.3, 0.3, 1.0);
-                        cr.set_line_width(1.0);
-                        cr.rectangle(0.5, 0.5, width as f64 - 1.0, height as f64 - 1.0);
-                        let _ = cr.stroke();
-                    }
-                });
                 indicator_clone.queue_draw();
                 popover_clone.popdown();
             });
@@ -375,10 +568,65 @@ impl GnomeShotWindow {
         popover.set_child(Some(&color_box));
         color_btn.set_popover(Some(&popover));
 
+        // Keep the tool buttons and color in step when a preset is applied
+        let tool_buttons = [
+            (Tool::Select, select_btn.clone()),
+            (Tool::Arrow, arrow_btn.clone()),
+            (Tool::Rectangle, rect_btn.clone()),
+            (Tool::Line, line_btn.clone()),
+            (Tool::Ellipse, ellipse_btn.clone()),
+            (Tool::Highlight, highlight_btn.clone()),
+            (Tool::Blur, blur_btn.clone()),
+            (Tool::Spotlight, spotlight_btn.clone()),
+            (Tool::Magnify, magnify_btn.clone()),
+            (Tool::Callout, callout_btn.clone()),
+            (Tool::Stamp, stamp_btn.clone()),
+            (Tool::Crop, crop_btn.clone()),
+            (Tool::Text, text_btn.clone()),
+        ];
+        canvas.connect_style_changed(glib::clone!(
+            #[weak]
+            color_indicator,
+            move |canvas| {
+                if let Some((_, btn)) = tool_buttons.iter().find(|(tool, _)| *tool == canvas.current_tool()) {
+                    btn.set_active(true);
+                }
+                color_indicator.queue_draw();
+            }
+        ));
+
+        // Style presets
+        let presets_btn = self.create_presets_button(canvas);
+
+        // Tool options
+        let options_btn = self.create_options_button(canvas);
+
+        // Arrange menu for the selected annotation
+        let arrange_menu = gio::Menu::new();
+        let order_section = gio::Menu::new();
+        order_section.append(Some("Bring to Front"), Some("app.bring-to-front"));
+        order_section.append(Some("Bring Forward"), Some("app.bring-forward"));
+        order_section.append(Some("Send Backward"), Some("app.send-backward"));
+        order_section.append(Some("Send to Back"), Some("app.send-to-back"));
+        arrange_menu.append_section(None, &order_section);
+        let edit_section = gio::Menu::new();
+        edit_section.append(Some("Duplicate"), Some("app.duplicate"));
+        edit_section.append(Some("Copy Annotation"), Some("app.copy"));
+        edit_section.append(Some("Paste Annotation"), Some("app.paste"));
+        edit_section.append(Some("Delete"), Some("app.delete"));
+        arrange_menu.append_section(None, &edit_section);
+
+        let arrange_btn = gtk::MenuButton::builder()
+            .icon_name("view-list-symbolic")
+            .tooltip_text("Arrange")
+            .menu_model(&arrange_menu)
+            .build();
+
         // Undo button
         let undo_btn = gtk::Button::builder()
             .icon_name("edit-undo-symbolic")
             .tooltip_text("Undo (Ctrl+Z)")
+            .sensitive(false)
             .build();
 
         let canvas_for_undo = canvas.clone();
@@ -390,6 +638,7 @@ impl GnomeShotWindow {
         let redo_btn = gtk::Button::builder()
             .icon_name("edit-redo-symbolic")
             .tooltip_text("Redo (Ctrl+Shift+Z)")
+            .sensitive(false)
             .build();
 
         let canvas_for_redo = canvas.clone();
@@ -397,28 +646,1366 @@ impl GnomeShotWindow {
             canvas_for_redo.redo();
         });
 
+        canvas.connect_history_changed(glib::clone!(
+            #[weak]
+            undo_btn,
+            #[weak]
+            redo_btn,
+            move |canvas| {
+                undo_btn.set_sensitive(canvas.can_undo());
+                redo_btn.set_sensitive(canvas.can_redo());
+            }
+        ));
+
+        // Snapping and grid toggles
+        let snap_btn = gtk::ToggleButton::builder()
+            .label("Snap")
+            .tooltip_text("Snap to edges and other annotations")
+            .active(true)
+            .build();
+
+        let canvas_for_snap = canvas.clone();
+        snap_btn.connect_toggled(move |btn| {
+            canvas_for_snap.set_snapping(btn.is_active());
+        });
+
+        let grid_btn = gtk::ToggleButton::builder()
+            .label("Grid")
+            .tooltip_text("Show pixel grid")
+            .build();
+
+        let canvas_for_grid = canvas.clone();
+        grid_btn.connect_toggled(move |btn| {
+            canvas_for_grid.set_show_grid(btn.is_active());
+        });
+
         // Add separator
         let separator = gtk::Separator::new(gtk::Orientation::Vertical);
 
         // Add widgets to toolbar
+        toolbar.append(&select_btn);
         toolbar.append(&arrow_btn);
         toolbar.append(&rect_btn);
         toolbar.append(&line_btn);
         toolbar.append(&ellipse_btn);
         toolbar.append(&highlight_btn);
         toolbar.append(&blur_btn);
+        toolbar.append(&spotlight_btn);
+        toolbar.append(&magnify_btn);
+        toolbar.append(&callout_btn);
+        toolbar.append(&stamp_btn);
+        toolbar.append(&stamps_btn);
+        toolbar.append(&crop_btn);
+        toolbar.append(&text_btn);
         toolbar.append(&separator);
         toolbar.append(&color_btn);
+        toolbar.append(&presets_btn);
+        toolbar.append(&options_btn);
+        toolbar.append(&snap_btn);
+        toolbar.append(&grid_btn);
 
         let separator2 = gtk::Separator::new(gtk::Orientation::Vertical);
         toolbar.append(&separator2);
+        toolbar.append(&arrange_btn);
         toolbar.append(&undo_btn);
         toolbar.append(&redo_btn);
 
         toolbar
     }
 
+    /// Side panel listing history steps; clicking one jumps to the state after it
+    fn create_history_panel(&self, canvas: &CanvasWidget) -> gtk::Revealer {
+        let heading = gtk::Label::builder()
+            .label("History")
+            .halign(gtk::Align::Start)
+            .margin_start(12)
+            .margin_top(12)
+            .margin_bottom(6)
+            .build();
+        heading.add_css_class("heading");
+
+        let list = gtk::ListBox::new();
+        list.set_selection_mode(gtk::SelectionMode::Single);
+        list.add_css_class("navigation-sidebar");
+
+        let canvas_for_list = canvas.clone();
+        list.connect_row_activated(move |_, row| {
+            canvas_for_list.jump_to_history(row.index() as usize);
+        });
+
+        canvas.connect_history_changed(glib::clone!(
+            #[weak]
+            list,
+            move |canvas| {
+                populate_history_list(&list, canvas);
+            }
+        ));
+        populate_history_list(&list, canvas);
+
+        let scrolled = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .min_content_width(220)
+            .vexpand(true)
+            .child(&list)
+            .build();
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        content.append(&heading);
+        content.append(&scrolled);
+
+        let panel = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        panel.append(&gtk::Separator::new(gtk::Orientation::Vertical));
+        panel.append(&content);
+
+        gtk::Revealer::builder()
+            .transition_type(gtk::RevealerTransitionType::SlideLeft)
+            .child(&panel)
+            .build()
+    }
+
+    /// Side panel listing annotations top first, with visibility and lock
+    /// toggles; its selection follows the canvas and the other way round
+    fn create_layers_panel(&self, canvas: &CanvasWidget) -> gtk::Revealer {
+        let heading = gtk::Label::builder()
+            .label("Layers")
+            .halign(gtk::Align::Start)
+            .margin_start(12)
+            .margin_top(12)
+            .margin_bottom(6)
+            .build();
+        heading.add_css_class("heading");
+
+        let list = gtk::ListBox::new();
+        list.set_selection_mode(gtk::SelectionMode::Single);
+        list.add_css_class("navigation-sidebar");
+
+        // Set while the list is updated from the canvas, so that isn't echoed back
+        let updating = Rc::new(Cell::new(false));
+
+        list.connect_row_selected(glib::clone!(
+            #[weak]
+            canvas,
+            #[strong]
+            updating,
+            move |_, row| {
+                if !updating.get() {
+                    canvas.select(row.and_then(|row| layer_index_of(row, &canvas)));
+                }
+            }
+        ));
+
+        // Rebuilt only when what the rows show changes, not when an annotation just moves
+        let shown_rows: Rc<RefCell<Vec<LayerRow>>> = Rc::default();
+        canvas.connect_history_changed(glib::clone!(
+            #[weak]
+            list,
+            #[strong]
+            updating,
+            move |canvas| {
+                let rows = layer_rows(canvas);
+                updating.set(true);
+                if *shown_rows.borrow() != rows {
+                    populate_layers_list(&list, canvas, &rows);
+                    shown_rows.replace(rows);
+                } else {
+                    select_layer_row(&list, canvas);
+                }
+                updating.set(false);
+            }
+        ));
+
+        canvas.connect_selection_changed(glib::clone!(
+            #[weak]
+            list,
+            #[strong]
+            updating,
+            move |canvas| {
+                updating.set(true);
+                select_layer_row(&list, canvas);
+                updating.set(false);
+            }
+        ));
+
+        let scrolled = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .min_content_width(240)
+            .vexpand(true)
+            .child(&list)
+            .build();
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        content.append(&heading);
+        content.append(&scrolled);
+
+        let panel = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        panel.append(&gtk::Separator::new(gtk::Orientation::Vertical));
+        panel.append(&content);
+
+        gtk::Revealer::builder()
+            .transition_type(gtk::RevealerTransitionType::SlideLeft)
+            .child(&panel)
+            .build()
+    }
+
+    fn create_frame_panel(&self, canvas: &CanvasWidget) -> gtk::Revealer {
+        let frame = canvas.frame();
+
+        let heading = gtk::Label::builder()
+            .label("Frame")
+            .halign(gtk::Align::Start)
+            .hexpand(true)
+            .css_classes(["heading"])
+            .build();
+
+        let enabled_switch = gtk::Switch::builder()
+            .active(frame.enabled)
+            .valign(gtk::Align::Center)
+            .tooltip_text("Put saved and copied images on a background")
+            .build();
+
+        let heading_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        heading_row.append(&heading);
+        heading_row.append(&enabled_switch);
+
+        let preview = gtk::Picture::builder()
+            .can_shrink(true)
+            .height_request(160)
+            .build();
+
+        let backgrounds = frame_backgrounds();
+        let names: Vec<&str> = backgrounds.iter().map(|(name, _)| *name).collect();
+        let background_dropdown = gtk::DropDown::from_strings(&names);
+        background_dropdown.set_tooltip_text(Some("Background"));
+        let selected = backgrounds.iter().position(|(_, bg)| *bg == frame.background).unwrap_or(0);
+        background_dropdown.set_selected(selected as u32);
+
+        let aspect_dropdown = gtk::DropDown::from_strings(&["Fit Screenshot", "16:9", "4:3", "1:1"]);
+        aspect_dropdown.set_tooltip_text(Some("Aspect ratio"));
+        aspect_dropdown.set_selected(match frame.aspect {
+            FrameAspect::Auto => 0,
+            FrameAspect::Widescreen => 1,
+            FrameAspect::Standard => 2,
+            FrameAspect::Square => 3,
+        });
+
+        let padding_spin = gtk::SpinButton::with_range(0.0, 512.0, 8.0);
+        padding_spin.set_value(frame.padding);
+        padding_spin.set_tooltip_text(Some("Padding"));
+
+        let radius_spin = gtk::SpinButton::with_range(0.0, 64.0, 2.0);
+        radius_spin.set_value(frame.corner_radius);
+        radius_spin.set_tooltip_text(Some("Corner radius"));
+
+        let shadow_check = gtk::CheckButton::builder()
+            .label("Drop shadow")
+            .active(frame.shadow)
+            .build();
+
+        let chrome_check = gtk::CheckButton::builder()
+            .label("Window title bar")
+            .active(frame.window_chrome)
+            .build();
+
+        let spin_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        spin_row.set_homogeneous(true);
+        spin_row.append(&padding_spin);
+        spin_row.append(&radius_spin);
+
+        let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        content.set_margin_start(12);
+        content.set_margin_end(12);
+        content.set_margin_top(12);
+        content.set_margin_bottom(12);
+        content.set_width_request(240);
+        content.append(&heading_row);
+        content.append(&preview);
+        content.append(&background_dropdown);
+        content.append(&aspect_dropdown);
+        content.append(&spin_row);
+        content.append(&shadow_check);
+        content.append(&chrome_check);
+
+        let updater = Rc::new(FrameUpdater {
+            canvas: canvas.downgrade(),
+            preview: preview.downgrade(),
+            pending: RefCell::new(None),
+            unsaved: Cell::new(false),
+        });
+
+        let updater_for_switch = updater.clone();
+        enabled_switch.connect_active_notify(move |switch| {
+            updater_for_switch.change(|frame| frame.enabled = switch.is_active());
+        });
+        let updater_for_background = updater.clone();
+        background_dropdown.connect_selected_notify(move |dropdown| {
+            if let Some((_, background)) = frame_backgrounds().into_iter().nth(dropdown.selected() as usize) {
+                updater_for_background.change(|frame| frame.background = background);
+            }
+        });
+        let updater_for_aspect = updater.clone();
+        aspect_dropdown.connect_selected_notify(move |dropdown| {
+            let aspect = match dropdown.selected() {
+                1 => FrameAspect::Widescreen,
+                2 => FrameAspect::Standard,
+                3 => FrameAspect::Square,
+                _ => FrameAspect::Auto,
+            };
+            updater_for_aspect.change(|frame| frame.aspect = aspect);
+        });
+        let updater_for_padding = updater.clone();
+        padding_spin.connect_value_changed(move |spin| {
+            updater_for_padding.change(|frame| frame.padding = spin.value());
+        });
+        let updater_for_radius = updater.clone();
+        radius_spin.connect_value_changed(move |spin| {
+            updater_for_radius.change(|frame| frame.corner_radius = spin.value());
+        });
+        let updater_for_shadow = updater.clone();
+        shadow_check.connect_toggled(move |check| {
+            updater_for_shadow.change(|frame| frame.shadow = check.is_active());
+        });
+        let updater_for_chrome = updater.clone();
+        chrome_check.connect_toggled(move |check| {
+            updater_for_chrome.change(|frame| frame.window_chrome = check.is_active());
+        });
+
+        // Keep the preview in step with the annotations
+        canvas.connect_history_changed(move |_| updater.schedule());
+
+        let panel = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        panel.append(&gtk::Separator::new(gtk::Orientation::Vertical));
+        panel.append(&content);
+
+        let revealer = gtk::Revealer::builder()
+            .transition_type(gtk::RevealerTransitionType::SlideLeft)
+            .child(&panel)
+            .build();
+
+        // Rendering the preview is costly, so only do it while the panel is open
+        revealer.connect_reveal_child_notify(glib::clone!(
+            #[weak]
+            canvas,
+            #[weak]
+            preview,
+            move |revealer| {
+                if revealer.reveals_child() {
+                    preview.set_paintable(canvas.export_to_texture().as_ref());
+                }
+            }
+        ));
+
+        revealer
+    }
+
+    fn create_options_button(&self, canvas: &CanvasWidget) -> gtk::MenuButton {
+        let options_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        options_box.set_margin_start(6);
+        options_box.set_margin_end(6);
+        options_box.set_margin_top(6);
+        options_box.set_margin_bottom(6);
+
+        // Arrow options
+        let arrow_label = gtk::Label::builder()
+            .label("Arrow")
+            .halign(gtk::Align::Start)
+            .css_classes(["heading"])
+            .build();
+
+        let arrow_dropdown = gtk::DropDown::from_strings(&["Open Head", "Filled Head", "Double-Headed", "Tapered", "Curved"]);
+        arrow_dropdown.set_tooltip_text(Some("Arrow style"));
+        let canvas_for_arrow_style = canvas.clone();
+        let arrow_handler = arrow_dropdown.connect_selected_notify(move |dropdown| {
+            let style = match dropdown.selected() {
+                1 => ArrowStyle::Filled,
+                2 => ArrowStyle::Double,
+                3 => ArrowStyle::Tapered,
+                4 => ArrowStyle::Curved,
+                _ => ArrowStyle::Open,
+            };
+            canvas_for_arrow_style.set_arrow_style(style);
+        });
+
+        options_box.append(&arrow_label);
+        options_box.append(&arrow_dropdown);
+
+        // Line and shape options
+        let shape_label = gtk::Label::builder()
+            .label("Lines and Shapes")
+            .halign(gtk::Align::Start)
+            .css_classes(["heading"])
+            .margin_top(6)
+            .build();
+
+        let dash_dropdown = gtk::DropDown::from_strings(&["Solid", "Dashed", "Dotted", "Dash-Dot"]);
+        dash_dropdown.set_tooltip_text(Some("Line style for lines, rectangles and ellipses"));
+        let canvas_for_dash = canvas.clone();
+        let dash_handler = dash_dropdown.connect_selected_notify(move |dropdown| {
+            let dash = match dropdown.selected() {
+                1 => DashStyle::Dashed,
+                2 => DashStyle::Dotted,
+                3 => DashStyle::DashDot,
+                _ => DashStyle::Solid,
+            };
+            canvas_for_dash.set_dash_style(dash);
+        });
+
+        let stroke_spin = gtk::SpinButton::with_range(1.0, 32.0, 1.0);
+        stroke_spin.set_value(canvas.stroke_width());
+        stroke_spin.set_tooltip_text(Some("Stroke width"));
+        let canvas_for_stroke = canvas.clone();
+        let stroke_handler = stroke_spin.connect_value_changed(move |spin| {
+            canvas_for_stroke.set_stroke_width(spin.value());
+        });
+
+        let fill_check = gtk::CheckButton::builder()
+            .label("Fill rectangles and ellipses")
+            .build();
+        let canvas_for_fill = canvas.clone();
+        let fill_handler = fill_check.connect_toggled(move |check| {
+            canvas_for_fill.set_filled(check.is_active());
+        });
+
+        let radius_spin = gtk::SpinButton::with_range(0.0, 64.0, 2.0);
+        radius_spin.set_value(0.0);
+        radius_spin.set_tooltip_text(Some("Corner radius for rectangles and highlights"));
+        let canvas_for_radius = canvas.clone();
+        let radius_handler = radius_spin.connect_value_changed(move |spin| {
+            canvas_for_radius.set_corner_radius(spin.value());
+        });
+
+        let shape_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        shape_row.append(&stroke_spin);
+        shape_row.append(&dash_dropdown);
+        shape_row.append(&radius_spin);
+
+        options_box.append(&shape_label);
+        options_box.append(&shape_row);
+        options_box.append(&fill_check);
+
+        // Spotlight options
+        let spotlight_label = gtk::Label::builder()
+            .label("Spotlight")
+            .halign(gtk::Align::Start)
+            .css_classes(["heading"])
+            .margin_top(6)
+            .build();
+
+        let shape_dropdown = gtk::DropDown::from_strings(&["Rectangle", "Ellipse"]);
+        shape_dropdown.set_tooltip_text(Some("Spotlight shape"));
+        let canvas_for_shape = canvas.clone();
+        shape_dropdown.connect_selected_notify(move |dropdown| {
+            let shape = match dropdown.selected() {
+                1 => SpotlightShape::Ellipse,
+                _ => SpotlightShape::Rectangle,
+            };
+            canvas_for_shape.set_spotlight_shape(shape);
+        });
+
+        let effect_dropdown = gtk::DropDown::from_strings(&["Dim", "Blur"]);
+        effect_dropdown.set_tooltip_text(Some("What happens outside the spotlight"));
+        let canvas_for_effect = canvas.clone();
+        effect_dropdown.connect_selected_notify(move |dropdown| {
+            let effect = match dropdown.selected() {
+                1 => SpotlightEffect::Blur,
+                _ => SpotlightEffect::Dim,
+            };
+            canvas_for_effect.set_spotlight_effect(effect);
+        });
+
+        let spotlight_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        spotlight_row.append(&shape_dropdown);
+        spotlight_row.append(&effect_dropdown);
+
+        let opacity_scale = gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.0, 0.9, 0.05);
+        opacity_scale.set_value(0.6);
+        opacity_scale.set_tooltip_text(Some("Spotlight dimming"));
+        let canvas_for_opacity = canvas.clone();
+        opacity_scale.connect_value_changed(move |scale| {
+            canvas_for_opacity.set_spotlight_opacity(scale.value());
+        });
+
+        options_box.append(&spotlight_label);
+        options_box.append(&spotlight_row);
+        options_box.append(&opacity_scale);
+
+        // Magnifier options
+        let magnify_label = gtk::Label::builder()
+            .label("Magnifier")
+            .halign(gtk::Align::Start)
+            .css_classes(["heading"])
+            .margin_top(6)
+            .build();
+
+        let zoom_spin = gtk::SpinButton::with_range(1.5, 8.0, 0.5);
+        zoom_spin.set_value(2.0);
+        zoom_spin.set_tooltip_text(Some("Magnifier zoom factor"));
+        let canvas_for_zoom = canvas.clone();
+        zoom_spin.connect_value_changed(move |spin| {
+            canvas_for_zoom.set_magnify_zoom(spin.value());
+        });
+
+        let callout_dropdown = gtk::DropDown::from_strings(&["Circle", "Rounded Rectangle"]);
+        callout_dropdown.set_tooltip_text(Some("Magnifier callout shape"));
+        let canvas_for_callout = canvas.clone();
+        callout_dropdown.connect_selected_notify(move |dropdown| {
+            let shape = match dropdown.selected() {
+                1 => MagnifyShape::RoundedRect,
+                _ => MagnifyShape::Circle,
+            };
+            canvas_for_callout.set_magnify_shape(shape);
+        });
+
+        let magnify_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        magnify_row.append(&zoom_spin);
+        magnify_row.append(&callout_dropdown);
+
+        options_box.append(&magnify_label);
+        options_box.append(&magnify_row);
+
+        // Callout options
+        let callout_label = gtk::Label::builder()
+            .label("Callout")
+            .halign(gtk::Align::Start)
+            .css_classes(["heading"])
+            .margin_top(6)
+            .build();
+
+        let style_dropdown = gtk::DropDown::from_strings(&["Speech Bubble", "Box", "Tag"]);
+        style_dropdown.set_tooltip_text(Some("Callout style"));
+        let canvas_for_style = canvas.clone();
+        style_dropdown.connect_selected_notify(move |dropdown| {
+            let style = match dropdown.selected() {
+                1 => CalloutStyle::Box,
+                2 => CalloutStyle::Tag,
+                _ => CalloutStyle::Bubble,
+            };
+            canvas_for_style.set_callout_style(style);
+        });
+
+        let font_spin = gtk::SpinButton::with_range(8.0, 96.0, 1.0);
+        font_spin.set_value(16.0);
+        font_spin.set_tooltip_text(Some("Font size"));
+        let canvas_for_font = canvas.clone();
+        let font_handler = font_spin.connect_value_changed(move |spin| {
+            canvas_for_font.set_font_size(spin.value());
+        });
+
+        let callout_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        callout_row.append(&style_dropdown);
+        callout_row.append(&font_spin);
+
+        options_box.append(&callout_label);
+        options_box.append(&callout_row);
+
+        // Show the settings a preset brings in. The widgets' own handlers are
+        // blocked meanwhile: they'd restyle the selected annotation, and a
+        // preset only sets the style for what's drawn next.
+        canvas.connect_style_changed(glib::clone!(
+            #[weak]
+            arrow_dropdown,
+            #[weak]
+            dash_dropdown,
+            #[weak]
+            stroke_spin,
+            #[weak]
+            radius_spin,
+            #[weak]
+            fill_check,
+            #[weak]
+            font_spin,
+            move |canvas| {
+                arrow_dropdown.block_signal(&arrow_handler);
+                dash_dropdown.block_signal(&dash_handler);
+                stroke_spin.block_signal(&stroke_handler);
+                radius_spin.block_signal(&radius_handler);
+                fill_check.block_signal(&fill_handler);
+                font_spin.block_signal(&font_handler);
+
+                arrow_dropdown.set_selected(match canvas.arrow_style() {
+                    ArrowStyle::Open => 0,
+                    ArrowStyle::Filled => 1,
+                    ArrowStyle::Double => 2,
+                    ArrowStyle::Tapered => 3,
+                    ArrowStyle::Curved => 4,
+                });
+                dash_dropdown.set_selected(match canvas.dash_style() {
+                    DashStyle::Solid => 0,
+                    DashStyle::Dashed => 1,
+                    DashStyle::Dotted => 2,
+                    DashStyle::DashDot => 3,
+                });
+                stroke_spin.set_value(canvas.stroke_width());
+                radius_spin.set_value(canvas.corner_radius());
+                fill_check.set_active(canvas.filled());
+                font_spin.set_value(canvas.font_size());
+
+                arrow_dropdown.unblock_signal(&arrow_handler);
+                dash_dropdown.unblock_signal(&dash_handler);
+                stroke_spin.unblock_signal(&stroke_handler);
+                radius_spin.unblock_signal(&radius_handler);
+                fill_check.unblock_signal(&fill_handler);
+                font_spin.unblock_signal(&font_handler);
+            }
+        ));
+
+        let popover = gtk::Popover::new();
+        popover.set_child(Some(&options_box));
+
+        gtk::MenuButton::builder()
+            .icon_name("emblem-system-symbolic")
+            .tooltip_text("Tool options")
+            .popover(&popover)
+            .build()
+    }
+
+    /// Style presets menu: apply a preset, save the current style as one,
+    /// and share presets with others as a file
+    fn create_presets_button(&self, canvas: &CanvasWidget) -> gtk::MenuButton {
+        let presets_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        presets_box.set_margin_start(6);
+        presets_box.set_margin_end(6);
+        presets_box.set_margin_top(6);
+        presets_box.set_margin_bottom(6);
+
+        let list_box = gtk::Box::new(gtk::Orientation::Vertical, 2);
+
+        let save_btn = gtk::Button::with_label("Save Current Style…");
+        let export_btn = gtk::Button::with_label("Export…");
+        let import_btn = gtk::Button::with_label("Import…");
+        export_btn.set_tooltip_text(Some("Save presets to a file to share them"));
+        import_btn.set_tooltip_text(Some("Add presets from a file, replacing ones with the same name"));
+
+        let file_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        file_row.set_homogeneous(true);
+        file_row.append(&export_btn);
+        file_row.append(&import_btn);
+
+        presets_box.append(&list_box);
+        presets_box.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
+        presets_box.append(&save_btn);
+        presets_box.append(&file_row);
+
+        let popover = gtk::Popover::new();
+        popover.set_child(Some(&presets_box));
+
+        // The list is rebuilt every time so it shows presets saved since
+        popover.connect_show(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            #[weak]
+            list_box,
+            #[weak]
+            popover,
+            move |_| {
+                window.populate_presets(&list_box, &popover);
+            }
+        ));
+
+        save_btn.connect_clicked(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            #[weak]
+            canvas,
+            #[weak]
+            popover,
+            move |_| {
+                popover.popdown();
+                window.save_current_style(&canvas);
+            }
+        ));
+
+        export_btn.connect_clicked(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            #[weak]
+            popover,
+            move |_| {
+                popover.popdown();
+                window.export_presets();
+            }
+        ));
+
+        import_btn.connect_clicked(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            #[weak]
+            popover,
+            move |_| {
+                popover.popdown();
+                window.import_presets();
+            }
+        ));
+
+        gtk::MenuButton::builder()
+            .icon_name("starred-symbolic")
+            .tooltip_text("Style presets (1-9)")
+            .popover(&popover)
+            .build()
+    }
+
+    fn populate_presets(&self, list_box: &gtk::Box, popover: &gtk::Popover) {
+        while let Some(child) = list_box.first_child() {
+            list_box.remove(&child);
+        }
+
+        for (index, preset) in self.imp().presets.borrow().iter().enumerate() {
+            let label = if index < 9 {
+                format!("{}  {}", index + 1, preset.name)
+            } else {
+                preset.name.clone()
+            };
+
+            let apply_btn = gtk::Button::builder()
+                .label(label)
+                .hexpand(true)
+                .build();
+            apply_btn.add_css_class("flat");
+            if let Some(child) = apply_btn.child().and_downcast::<gtk::Label>() {
+                child.set_xalign(0.0);
+            }
+            apply_btn.connect_clicked(glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                #[weak]
+                popover,
+                move |_| {
+                    popover.popdown();
+                    window.apply_preset(index);
+                }
+            ));
+
+            let delete_btn = gtk::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text("Delete preset")
+                .build();
+            delete_btn.add_css_class("flat");
+            delete_btn.connect_clicked(glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                #[weak]
+                list_box,
+                #[weak]
+                popover,
+                move |_| {
+                    window.imp().presets.borrow_mut().remove(index);
+                    window.store_presets();
+                    window.populate_presets(&list_box, &popover);
+                }
+            ));
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 2);
+            row.append(&apply_btn);
+            row.append(&delete_btn);
+            list_box.append(&row);
+        }
+
+        if self.imp().presets.borrow().is_empty() {
+            let empty = gtk::Label::new(Some("No presets yet"));
+            empty.add_css_class("dim-label");
+            list_box.append(&empty);
+        }
+    }
+
+    /// Apply the preset at `index`, counting from zero
+    pub fn apply_preset(&self, index: usize) {
+        let Some(preset) = self.imp().presets.borrow().get(index).cloned() else { return };
+        if let Some(canvas) = self.canvas() {
+            canvas.apply_preset(&preset);
+        }
+    }
+
+    fn on_key_pressed(&self, keyval: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+        let modifiers = gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::ALT_MASK | gdk::ModifierType::SUPER_MASK;
+        if state.intersects(modifiers) {
+            return glib::Propagation::Proceed;
+        }
+
+        // Switching tools mid-drag would leave the drag half done
+        if self.canvas().is_some_and(|canvas| canvas.is_drawing()) {
+            return glib::Propagation::Proceed;
+        }
+
+        let Some(key) = keyval.to_unicode() else { return glib::Propagation::Proceed };
+        if let Some(digit @ 1..=9) = key.to_digit(10) {
+            self.apply_preset(digit as usize - 1);
+            return glib::Propagation::Stop;
+        }
+
+        match (Tool::from_shortcut(key), self.canvas()) {
+            // The text tool does nothing without Tesseract
+            (Some(Tool::Text), _) if !ocr::TesseractBackend::is_available() => glib::Propagation::Proceed,
+            (Some(tool), Some(canvas)) => {
+                canvas.set_tool(tool);
+                glib::Propagation::Stop
+            }
+            _ => glib::Propagation::Proceed,
+        }
+    }
+
+    fn store_presets(&self) {
+        if let Err(e) = settings::save_presets(&self.imp().presets.borrow()) {
+            eprintln!("Failed to save presets: {}", e);
+        }
+    }
+
+    fn save_current_style(&self, canvas: &CanvasWidget) {
+        let dialog = adw::MessageDialog::new(Some(self), Some("Save Style Preset"), None);
+
+        let entry = gtk::Entry::builder()
+            .placeholder_text("Preset name")
+            .activates_default(true)
+            .build();
+        dialog.set_extra_child(Some(&entry));
+        dialog.add_responses(&[("cancel", "_Cancel"), ("save", "_Save")]);
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("save"));
+        dialog.set_close_response("cancel");
+
+        dialog.connect_response(
+            None,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                #[weak]
+                canvas,
+                #[weak]
+                entry,
+                move |_, response| {
+                    let name = entry.text().trim().to_string();
+                    if response != "save" || name.is_empty() {
+                        return;
+                    }
+
+                    let preset = canvas.current_style(&name);
+                    let mut presets = window.imp().presets.borrow_mut();
+                    match presets.iter_mut().find(|p| p.name == name) {
+                        Some(existing) => *existing = preset,
+                        None => presets.push(preset),
+                    }
+                    drop(presets);
+                    window.store_presets();
+                }
+            ),
+        );
+
+        dialog.present();
+        entry.grab_focus();
+    }
+
+    fn create_stamps_button(&self, canvas: &CanvasWidget) -> gtk::MenuButton {
+        let stamps_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        stamps_box.set_margin_start(6);
+        stamps_box.set_margin_end(6);
+        stamps_box.set_margin_top(6);
+        stamps_box.set_margin_bottom(6);
+
+        let popover = gtk::Popover::new();
+
+        // Built-in symbols, previewed in the current color
+        let symbols_row = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        for symbol in StampSymbol::ALL {
+            let preview = gtk::DrawingArea::builder()
+                .width_request(24)
+                .height_request(24)
+                .build();
+            let canvas_for_preview = canvas.clone();
+            preview.set_draw_func(move |_, cr, width, height| {
+                let size = width.min(height) as f64;
+                cr.scale(size, size);
+                symbol.draw_unit(cr, &canvas_for_preview.current_color());
+            });
+
+            let btn = gtk::Button::builder()
+                .child(&preview)
+                .tooltip_text(symbol.name())
+                .build();
+            btn.add_css_class("flat");
+            btn.connect_clicked(glib::clone!(
+                #[weak]
+                canvas,
+                #[weak]
+                popover,
+                move |_| {
+                    canvas.set_stamp(StampContent::Symbol(symbol));
+                    popover.popdown();
+                }
+            ));
+            symbols_row.append(&btn);
+        }
+
+        let emoji_chooser = gtk::EmojiChooser::new();
+        emoji_chooser.connect_emoji_picked(glib::clone!(
+            #[weak]
+            canvas,
+            #[weak]
+            popover,
+            move |_, emoji| {
+                canvas.set_stamp(StampContent::Emoji(emoji.to_string()));
+                popover.popdown();
+            }
+        ));
+        let emoji_btn = gtk::MenuButton::builder()
+            .label("Emoji…")
+            .popover(&emoji_chooser)
+            .build();
+
+        let image_btn = gtk::Button::with_label("Image…");
+        image_btn.set_tooltip_text(Some("Use a PNG or SVG file, such as a logo"));
+        image_btn.connect_clicked(glib::clone!(
+            #[weak(rename_to = window)]
+            self,
+            #[weak]
+            canvas,
+            #[weak]
+            popover,
+            move |_| {
+                popover.popdown();
+                window.choose_stamp_image(&canvas);
+            }
+        ));
+
+        let more_row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        more_row.set_homogeneous(true);
+        more_row.append(&emoji_btn);
+        more_row.append(&image_btn);
+
+        stamps_box.append(&symbols_row);
+        stamps_box.append(&more_row);
+
+        // Previews follow the annotation color
+        popover.connect_show(glib::clone!(
+            #[weak]
+            symbols_row,
+            move |_| {
+                let mut child = symbols_row.first_child();
+                while let Some(btn) = child {
+                    if let Some(preview) = btn.first_child() {
+                        preview.queue_draw();
+                    }
+                    child = btn.next_sibling();
+                }
+            }
+        ));
+        popover.set_child(Some(&stamps_box));
+
+        gtk::MenuButton::builder()
+            .icon_name("pan-down-symbolic")
+            .tooltip_text("Choose stamp")
+            .popover(&popover)
+            .build()
+    }
+
+    fn choose_stamp_image(&self, canvas: &CanvasWidget) {
+        let filter = gtk::FileFilter::new();
+        filter.add_mime_type("image/png");
+        filter.add_mime_type("image/svg+xml");
+        filter.set_name(Some("PNG and SVG Images"));
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+
+        let dialog = gtk::FileDialog::builder()
+            .title("Choose Stamp Image")
+            .modal(true)
+            .filters(&filters)
+            .build();
+
+        dialog.open(
+            Some(self),
+            gio::Cancellable::NONE,
+            glib::clone!(
+                #[weak]
+                canvas,
+                move |result: Result<gio::File, glib::Error>| {
+                    let Some(path) = result.ok().and_then(|file| file.path()) else { return };
+                    match std::fs::read(&path) {
+                        Ok(data) => canvas.set_stamp(StampContent::Image(StampImage::new(data))),
+                        Err(e) => eprintln!("Failed to read {}: {}", path.display(), e),
+                    }
+                }
+            ),
+        );
+    }
+
+    fn presets_file_filters() -> gio::ListStore {
+        let filter = gtk::FileFilter::new();
+        filter.add_pattern("*.json");
+        filter.set_name(Some("Style Presets"));
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+        filters
+    }
+
+    fn export_presets(&self) {
+        let dialog = gtk::FileDialog::builder()
+            .title("Export Style Presets")
+            .modal(true)
+            .initial_name("gnome-shot-presets.json")
+            .filters(&Self::presets_file_filters())
+            .build();
+
+        let presets = self.imp().presets.borrow().clone();
+        dialog.save(Some(self), gio::Cancellable::NONE, move |result: Result<gio::File, glib::Error>| {
+            if let Some(path) = result.ok().and_then(|file| file.path()) {
+                match settings::export_presets(&path, &presets) {
+                    Ok(()) => eprintln!("Exported presets to: {}", path.display()),
+                    Err(e) => eprintln!("Failed to export presets: {}", e),
+                }
+            }
+        });
+    }
+
+    fn import_presets(&self) {
+        let dialog = gtk::FileDialog::builder()
+            .title("Import Style Presets")
+            .modal(true)
+            .filters(&Self::presets_file_filters())
+            .build();
+
+        dialog.open(
+            Some(self),
+            gio::Cancellable::NONE,
+            glib::clone!(
+                #[weak(rename_to = window)]
+                self,
+                move |result: Result<gio::File, glib::Error>| {
+                    let Some(path) = result.ok().and_then(|file| file.path()) else { return };
+                    let imported = match settings::import_presets(&path) {
+                        Ok(imported) => imported,
+                        Err(e) => {
+                            eprintln!("Failed to import presets: {}", e);
+                            return;
+                        }
+                    };
+
+                    let mut presets = window.imp().presets.borrow_mut();
+                    for preset in imported {
+                        match presets.iter_mut().find(|p| p.name == preset.name) {
+                            Some(existing) => *existing = preset,
+                            None => presets.push(preset),
+                        }
+                    }
+                    drop(presets);
+                    window.store_presets();
+                }
+            ),
+        );
+    }
+
     pub fn canvas(&self) -> Option<CanvasWidget> {
         self.imp().canvas.borrow().clone()
     }
+
+    /// Deal with the raw capture file now that the screenshot is saved as `path`,
+    /// as chosen by the `raw_files` setting
+    pub fn screenshot_saved(&self, path: &std::path::Path) {
+        let Some(canvas) = self.canvas() else { return };
+        let Some(raw) = canvas.source_path() else { return };
+
+        match capture::release_raw_file(&raw, path, settings::raw_file_policy()) {
+            Ok(released) => {
+                if let Some(app) = self.application().and_downcast::<GnomeShotApplication>() {
+                    app.capture_moved(&raw, released.as_deref());
+                }
+                canvas.set_source_path(released);
+            }
+            Err(e) => eprintln!("Failed to clean up {}: {}", raw.display(), e),
+        }
+    }
+}
+
+/// Rebuild the history list: the original screenshot first, then every step,
+/// with undone steps dimmed and the current state selected
+fn populate_history_list(list: &gtk::ListBox, canvas: &CanvasWidget) {
+    while let Some(child) = list.first_child() {
+        list.remove(&child);
+    }
+
+    let add_row = |icon_name: &str, description: &str, undone: bool| {
+        let row_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        row_box.append(&gtk::Image::from_icon_name(icon_name));
+        row_box.append(&gtk::Label::builder().label(description).xalign(0.0).build());
+
+        let row = gtk::ListBoxRow::builder().child(&row_box).build();
+        if undone {
+            row.add_css_class("dim-label");
+        }
+        list.append(&row);
+    };
+
+    // Once old steps are dropped, the first row only goes back as far as the history does
+    if canvas.history_has_dropped_steps() {
+        add_row("document-open-recent-symbolic", "Oldest kept step", false);
+    } else {
+        add_row("image-x-generic-symbolic", "Original screenshot", false);
+    }
+    for entry in canvas.history_entries() {
+        add_row(entry.icon_name, &entry.description, entry.undone);
+    }
+
+    list.select_row(list.row_at_index(canvas.history_position() as i32).as_ref());
+}
+
+/// What a row of the layers list shows
+#[derive(Debug, Clone, PartialEq)]
+struct LayerRow {
+    name: String,
+    /// Kind of annotation, shown under a custom name
+    kind: Option<&'static str>,
+    color: Option<gdk::RGBA>,
+    visible: bool,
+    locked: bool,
+}
+
+/// Rows for the canvas's layers, bottom first
+fn layer_rows(canvas: &CanvasWidget) -> Vec<LayerRow> {
+    canvas
+        .layers()
+        .iter()
+        .map(|layer| LayerRow {
+            name: layer.display_name().to_string(),
+            kind: layer.name.is_some().then(|| layer.annotation.name()),
+            color: layer.annotation.color(),
+            visible: layer.visible,
+            locked: layer.locked,
+        })
+        .collect()
+}
+
+/// Index of the layer whose row holds `widget`, from where the row is now
+///
+/// Rows are listed topmost first, so the first row is the last layer.
+fn layer_index_of(widget: &impl IsA<gtk::Widget>, canvas: &CanvasWidget) -> Option<usize> {
+    let row = widget.ancestor(gtk::ListBoxRow::static_type()).and_downcast::<gtk::ListBoxRow>()?;
+    let position = usize::try_from(row.index()).ok()?;
+    canvas.layer_count().checked_sub(position + 1)
+}
+
+/// Rebuild the layers list from `rows`, topmost layer first
+fn populate_layers_list(list: &gtk::ListBox, canvas: &CanvasWidget, rows: &[LayerRow]) {
+    while let Some(child) = list.first_child() {
+        list.remove(&child);
+    }
+
+    for layer in rows.iter().rev() {
+        // Color swatch, crossed out for annotations without a color
+        let color = layer.color;
+        let swatch = gtk::DrawingArea::builder()
+            .content_width(14)
+            .content_height(14)
+            .valign(gtk::Align::Center)
+            .build();
+        swatch.set_draw_func(move |_, cr, width, height| {
+            let (w, h) = (width as f64, height as f64);
+            cr.rectangle(0.5, 0.5, w - 1.0, h - 1.0);
+            match color {
+                Some(color) => {
+                    cr.set_source_rgba(color.red() as f64, color.green() as f64, color.blue() as f64, color.alpha() as f64);
+                    let _ = cr.fill_preserve();
+                    cr.set_source_rgba(0.0, 0.0, 0.0, 0.3);
+                }
+                None => {
+                    cr.move_to(w - 0.5, 0.5);
+                    cr.line_to(0.5, h - 0.5);
+                    cr.set_source_rgba(0.5, 0.5, 0.5, 1.0);
+                }
+            }
+            cr.set_line_width(1.0);
+            let _ = cr.stroke();
+        });
+
+        let name = gtk::EditableLabel::new(&layer.name);
+        name.set_tooltip_text(Some("Double-click to rename"));
+        name.connect_notify_local(Some("editing"), glib::clone!(
+            #[weak]
+            canvas,
+            move |label, _| {
+                if let Some(index) = layer_index_of(label, &canvas).filter(|_| !label.is_editing()) {
+                    canvas.rename_layer(index, &label.text());
+                }
+            }
+        ));
+
+        // Show the kind of annotation under a custom name
+        let labels = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        labels.set_hexpand(true);
+        labels.set_valign(gtk::Align::Center);
+        labels.append(&name);
+        if let Some(kind) = layer.kind {
+            let kind = gtk::Label::builder().label(kind).xalign(0.0).build();
+            kind.add_css_class("caption");
+            kind.add_css_class("dim-label");
+            labels.append(&kind);
+        }
+
+        let visible_btn = gtk::ToggleButton::builder()
+            .icon_name(if layer.visible { "view-reveal-symbolic" } else { "view-conceal-symbolic" })
+            .tooltip_text("Show or hide")
+            .active(layer.visible)
+            .valign(gtk::Align::Center)
+            .build();
+        visible_btn.add_css_class("flat");
+        visible_btn.connect_toggled(glib::clone!(
+            #[weak]
+            canvas,
+            move |btn| {
+                if let Some(index) = layer_index_of(btn, &canvas) {
+                    canvas.set_layer_visible(index, btn.is_active());
+                }
+            }
+        ));
+
+        let lock_btn = gtk::ToggleButton::builder()
+            .icon_name(if layer.locked { "changes-prevent-symbolic" } else { "changes-allow-symbolic" })
+            .tooltip_text("Lock or unlock")
+            .active(layer.locked)
+            .valign(gtk::Align::Center)
+            .build();
+        lock_btn.add_css_class("flat");
+        lock_btn.connect_toggled(glib::clone!(
+            #[weak]
+            canvas,
+            move |btn| {
+                if let Some(index) = layer_index_of(btn, &canvas) {
+                    canvas.set_layer_locked(index, btn.is_active());
+                }
+            }
+        ));
+
+        let row_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        row_box.append(&swatch);
+        row_box.append(&labels);
+        row_box.append(&visible_btn);
+        row_box.append(&lock_btn);
+
+        let row = gtk::ListBoxRow::builder().child(&row_box).build();
+        if !layer.visible {
+            row.add_css_class("dim-label");
+        }
+        list.append(&row);
+    }
+
+    select_layer_row(list, canvas);
+}
+
+/// Select the row of the canvas's selected layer, if any
+fn select_layer_row(list: &gtk::ListBox, canvas: &CanvasWidget) {
+    let count = canvas.layer_count();
+    let row = canvas
+        .selected()
+        .filter(|&index| index < count)
+        .and_then(|index| list.row_at_index((count - 1 - index) as i32));
+    match row {
+        Some(row) => list.select_row(Some(&row)),
+        None => list.unselect_all(),
+    }
+}
+
+/// How long frame changes settle before they're saved and previewed
+const FRAME_UPDATE_DELAY: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Applies frame changes to the canvas right away, but saves them and redraws
+/// the preview only once they settle, so holding a spin button doesn't write
+/// the settings and render the whole image on every step
+struct FrameUpdater {
+    canvas: glib::WeakRef<CanvasWidget>,
+    preview: glib::WeakRef<gtk::Picture>,
+    pending: RefCell<Option<glib::SourceId>>,
+    /// Whether the settings changed since they were last saved
+    unsaved: Cell<bool>,
+}
+
+impl FrameUpdater {
+    fn change(self: &Rc<Self>, change: impl FnOnce(&mut FrameSettings)) {
+        let Some(canvas) = self.canvas.upgrade() else { return };
+        let mut frame = canvas.frame();
+        change(&mut frame);
+        canvas.set_frame(frame);
+        self.unsaved.set(true);
+        self.schedule();
+    }
+
+    /// Save and redraw the preview after `FRAME_UPDATE_DELAY`, restarting the wait if one is pending
+    fn schedule(self: &Rc<Self>) {
+        if let Some(source) = self.pending.take() {
+            source.remove();
+        }
+        let weak = Rc::downgrade(self);
+        let source = glib::timeout_add_local_once(FRAME_UPDATE_DELAY, move || {
+            let Some(updater) = weak.upgrade() else { return };
+            // Already finished, so not to be removed
+            updater.pending.take();
+            updater.update();
+        });
+        self.pending.replace(Some(source));
+    }
+
+    fn update(&self) {
+        let Some(canvas) = self.canvas.upgrade() else { return };
+        if self.unsaved.replace(false) {
+            if let Err(e) = settings::save_frame(&canvas.frame()) {
+                eprintln!("Failed to save frame settings: {}", e);
+            }
+        }
+        // Rendering the preview is costly, so skip it while the panel is closed
+        if let Some(preview) = self.preview.upgrade().filter(|preview| preview.is_mapped()) {
+            preview.set_paintable(canvas.export_to_texture().as_ref());
+        }
+    }
+}
+
+/// Capture button with the other ways to start in its menu
+fn create_capture_button() -> adw::SplitButton {
+    let window_section = gio::Menu::new();
+    window_section.append(Some("Capture Window"), Some("app.capture-window"));
+    window_section.append(Some("Window Shadow"), Some("app.window-shadow"));
+    window_section.append(Some("Rounded Corners"), Some("app.window-rounded-corners"));
+
+    let other_section = gio::Menu::new();
+    other_section.append(Some("Select Region…"), Some("app.select-region"));
+
+    // Select a region limited to a capture preset's size or aspect ratio
+    let presets_menu = gio::Menu::new();
+    for preset in settings::load_capture_presets() {
+        if preset.constraint == capture::Constraint::Free {
+            continue;
+        }
+        let item = gio::MenuItem::new(Some(&preset.label()), None);
+        item.set_action_and_target_value(Some("app.capture-preset"), Some(&preset.name.to_variant()));
+        presets_menu.append_item(&item);
+    }
+    other_section.append_submenu(Some("Capture with Preset"), &presets_menu);
+    // Disabled until a region was picked with the selector, so say so
+    let previous_label = match settings::last_area() {
+        Some(_) => "Capture Previous Area",
+        None => "Capture Previous Area (Select a Region First)",
+    };
+    other_section.append(Some(previous_label), Some("app.capture-previous"));
+
+    // One entry per monitor when there's more than one
+    let monitors = capture::monitors();
+    if monitors.len() > 1 {
+        let monitors_menu = gio::Menu::new();
+        for (index, monitor) in monitors.iter().enumerate() {
+            let item = gio::MenuItem::new(Some(&capture::monitor_name(monitor)), None);
+            item.set_action_and_target_value(Some("app.capture-monitor"), Some(&(index as i32).to_variant()));
+            monitors_menu.append_item(&item);
+        }
+        other_section.append_submenu(Some("Capture Monitor"), &monitors_menu);
+    }
+    other_section.append(Some("Scrolling Capture…"), Some("app.capture-scrolling"));
+    other_section.append(Some("Stitch Images…"), Some("app.stitch-files"));
+    other_section.append(Some("Combine Images…"), Some("app.combine"));
+    other_section.append(Some("Record Screen…"), Some("app.record"));
+
+    let menu = gio::Menu::new();
+    menu.append_section(None, &window_section);
+    menu.append_section(None, &other_section);
+
+    let capture_btn = adw::SplitButton::builder()
+        .label("Capture")
+        .action_name("app.capture")
+        .menu_model(&menu)
+        .build();
+    capture_btn.add_css_class("suggested-action");
+    capture_btn
 }